@@ -1,6 +1,14 @@
 mod opts;
 
-use std::process;
+use std::{
+	env, fs,
+	io::{self, BufRead, Write},
+	net::SocketAddr,
+	path::{Path, PathBuf},
+	process,
+	sync::Arc,
+	time::Duration,
+};
 
 use clap::Parser;
 use clap::{crate_authors, crate_name, crate_version};
@@ -11,17 +19,66 @@ use subrpc_core::*;
 use webbrowser::{Browser, BrowserOptions};
 
 /// Main entry point of the `subwasm` cli
-fn main() -> color_eyre::Result<()> {
+#[tokio::main]
+async fn main() -> color_eyre::Result<()> {
 	env_logger::Builder::from_env(Env::default().default_filter_or("none")).init();
 	let opts: Opts = Opts::parse();
 	debug!("opts: {:?}", opts);
 
-	let local_data_file = &LocalData::get_default_file();
-	debug!("Using local data from: {}", local_data_file.display());
-	let mut db = LocalData::init(local_data_file, false)
-		.expect("Should be able to load local data")
-		.load()
-		.expect("Should load properly");
+	let paths = Paths::new()?;
+	match paths.migrate_legacy() {
+		Ok(moved) if !moved.is_empty() => {
+			eprintln!("Your files have moved to their new location:");
+			moved.iter().for_each(|file| eprintln!("  {}", file.display()));
+		}
+		Ok(_) => {}
+		Err(e) => warn!("Failed moving your files to their new location: {e}"),
+	}
+
+	let config_file = &paths.config_file();
+	let config = Config::load(config_file).unwrap_or_else(|e| {
+		eprintln!("Your config at {} is invalid, using the defaults: {e}", config_file.display());
+		Config::default()
+	});
+	debug!("config: {:?}", config);
+
+	let (backend, storage) = match &opts.data {
+		Some(file) => {
+			let backend = StorageBackend::for_file(file).unwrap_or(config.storage);
+			(backend, backend.open(file)?)
+		}
+		None => {
+			let storage = config.storage.open(&config.storage.default_file()?)?;
+			import_json_data(&storage)?;
+			(config.storage, storage)
+		}
+	};
+	debug!("Using local data from: {} ({backend})", storage.location());
+	if backend == StorageBackend::Memory {
+		warn!("The memory storage does not keep the local data between runs");
+	}
+	// Commands modifying the local data hold the lock until they exit
+	let _lock = match storage.file() {
+		Some(file) if opts.subcmd.modifies_data() => Some(LocalData::lock_with(file, |holder| {
+			let holder = holder.map(|pid| format!("process {pid}")).unwrap_or(String::from("another process"));
+			eprintln!("Waiting for {holder} to release the lock on {}…", file.display());
+		})?),
+		_ => None,
+	};
+	let mut db = match LocalData::init_with(storage.clone(), false) {
+		Ok(db) => db,
+		Err(e @ Error::CorruptData { .. }) if !matches!(&opts.subcmd, SubCommand::System(_)) => {
+			eprintln!("{e}\nRun `subrpc system init` to start from scratch.");
+			process::exit(1);
+		}
+		Err(Error::CorruptData { file, .. }) => {
+			LocalData { file, storage: Some(storage.clone()), ..Default::default() }
+		}
+		Err(e) => return Err(e.into()),
+	};
+	if let Some(backup) = &db.recovered_from {
+		eprintln!("⚠️  Your local data was corrupt, it has been restored from {}", backup.display());
+	}
 
 	match opts.subcmd {
 		SubCommand::Registry(cmd_opts) => {
@@ -50,22 +107,43 @@ fn main() -> color_eyre::Result<()> {
 					debug!("registry/chains");
 					debug!("reg_opts: {:?}", reg_opts);
 
-					let mut chains = db
-						.registries
-						.values()
-						.map(|reg| reg.rpc_endpoints.keys().cloned().collect::<Vec<String>>())
-						.fold(Vec::new(), |mut acc, x| {
-							acc.extend(x);
-							acc
-						});
-					chains.sort();
-					chains.dedup();
+					let mut chains = db.chains();
+					if let Some(pattern) = &reg_opts.pattern {
+						let matches = db.chain_resolver(&config).search(pattern);
+						chains = matches
+							.iter()
+							.filter_map(|m| chains.iter().find(|c| c.chain.name == m.chain).cloned())
+							.collect();
+					}
+
 					if opts.json {
 						let serialized = serde_json::to_string_pretty(&chains).unwrap();
 						println!("{serialized}");
+					} else if reg_opts.names {
+						chains.iter().for_each(|c| {
+							println!("{}", c.chain.name);
+						})
 					} else {
-						chains.iter().for_each(|chain| {
-							println!("{chain}");
+						println!(
+							"{:<28} {:<24} {:<6} {:>5} {:<16} {:>9}",
+							"name", "id", "token", "ss58", "relay", "endpoints"
+						);
+						chains.iter().for_each(|c| {
+							let relay = match (&c.chain.relay, c.chain.para_id) {
+								(Some(relay), Some(para_id)) => format!("{relay}/{para_id}"),
+								(Some(relay), None) => relay.clone(),
+								(None, Some(para_id)) => format!("?/{para_id}"),
+								(None, None) => "-".to_string(),
+							};
+							println!(
+								"{:<28} {:<24} {:<6} {:>5} {:<16} {:>9}",
+								c.chain.name,
+								c.chain.id,
+								c.chain.token.as_deref().unwrap_or("-"),
+								c.chain.ss58_prefix.map(|p| p.to_string()).unwrap_or("-".to_string()),
+								relay,
+								c.endpoints
+							);
 						})
 					}
 				}
@@ -73,7 +151,7 @@ fn main() -> color_eyre::Result<()> {
 				RegistrySubCommand::Add(reg_opts) => {
 					debug!("registry/add");
 					debug!("reg_opts: {:?}", reg_opts);
-					let reg_maybe = Registry::load_from_url(&reg_opts.url);
+					let reg_maybe = Registry::load_from_url(&reg_opts.url).await;
 
 					match reg_maybe {
 						Ok(mut reg) => {
@@ -98,9 +176,17 @@ fn main() -> color_eyre::Result<()> {
 					debug!("Running Update command");
 					debug!("cmd_opts: {:?}", cmd_opts);
 
-					let db = db.refresh();
+					let network_opts = network_options(&config, cmd_opts.concurrency, cmd_opts.timeout);
+					let db = db.refresh_with(&network_opts).await;
 					db.print_summary();
 
+					let changed = db.changed_registries();
+					if changed.is_empty() {
+						println!("No registry changed");
+					} else {
+						println!("Changed: {}", changed.join(", "));
+					}
+
 					let res = db.save();
 					match res {
 						Ok(_db) => {
@@ -112,14 +198,52 @@ fn main() -> color_eyre::Result<()> {
 							process::exit(1);
 						}
 					}
-				} // RegistrySubCommand::Enable(reg_opts) => {
-				  //     debug!("registry/enable");
-				  //     debug!("reg_opts: {:?}", reg_opts);
-				  // }
-				  // RegistrySubCommand::Remove(_) => {
-				  //     debug!("registry/remove");
-
-				  // },
+				}
+
+				RegistrySubCommand::Enable(reg_opts) => {
+					debug!("registry/enable");
+					debug!("reg_opts: {:?}", reg_opts);
+					let res = db.set_registry_enabled(&reg_opts.registry, true).and_then(|db| db.save());
+					print_registry_change(res, &reg_opts.registry, "enabled");
+				}
+
+				RegistrySubCommand::Disable(reg_opts) => {
+					debug!("registry/disable");
+					debug!("reg_opts: {:?}", reg_opts);
+					let res = db.set_registry_enabled(&reg_opts.registry, false).and_then(|db| db.save());
+					print_registry_change(res, &reg_opts.registry, "disabled");
+				}
+
+				RegistrySubCommand::Remove(reg_opts) => {
+					debug!("registry/remove");
+					debug!("reg_opts: {:?}", reg_opts);
+					let res = db.remove_registry(&reg_opts.registry).and_then(|db| db.save());
+					print_registry_change(res, &reg_opts.registry, "removed");
+				}
+
+				RegistrySubCommand::Lint(reg_opts) => {
+					debug!("registry/lint");
+					debug!("reg_opts: {:?}", reg_opts);
+					let json = read_source(&reg_opts.source).await?;
+					let issues = lint(&json);
+
+					if opts.json {
+						println!("{}", serde_json::to_string_pretty(&issues).unwrap());
+					} else if issues.is_empty() {
+						println!("{}: OK", reg_opts.source);
+					} else {
+						issues.iter().for_each(|issue| println!("{}:{issue}", reg_opts.source));
+					}
+
+					let failed = issues.iter().any(|i| reg_opts.strict || i.severity == Severity::Error);
+					process::exit(if failed { 1 } else { 0 });
+				}
+
+				RegistrySubCommand::Schema(reg_opts) => {
+					debug!("registry/schema");
+					debug!("reg_opts: {:?}", reg_opts);
+					print!("{REGISTRY_JSON_SCHEMA}");
+				}
 			}
 		}
 
@@ -133,13 +257,46 @@ fn main() -> color_eyre::Result<()> {
 					println!("Running {} v{}", crate_name!(), crate_version!());
 					println!("{}", crate_authors!(", "));
 
-					println!("local data file: {}", local_data_file.display());
+					println!("config file: {}", config_file.display());
+					println!("local data: {} ({backend})", storage.location());
+					println!("local data version: {} (latest: {DATA_VERSION})", db.version);
 					db.print_summary();
 				}
 				SystemSubCommand::Init(sys_opts) => {
 					debug!("Running Init command");
 					debug!("sys_opts: {:?}", sys_opts);
-					let _db = LocalData::init(local_data_file, true);
+					LocalData::init_with(storage.clone(), true)?;
+					println!("Your local data has been reset in {}", storage.location());
+				}
+				SystemSubCommand::Migrate(sys_opts) => {
+					debug!("Running Migrate command");
+					debug!("sys_opts: {:?}", sys_opts);
+					let report = match storage.migrate(sys_opts.dry_run) {
+						Ok(report) => report,
+						Err(e) => {
+							eprintln!("{e}");
+							process::exit(1);
+						}
+					};
+
+					if opts.json {
+						println!("{}", serde_json::to_string_pretty(&report).unwrap());
+					} else if report.is_empty() {
+						println!("Your local data is up to date (version {})", report.to);
+					} else {
+						report.changes.iter().for_each(|change| println!("- {change}"));
+						if sys_opts.dry_run {
+							println!(
+								"Dry run: your local data would be upgraded from version {} to {}",
+								report.from, report.to
+							);
+						} else {
+							println!(
+								"OK, your local data has been upgraded from version {} to {}",
+								report.from, report.to
+							);
+						}
+					}
 				}
 			}
 		}
@@ -151,35 +308,32 @@ fn main() -> color_eyre::Result<()> {
 				EndpointsSubCommand::Get(ep_opts) => {
 					debug!("endpoints/get");
 					debug!("ep_opts: {:?}", ep_opts);
-					let endpoints = db.get_endpoints(Some(&ep_opts.chain));
-					// let sorted_vec: Vec<_> = endpoints.iter().collect::<Vec<_>>().sort();
+					let chain = resolve_chain(&db, &config, &ep_opts.chain);
+					let query = ep_opts.query.query(Some(&chain));
+					let endpoints = db.best_endpoints_for(&query, ep_opts.max, &config);
 
-					endpoints.iter().enumerate().for_each(|(i, e)| {
-						if let Some(max) = ep_opts.max {
-							if i < max {
-								println!("{}", e.url);
-							}
-						} else {
-							// println!("- {:<20}: {}", e.name, e.url);
-							println!("{}", e.url);
-						}
+					endpoints.iter().for_each(|e| {
+						println!("{}", exported_url(e, &config.secrets));
 					})
 				}
 
 				EndpointsSubCommand::List(ep_opts) => {
 					debug!("endpoints/list");
 					debug!("ep_opts: {:?}", ep_opts);
-					let mut endpoint_url_vec: Vec<EndpointUrl> =
-						db.get_endpoints(None).iter().cloned().map(|ep| ep.url).collect();
-					endpoint_url_vec.sort();
-					endpoint_url_vec.dedup();
+					let query = ep_opts.query.query(None);
+					let endpoints = db.best_endpoints_for(&query, ep_opts.max, &config);
 
 					if opts.json {
+						// The urls keep their placeholders so the secrets are not exposed
+						let endpoint_url_vec: Vec<&EndpointUrl> = endpoints.iter().map(|ep| &ep.url).collect();
 						let serialized = serde_json::to_string_pretty(&endpoint_url_vec).unwrap();
 						println!("{serialized}");
 					} else {
-						endpoint_url_vec.iter().for_each(|e| {
-							println!("{e}");
+						endpoints.iter().for_each(|e| match &e.stats.mismatch {
+							Some(mismatch) => {
+								println!("{}  ⚠️  wrong chain: {mismatch}", exported_url(e, &config.secrets))
+							}
+							None => println!("{}", exported_url(e, &config.secrets)),
 						})
 					}
 				}
@@ -187,46 +341,287 @@ fn main() -> color_eyre::Result<()> {
 					debug!("endpoints/ping");
 					debug!("ep_opts: {:?}", ep_opts);
 
-					db.registries.iter_mut().for_each(|(_name, reg)| {
+					db.registries.values().filter(|reg| reg.enabled).for_each(|reg| {
 						println!("Pinging endpoints from '{}'", reg.name);
-						reg.refresh_stats();
 					});
+					let network_opts = network_options(&config, ep_opts.concurrency, ep_opts.timeout);
+					db.refresh_stats(&network_opts).await;
 
 					match db.save() {
 						Ok(_) => println!("OK"),
 						Err(e) => eprintln!("{e}"),
 					}
 				}
+				EndpointsSubCommand::Probe(ep_opts) => {
+					debug!("endpoints/probe");
+					debug!("ep_opts: {:?}", ep_opts);
+
+					let network_opts = network_options(&config, ep_opts.concurrency, ep_opts.timeout);
+					db.refresh_capabilities(&network_opts).await;
+
+					let endpoints = db.best_endpoints_for(&EndpointQuery::new(), None, &config);
+					if opts.json {
+						let json: Vec<_> = endpoints
+							.iter()
+							.map(
+								|e| serde_json::json!({ "name": e.name, "url": e.url, "capabilities": e.stats.capabilities }),
+							)
+							.collect();
+						println!("{}", serde_json::to_string_pretty(&json).unwrap());
+					} else {
+						println!(
+							"{:<20} {:<20} {:>10} {:>8} {:>8}  url",
+							"name", "runtime", "version", "archive", "methods"
+						);
+						endpoints.iter().for_each(|e| match &e.stats.capabilities {
+							Some(c) => println!(
+								"{:<20} {:<20} {:>10} {:>8} {:>8}  {}",
+								e.name,
+								c.spec_name,
+								c.spec_version,
+								c.archive.map(|a| if a { "yes" } else { "no" }).unwrap_or("?"),
+								c.methods.len(),
+								e.url
+							),
+							None => println!("{:<20} {:<20} {:>10} {:>8} {:>8}  {}", e.name, "-", "-", "-", "-", e.url),
+						})
+					}
+
+					if let Err(e) = db.save() {
+						eprintln!("{e}");
+					}
+				}
 				EndpointsSubCommand::Open(ep_opts) => {
 					debug!("endpoints/ping");
 					debug!("ep_opts: {:?}", ep_opts);
-					let endpoints = db.get_endpoints(Some(&ep_opts.chain));
-					match endpoints.iter().next() {
+					let chain = resolve_chain(&db, &config, &ep_opts.chain);
+					let query = ep_opts.query.query(Some(&chain));
+					let endpoints = db.best_endpoints_for(&query, Some(1), &config);
+					match endpoints.first() {
 						Some(endpoint) => {
-							let url = ep_opts.browser_url.replace("{}", &endpoint.url.to_string());
+							let browser_url = ep_opts.browser_url.as_ref().unwrap_or(&config.browser_url);
+							let url = browser_url.replace("{}", &exported_url(endpoint, &config.secrets));
 
-							log::debug!("Opening '{}' via {}", ep_opts.chain, url);
+							log::debug!(
+								"Opening '{chain}' via {}",
+								browser_url.replace("{}", &endpoint.url.to_string())
+							);
 							let mut browser_options = BrowserOptions::new();
-							browser_options.with_target_hint(&ep_opts.chain);
+							browser_options.with_target_hint(&chain);
 							webbrowser::open_browser_with_options(Browser::Default, &url, &browser_options)
 								.expect("Problem while opening default browser");
 						}
 						None => {
-							eprintln!("No endpoint found for '{}'", ep_opts.chain)
+							eprintln!("No endpoint found for '{chain}'")
 						}
 					}
 				}
+				EndpointsSubCommand::Stats(ep_opts) => {
+					debug!("endpoints/stats");
+					debug!("ep_opts: {:?}", ep_opts);
+					let chain = ep_opts.chain.as_ref().map(|chain| resolve_chain(&db, &config, chain));
+					let query = ep_opts.query.query(chain.as_deref());
+					let endpoints = db.best_endpoints_for(&query, ep_opts.max, &config);
+					let stats: Vec<(Endpoint, WindowStats)> = endpoints
+						.into_iter()
+						.map(|e| {
+							let stats = e.stats.window_stats(ep_opts.window);
+							(e, stats)
+						})
+						.collect();
+
+					if opts.json {
+						let json: Vec<_> = stats
+							.iter()
+							.map(
+								|(e, stats)| serde_json::json!({ "name": e.name, "url": e.url, "stats": stats, "health": e.stats.health }),
+							)
+							.collect();
+						println!("{}", serde_json::to_string_pretty(&json).unwrap());
+					} else {
+						let fmt = |v: Option<f32>| v.map(|v| format!("{v:0.3}s")).unwrap_or("-".to_string());
+						let lag = |e: &Endpoint| match &e.stats.health {
+							Some(h) if h.is_syncing => "syncing".to_string(),
+							Some(h) if h.is_isolated() => "no peer".to_string(),
+							Some(h) => h.lag.to_string(),
+							None => "-".to_string(),
+						};
+						println!(
+							"{:<20} {:>7} {:>8} {:>8} {:>8} {:>7} {:>7}  url (last {})",
+							"name", "avail.", "p50", "p90", "p99", "samples", "lag", ep_opts.window
+						);
+						stats.iter().for_each(|(e, stats)| {
+							println!(
+								"{:<20} {:>7} {:>8} {:>8} {:>8} {:>7} {:>7}  {}",
+								e.name,
+								stats.availability.map(|a| format!("{:0.1}%", a * 100f32)).unwrap_or("-".to_string()),
+								fmt(stats.p50),
+								fmt(stats.p90),
+								fmt(stats.p99),
+								stats.samples,
+								lag(e),
+								e.url
+							);
+						})
+					}
+				}
+			}
+		}
+
+		SubCommand::Config(cmd_opts) => {
+			debug!("Running Config command");
+			debug!("cmd_opts: {:?}", cmd_opts);
+			match cmd_opts.config_subcmd {
+				ConfigSubCommand::List(conf_opts) => {
+					debug!("config/list");
+					debug!("conf_opts: {:?}", conf_opts);
+					let entries = config.entries();
+					if opts.json {
+						let serialized = serde_json::to_string_pretty(&entries).unwrap();
+						println!("{serialized}");
+					} else {
+						entries.iter().for_each(|entry| {
+							println!("{:<20} = {:<40} ({})", entry.key, entry.value.to_string(), entry.source);
+						})
+					}
+				}
+
+				ConfigSubCommand::Edit(conf_opts) => {
+					debug!("config/edit");
+					debug!("conf_opts: {:?}", conf_opts);
+					edit_config(config_file)?;
+				}
 			}
 		}
 
-		_ => {
-			println!("This command is not yet implemented.");
-			println!("Do you fancy a PR ? Here is the repo: https://github.com/chevdor/subrpc");
-			std::process::exit(1);
-		} // SubCommand::Config(cmd_opts) => {
-		  // 	debug!("Running Config command");
-		  // 	debug!("cmd_opts: {:?}", cmd_opts);
-		  // }
+		SubCommand::Proxy(cmd_opts) => {
+			debug!("Running Proxy command");
+			debug!("cmd_opts: {:?}", cmd_opts);
+			let chain = cmd_opts.chain.as_ref().map(|chain| resolve_chain(&db, &config, chain));
+			let mut proxy =
+				Proxy::new(storage.clone(), config.clone()).addr(SocketAddr::new(cmd_opts.host, cmd_opts.port));
+			if let Some(chain) = &chain {
+				proxy = proxy.chain(chain);
+			}
+			let proxy = proxy.bind().await?;
+			let addr = proxy.local_addr()?;
+			match chain {
+				Some(chain) => println!("Proxying {chain} on ws://{addr}, other chains on ws://{addr}/<chain>"),
+				None => println!("Proxying on ws://{addr}/<chain>"),
+			}
+			proxy.run_until(tokio::signal::ctrl_c()).await?;
+		}
+	}
+	Ok(())
+}
+
+/// Import the local data stored as JSON into a new SQLite database, when switching storage
+fn import_json_data(storage: &Arc<dyn Storage>) -> color_eyre::Result<()> {
+	let json_file = StorageBackend::Json.default_file()?;
+	if storage.exists() || storage.file().is_none_or(|file| file == json_file) || !json_file.exists() {
+		return Ok(());
 	}
+	let data = LocalData::init(&json_file, false)?;
+	LocalData { storage: Some(storage.clone()), ..data }.save()?;
+	eprintln!("Imported your local data from {} into {}", json_file.display(), storage.location());
 	Ok(())
 }
+
+/// Build the [NetworkOptions] from the config and the optional values passed by the user
+fn network_options(config: &Config, concurrency: Option<usize>, timeout: Option<u64>) -> NetworkOptions {
+	let mut opts = config.network_options();
+	if let Some(concurrency) = concurrency {
+		opts.concurrency = concurrency;
+	}
+	if let Some(timeout) = timeout {
+		opts.timeout = Duration::from_secs(timeout);
+	}
+	opts
+}
+
+/// Url of an endpoint with its secrets applied, to be used outside of subrpc.
+/// Falls back to the url with its placeholders if a secret is missing.
+fn exported_url(endpoint: &Endpoint, secrets: &Secrets) -> String {
+	match endpoint.resolved_url(secrets) {
+		Ok(url) => url.to_string(),
+		Err(e) => {
+			eprintln!("{e}");
+			endpoint.url.to_string()
+		}
+	}
+}
+
+/// Resolve the chain name typed by the user through the aliases.
+/// Exits with the closest chains if the name is unknown or ambiguous.
+fn resolve_chain(db: &LocalData, config: &Config, name: &str) -> String {
+	match db.resolve_chain(name, config) {
+		Ok(m) => m.chain,
+		Err(e) => {
+			eprintln!("{e}");
+			process::exit(1);
+		}
+	}
+}
+
+/// Report the outcome of a change made to a registry and exit on error
+fn print_registry_change(res: Result<LocalData>, registry: &str, action: &str) {
+	match res {
+		Ok(local_data) => {
+			println!("OK, {registry} has been {action}.");
+			local_data.print_registries();
+		}
+		Err(e) => {
+			eprintln!("{e}");
+			process::exit(1);
+		}
+	}
+}
+
+/// Open the config in the user's editor and save it only once it is valid.
+///
+/// The edition happens on a copy so an invalid config never replaces the current one.
+fn edit_config(file: &Path) -> color_eyre::Result<()> {
+	let mut tmp_name = file.as_os_str().to_owned();
+	tmp_name.push(".edit");
+	let tmp_file = PathBuf::from(tmp_name);
+
+	if file.exists() {
+		fs::copy(file, &tmp_file)?;
+	} else {
+		Config::default().save(&tmp_file)?;
+	}
+
+	let editor = env::var("VISUAL").or_else(|_| env::var("EDITOR")).unwrap_or("vi".to_string());
+	let mut editor_args = editor.split_whitespace();
+	let editor_cmd = editor_args.next().unwrap_or("vi");
+	let editor_args: Vec<&str> = editor_args.collect();
+
+	loop {
+		let status = process::Command::new(editor_cmd).args(&editor_args).arg(&tmp_file).status()?;
+		if !status.success() {
+			fs::remove_file(&tmp_file)?;
+			eprintln!("Your editor exited with {status}, the config was not changed.");
+			process::exit(1);
+		}
+
+		match Config::parse(&fs::read_to_string(&tmp_file)?) {
+			Ok(_) => {
+				fs::rename(&tmp_file, file)?;
+				println!("OK, your config has been saved to {}", file.display());
+				return Ok(());
+			}
+			Err(e) => {
+				eprintln!("Your config is invalid: {e}");
+				eprint!("Edit again? [Y/n] ");
+				io::stderr().flush()?;
+				let mut answer = String::new();
+				io::stdin().lock().read_line(&mut answer)?;
+				if answer.trim().eq_ignore_ascii_case("n") {
+					fs::remove_file(&tmp_file)?;
+					eprintln!("The config was not changed.");
+					process::exit(1);
+				}
+			}
+		}
+	}
+}
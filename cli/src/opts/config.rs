@@ -14,13 +14,13 @@ pub enum ConfigSubCommand {
 #[derive(Debug, Parser)]
 pub struct ConfigOpts {
 	#[clap(subcommand)]
-	pub registry_subcmd: ConfigSubCommand,
+	pub config_subcmd: ConfigSubCommand,
 }
 
-/// Config list
+/// Show the effective config and where each value comes from
 #[derive(Debug, Parser)]
 pub struct ConfigListOpts {}
 
-/// Config edit
+/// Edit the config using $VISUAL or $EDITOR. The config is only saved if it is valid.
 #[derive(Debug, Parser)]
 pub struct ConfigEditOpts {}
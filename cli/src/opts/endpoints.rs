@@ -1,4 +1,5 @@
 use clap::{crate_authors, crate_version, Parser, Subcommand};
+use subrpc_core::{CapabilityFilter, EndpointQuery, Protocol, Window};
 
 /// You can find all available commands below.
 #[derive(Debug, Subcommand)]
@@ -14,6 +15,12 @@ pub enum EndpointsSubCommand {
 
 	#[clap(version = crate_version!(), author = crate_authors!())]
 	Open(EndpointsOpenOpts),
+
+	#[clap(version = crate_version!(), author = crate_authors!())]
+	Probe(EndpointsProbeOpts),
+
+	#[clap(version = crate_version!(), author = crate_authors!())]
+	Stats(EndpointsStatsOpts),
 }
 
 /// Endpoints
@@ -23,34 +30,158 @@ pub struct EndpointsOpts {
 	pub endpoints_subcmd: EndpointsSubCommand,
 }
 
-/// Show the list of all endpoints
+/// Capabilities required from the endpoints, as found by `subrpc endpoints probe`.
+/// Endpoints that were never probed are left out when any is required.
+#[derive(Debug, Parser)]
+pub struct CapabilityOpts {
+	/// Only use archive nodes
+	#[clap(long)]
+	pub archive: bool,
+
+	/// Only use endpoints supporting this RPC method or group of methods, such as `chainHead_v1`.
+	/// Can be repeated.
+	#[clap(long = "method")]
+	pub methods: Vec<String>,
+
+	/// Only use endpoints serving at least this runtime spec_version
+	#[clap(long)]
+	pub min_spec_version: Option<u32>,
+}
+
+impl CapabilityOpts {
+	pub fn filter(&self) -> CapabilityFilter {
+		CapabilityFilter {
+			archive: self.archive,
+			methods: self.methods.clone(),
+			min_spec_version: self.min_spec_version,
+		}
+	}
+}
+
+/// Filters selecting the endpoints
+#[derive(Debug, Parser)]
+pub struct QueryOpts {
+	/// Only use endpoints having this label, on the endpoint or on its registry. Can be repeated.
+	#[clap(long = "label")]
+	pub labels: Vec<String>,
+
+	/// Never use endpoints having this label, on the endpoint or on its registry. Can be repeated.
+	#[clap(long = "exclude-label")]
+	pub exclude_labels: Vec<String>,
+
+	/// Only use endpoints of this provider: the name of the endpoint or of its registry. Can be repeated.
+	#[clap(long = "provider")]
+	pub providers: Vec<String>,
+
+	/// Only use endpoints using this protocol: http, https, ws, wss, tcp or unix. Can be repeated.
+	#[clap(long = "proto")]
+	pub protocols: Vec<Protocol>,
+
+	/// Only use endpoints whose score is at least this value, between 0 and 1
+	#[clap(long)]
+	pub min_score: Option<f32>,
+
+	#[clap(flatten)]
+	pub capabilities: CapabilityOpts,
+}
+
+impl QueryOpts {
+	/// The [EndpointQuery] for the given chain
+	pub fn query(&self, chain: Option<&str>) -> EndpointQuery {
+		EndpointQuery {
+			chain: chain.map(String::from),
+			include_labels: self.labels.clone(),
+			exclude_labels: self.exclude_labels.clone(),
+			providers: self.providers.clone(),
+			protocols: self.protocols.clone(),
+			min_score: self.min_score,
+			capabilities: self.capabilities.filter(),
+		}
+	}
+}
+
+/// Show the list of all endpoints, the best ones first
 #[derive(Debug, Parser)]
-pub struct EndpointsListOpts {}
+pub struct EndpointsListOpts {
+	/// Only show the best N endpoints
+	#[clap(long, short)]
+	pub max: Option<usize>,
+
+	#[clap(flatten)]
+	pub query: QueryOpts,
+}
 
 /// Ping endpoints
 #[derive(Debug, Parser)]
-pub struct EndpointsPingOpts {}
+pub struct EndpointsPingOpts {
+	/// Maximum number of endpoints pinged concurrently
+	#[clap(long, short)]
+	pub concurrency: Option<usize>,
+
+	/// Timeout in seconds for each ping
+	#[clap(long, short)]
+	pub timeout: Option<u64>,
+}
+
+/// Query the runtime version, the RPC methods and the archive support of the endpoints
+#[derive(Debug, Parser)]
+pub struct EndpointsProbeOpts {
+	/// Maximum number of endpoints probed concurrently
+	#[clap(long, short)]
+	pub concurrency: Option<usize>,
+
+	/// Timeout in seconds for each probe
+	#[clap(long, short)]
+	pub timeout: Option<u64>,
+}
 
-/// Get one or some endpoints
+/// Get one or some endpoints, the best ones first
 #[derive(Debug, Parser)]
 pub struct EndpointsGetOpts {
-	/// Name of the chain. Case insensitive.
+	/// A chain name, id or alias, ignoring case and punctuation
 	#[clap(index = 1)]
 	pub chain: String,
 
+	/// Only return the best N endpoints
 	#[clap(long, short)]
 	pub max: Option<usize>,
+
+	#[clap(flatten)]
+	pub query: QueryOpts,
 }
 
 /// Pick an endpoint and open it using PolkadotJS
 #[derive(Debug, Parser)]
 pub struct EndpointsOpenOpts {
-	/// Name of the chain. Case insensitive.
+	/// A chain name, id or alias, ignoring case and punctuation
 	#[clap(index = 1)]
 	pub chain: String,
 
 	/// This optional argument allows customizing the url used in your browser to open the selected endpoint.{n}
 	/// At runtime, the "{}" marker will be substituted by the endpoint url.{n}
-	#[clap(long, short, env = "SUBRPC_BROWSER_URL", default_value = "https://polkadot.js.org/apps/?rpc={}")]
-	pub browser_url: String,
+	/// Defaults to the `browser_url` of your config, which can also be set using the SUBRPC_BROWSER_URL env.{n}
+	#[clap(long, short)]
+	pub browser_url: Option<String>,
+
+	#[clap(flatten)]
+	pub query: QueryOpts,
+}
+
+/// Show the availability and latency percentiles of the endpoints, based on the past pings
+#[derive(Debug, Parser)]
+pub struct EndpointsStatsOpts {
+	/// Name or alias of the chain, as for `get`. All chains are shown if omitted.
+	#[clap(index = 1)]
+	pub chain: Option<String>,
+
+	/// Time window: hour, day or week
+	#[clap(long, short, default_value = "day")]
+	pub window: Window,
+
+	/// Only show the best N endpoints
+	#[clap(long, short)]
+	pub max: Option<usize>,
+
+	#[clap(flatten)]
+	pub query: QueryOpts,
 }
@@ -1,14 +1,18 @@
 mod config;
 mod endpoints;
+mod proxy;
 mod registry;
 mod system;
 
+pub use config::*;
 pub use endpoints::*;
+pub use proxy::*;
 pub use registry::*;
 pub use system::*;
 
-use self::{config::ConfigOpts, endpoints::EndpointsOpts, registry::RegistryOpts, system::SystemOpts};
+use self::{endpoints::EndpointsOpts, registry::RegistryOpts, system::SystemOpts};
 use clap::{crate_authors, crate_version, ColorChoice, Parser, Subcommand};
+use std::path::PathBuf;
 
 /// `subrpc` allows managing a set of registry providing rpc nodes.
 #[derive(Debug, Parser)]
@@ -19,6 +23,11 @@ pub struct Opts {
 
 	#[clap(short, long, global = true)]
 	pub json: bool,
+
+	/// File of the local data to use instead of the default one.
+	/// Files ending with `.db`, `.sqlite` or `.sqlite3` use the SQLite storage.
+	#[clap(long, global = true, value_name = "FILE")]
+	pub data: Option<PathBuf>,
 }
 
 /// You can find all available commands below.
@@ -35,4 +44,34 @@ pub enum SubCommand {
 
 	#[clap(alias="conf", version = crate_version!(), author = crate_authors!())]
 	Config(ConfigOpts),
+
+	#[clap(version = crate_version!(), author = crate_authors!())]
+	Proxy(ProxyOpts),
+}
+
+impl SubCommand {
+	/// Whether the command modifies the local data, and must therefore hold its lock
+	pub fn modifies_data(&self) -> bool {
+		match self {
+			SubCommand::Registry(opts) => matches!(
+				opts.registry_subcmd,
+				RegistrySubCommand::Add(_)
+					| RegistrySubCommand::Enable(_)
+					| RegistrySubCommand::Disable(_)
+					| RegistrySubCommand::Remove(_)
+					| RegistrySubCommand::Update(_)
+			),
+			SubCommand::Endpoints(opts) => {
+				matches!(opts.endpoints_subcmd, EndpointsSubCommand::Ping(_) | EndpointsSubCommand::Probe(_))
+			}
+			SubCommand::System(opts) => match &opts.system_subcmd {
+				SystemSubCommand::Init(_) => true,
+				SystemSubCommand::Migrate(migrate_opts) => !migrate_opts.dry_run,
+				SystemSubCommand::Info(_) => false,
+			},
+			SubCommand::Config(_) => false,
+			// The proxy takes the lock only while saving the stats of the endpoints
+			SubCommand::Proxy(_) => false,
+		}
+	}
 }
@@ -0,0 +1,23 @@
+use clap::Parser;
+use std::net::IpAddr;
+use subrpc_core::DEFAULT_PROXY_PORT;
+
+/// Run a local JSON-RPC proxy forwarding to the best endpoints.
+///
+/// Connect to ws://<host>:<port>/<chain>, such as ws://127.0.0.1:9955/polkadot.
+/// When an endpoint drops, the proxy fails over to the next one and renews the subscriptions.
+/// The latency and the failures of the endpoints are recorded in their stats.
+#[derive(Debug, Parser)]
+pub struct ProxyOpts {
+	/// Chain served on ws://<host>:<port>/, the other chains remain available by path
+	#[clap(index = 1)]
+	pub chain: Option<String>,
+
+	/// Address to listen on
+	#[clap(long, default_value = "127.0.0.1")]
+	pub host: IpAddr,
+
+	/// Port to listen on
+	#[clap(short, long, default_value_t = DEFAULT_PROXY_PORT)]
+	pub port: u16,
+}
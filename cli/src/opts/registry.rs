@@ -14,13 +14,24 @@ pub enum RegistrySubCommand {
 
 	#[clap(version = crate_version!(), author = crate_authors!())]
 	Add(RegistryAddOpts),
-	// #[clap(version = crate_version!(), author = crate_authors!())]
-	// Enable(RegistryEnableOpts),
 
-	// #[clap(alias="rm", version = crate_version!(), author = crate_authors!())]
-	// Remove(RegistryRemoveOpts),
+	#[clap(version = crate_version!(), author = crate_authors!())]
+	Enable(RegistryEnableOpts),
+
+	#[clap(version = crate_version!(), author = crate_authors!())]
+	Disable(RegistryDisableOpts),
+
+	#[clap(alias="rm", version = crate_version!(), author = crate_authors!())]
+	Remove(RegistryRemoveOpts),
+
 	#[clap(alias="up", version = crate_version!(), author = crate_authors!())]
 	Update(RegistryUpdateOpts),
+
+	#[clap(version = crate_version!(), author = crate_authors!())]
+	Lint(RegistryLintOpts),
+
+	#[clap(version = crate_version!(), author = crate_authors!())]
+	Schema(RegistrySchemaOpts),
 }
 
 /// Manage your registries
@@ -32,7 +43,15 @@ pub struct RegistryOpts {
 
 /// Fetch the latest data from the registries and update the list of endpoints
 #[derive(Debug, Parser)]
-pub struct RegistryUpdateOpts {}
+pub struct RegistryUpdateOpts {
+	/// Maximum number of registries fetched concurrently
+	#[clap(long, short)]
+	pub concurrency: Option<usize>,
+
+	/// Timeout in seconds for each request
+	#[clap(long, short)]
+	pub timeout: Option<u64>,
+}
 
 /// List currently known registries
 #[derive(Debug, Parser)]
@@ -50,18 +69,54 @@ pub struct RegistryAddOpts {
 	pub url: String,
 }
 
-/// Remove Registry
+/// Remove a registry and all its endpoints from your local data
 #[derive(Debug, Parser)]
-pub struct RegistryRemoveOpts {}
+pub struct RegistryRemoveOpts {
+	/// Name or url of the registry
+	#[clap(index = 1)]
+	pub registry: String,
+}
 
-/// Enable or disable a registry
+/// Enable a registry
 #[derive(Debug, Parser)]
 pub struct RegistryEnableOpts {
-	/// true/false
+	/// Name or url of the registry
 	#[clap(index = 1)]
-	pub state: bool,
+	pub registry: String,
+}
+
+/// Disable a registry. Its endpoints are ignored and it is no longer updated.
+#[derive(Debug, Parser)]
+pub struct RegistryDisableOpts {
+	/// Name or url of the registry
+	#[clap(index = 1)]
+	pub registry: String,
+}
+
+/// List the known chains, merged across the registries, with their number of endpoints
+#[derive(Debug, Parser)]
+pub struct RegistryChainsOpts {
+	/// Only list the chains whose name or aliases are close to this pattern, the closest first
+	#[clap(index = 1)]
+	pub pattern: Option<String>,
+
+	/// Only print the names of the chains, one per line
+	#[clap(long)]
+	pub names: bool,
+}
+
+/// Check a registry file and report the issues with their line and column
+#[derive(Debug, Parser)]
+pub struct RegistryLintOpts {
+	/// Path or url of the registry
+	#[clap(index = 1)]
+	pub source: String,
+
+	/// Fail on warnings as well
+	#[clap(long)]
+	pub strict: bool,
 }
 
-/// List known chains
+/// Print the JSON Schema of the registry files
 #[derive(Debug, Parser)]
-pub struct RegistryChainsOpts {}
+pub struct RegistrySchemaOpts {}
@@ -8,6 +8,9 @@ pub enum SystemSubCommand {
 
 	#[clap(version = crate_version!(), author = crate_authors!())]
 	Init(SystemInitOpts),
+
+	#[clap(version = crate_version!(), author = crate_authors!())]
+	Migrate(SystemMigrateOpts),
 }
 
 /// Show general system information such as the location of relevant files
@@ -21,6 +24,17 @@ pub struct SystemInfoOpts {}
 #[derive(Debug, Parser)]
 pub struct SystemInitOpts {}
 
+/// Upgrade your local database to the latest layout.
+///
+/// Older layouts are upgraded automatically when loading the data, this command
+/// saves the upgraded data or shows what would change.
+#[derive(Debug, Parser)]
+pub struct SystemMigrateOpts {
+	/// Only show the changes, without saving them
+	#[clap(long)]
+	pub dry_run: bool,
+}
+
 /// System
 #[derive(Debug, Parser)]
 pub struct SystemOpts {
@@ -1,16 +1,14 @@
 #!/usr/bin/env bash
 
-# A function to interactively open a chain in your Browser
-# Simply call `sub` or `sub <pattern>`
+# A function to open a chain in your Browser
+# Simply call `sub <name>`, or `sub` to pick a chain interactively
 function sub() {
-    chains=$(subrpc reg chains)
-
     if [ ! -z "$1" ]; then
-        query="$1"
-        echo "Searching for chains matching: $query"
-        chain=$(echo "$chains" | sort -r | fzf -1 -q "$query" --prompt="Select the chain to open in your browser > ")
-    else
-        chain=$(echo "$chains" | sort -r | fzf -1 --prompt="Select the chain to open in your browser > ")
+        # Names and aliases of a single chain are resolved by subrpc
+        subrpc endpoints open "$1" 2>/dev/null && return
+        echo "Searching for chains matching: $1"
     fi
-    subrpc endpoints open "$chain"
+
+    chain=$(subrpc reg chains --names "$@" | fzf -1 --prompt="Select the chain to open in your browser > ")
+    [ ! -z "$chain" ] && subrpc endpoints open "$chain"
 }
@@ -15,14 +15,15 @@ fn main() {
 	let local_data_file = std::path::Path::new("/tmp/subrpc_demo.json");
 	println!("Using local data from: {}", local_data_file.display());
 
-	// We use the builder pattern to initialize our local data with one registry
+	// We use the builder pattern to initialize our local data with one registry.
+	// This example does not run an async runtime so we use the blocking API.
 	const REGISTRY_URL: &str = "https://raw.githubusercontent.com/chevdor/subrpc/master/registry/subrpc.json";
 	let db = LocalData::init(local_data_file, false)
 		.expect("Should be able to load local data")
 		.load()
 		.expect("Should load properly")
 		.add_registry(Registry::new("Subrpc Registry", REGISTRY_URL))
-		.refresh();
+		.refresh_blocking();
 
 	// This shows the list of registries we are aware of and how many RPC endpoints they contain
 	db.print_summary();
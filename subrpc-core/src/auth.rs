@@ -0,0 +1,233 @@
+//! Authentication to the endpoints.
+//!
+//! Registries only describe *how* an endpoint authenticates, using placeholders such as `{API_KEY}`
+//! in the url or in an [Auth] descriptor. The secrets replacing the placeholders are never part
+//! of a registry: they come from the `[secrets]` table of the [Config](crate::Config) or from the
+//! environment, see [Secrets].
+
+use crate::{Error, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, env, fmt::Debug};
+
+/// Prefix of the environment variables providing secrets, see [Secrets::get]
+pub const ENV_SECRET_PREFIX: &str = "SUBRPC_SECRET_";
+
+/// Text replacing the secrets in redacted output
+pub const REDACTED: &str = "***";
+
+/// Characters encoded in the secrets put in a url: all but the unreserved ones, so that a secret
+/// never changes the structure of the url, whatever the part of the url it is in
+const URL_COMPONENT: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+/// How an endpoint authenticates. The values may contain placeholders such as `{API_KEY}`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Auth {
+	/// A header, for instance `Authorization` with the value `Bearer {TOKEN}`
+	Header { name: String, value: String },
+
+	/// The secret is part of the url, for instance `wss://provider.io/ws/{API_KEY}`
+	Url,
+
+	/// Basic authentication
+	Basic { username: String, password: String },
+}
+
+impl Auth {
+	/// The header to send, with its placeholders replaced, if this auth uses one
+	pub fn header(&self, endpoint: &str, secrets: &Secrets) -> Result<Option<(String, String)>> {
+		match self {
+			Auth::Header { name, value } => Ok(Some((name.clone(), secrets.resolve(endpoint, value)?))),
+			Auth::Basic { username, password } => {
+				let credentials =
+					format!("{}:{}", secrets.resolve(endpoint, username)?, secrets.resolve(endpoint, password)?);
+				Ok(Some(("Authorization".to_string(), format!("Basic {}", STANDARD.encode(credentials)))))
+			}
+			Auth::Url => Ok(None),
+		}
+	}
+
+	/// Names of the placeholders used by this auth
+	pub fn placeholders(&self) -> Vec<String> {
+		match self {
+			Auth::Header { name: _, value } => placeholders(value),
+			Auth::Basic { username, password } => [placeholders(username), placeholders(password)].concat(),
+			Auth::Url => vec![],
+		}
+	}
+}
+
+/// Names of the `{PLACEHOLDERS}` found in `s`. Names are made of uppercase letters, digits and `_`.
+pub fn placeholders(s: &str) -> Vec<String> {
+	let mut names = vec![];
+	let mut rest = s;
+	while let Some(start) = rest.find('{') {
+		rest = &rest[start + 1..];
+		if let Some(end) = rest.find('}') {
+			let name = &rest[..end];
+			if !name.is_empty() && name.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_') {
+				names.push(name.to_string());
+				rest = &rest[end + 1..];
+			}
+		}
+	}
+	names
+}
+
+/// Secrets used to authenticate to the endpoints, indexed by endpoint name and then by placeholder name.
+///
+/// In the config, they look like:
+/// ```toml
+/// [secrets.OnFinality]
+/// API_KEY = "..."
+/// ```
+/// The environment variable `SUBRPC_SECRET_<ENDPOINT>_<NAME>`, such as `SUBRPC_SECRET_ONFINALITY_API_KEY`,
+/// takes precedence over the config. The endpoint name is uppercased and anything but letters and digits
+/// is replaced by `_`.
+#[derive(Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Secrets(BTreeMap<String, BTreeMap<String, String>>);
+
+impl Debug for Secrets {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_map().entries(self.redacted().0).finish()
+	}
+}
+
+impl Secrets {
+	pub fn is_empty(&self) -> bool {
+		self.0.is_empty()
+	}
+
+	/// Set a secret
+	pub fn insert(&mut self, endpoint: &str, name: &str, value: &str) {
+		self.0.entry(endpoint.to_string()).or_default().insert(name.to_string(), value.to_string());
+	}
+
+	/// Name of the environment variable providing the secret `name` of `endpoint`
+	pub fn env_var(endpoint: &str, name: &str) -> String {
+		let endpoint: String =
+			endpoint.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' }).collect();
+		format!("{ENV_SECRET_PREFIX}{endpoint}_{name}")
+	}
+
+	/// Get the secret `name` of `endpoint`, from the environment first and then from the config.
+	pub fn get(&self, endpoint: &str, name: &str) -> Option<String> {
+		self.get_with(endpoint, name, &|var| env::var(var).ok())
+	}
+
+	/// Same as [Self::get], `var` returning the value of the environment variables
+	fn get_with(&self, endpoint: &str, name: &str, var: &dyn Fn(&str) -> Option<String>) -> Option<String> {
+		var(&Self::env_var(endpoint, name))
+			.or_else(|| self.0.get(endpoint).and_then(|secrets| secrets.get(name)).cloned())
+	}
+
+	/// Replace the placeholders of `template` with the secrets of `endpoint`, as they are.
+	/// Use [Self::resolve_url] for urls.
+	pub fn resolve(&self, endpoint: &str, template: &str) -> Result<String> {
+		self.resolve_with(endpoint, template, &|var| env::var(var).ok(), false)
+	}
+
+	/// Replace the placeholders of the url `template` with the secrets of `endpoint`, percent encoded.
+	pub fn resolve_url(&self, endpoint: &str, template: &str) -> Result<String> {
+		self.resolve_with(endpoint, template, &|var| env::var(var).ok(), true)
+	}
+
+	/// Same as [Self::resolve] or [Self::resolve_url] if `encode` is set, `var` returning the value
+	/// of the environment variables
+	fn resolve_with(
+		&self,
+		endpoint: &str,
+		template: &str,
+		var: &dyn Fn(&str) -> Option<String>,
+		encode: bool,
+	) -> Result<String> {
+		placeholders(template).iter().try_fold(template.to_string(), |s, name| {
+			let secret = self.get_with(endpoint, name, var).ok_or_else(|| Error::MissingSecret {
+				endpoint: endpoint.to_string(),
+				name: name.clone(),
+				env: Self::env_var(endpoint, name),
+			})?;
+			let secret = if encode { utf8_percent_encode(&secret, URL_COMPONENT).to_string() } else { secret };
+			Ok(s.replace(&format!("{{{name}}}"), &secret))
+		})
+	}
+
+	/// A copy where all the values are replaced by [REDACTED]
+	pub fn redacted(&self) -> Self {
+		Self(
+			self.0
+				.iter()
+				.map(|(endpoint, secrets)| {
+					(endpoint.clone(), secrets.keys().map(|name| (name.clone(), REDACTED.to_string())).collect())
+				})
+				.collect(),
+		)
+	}
+}
+
+#[cfg(test)]
+mod test_auth {
+	use super::*;
+
+	#[test]
+	fn test_placeholders() {
+		assert_eq!(vec!["API_KEY"], placeholders("wss://provider.io/{API_KEY}?a={not_one}"));
+		assert_eq!(vec!["USER", "KEY_2"], placeholders("{USER}:{KEY_2}"));
+		assert!(placeholders("{}{").is_empty());
+	}
+
+	#[test]
+	fn test_resolve() {
+		let mut secrets = Secrets::default();
+		secrets.insert("Provider", "API_KEY", "s3cr3t");
+		assert_eq!("wss://provider.io/s3cr3t", secrets.resolve("Provider", "wss://provider.io/{API_KEY}").unwrap());
+		assert!(matches!(
+			secrets.resolve("Other", "wss://provider.io/{API_KEY}"),
+			Err(Error::MissingSecret { env, .. }) if env == "SUBRPC_SECRET_OTHER_API_KEY"
+		));
+
+		let var = |name: &str| (name == "SUBRPC_SECRET_MY_PROVIDER_TOKEN").then(|| "from-env".to_string());
+		assert_eq!("SUBRPC_SECRET_MY_PROVIDER_TOKEN", Secrets::env_var("My provider", "TOKEN"));
+		assert_eq!("Bearer from-env", secrets.resolve_with("My provider", "Bearer {TOKEN}", &var, false).unwrap());
+		secrets.insert("My provider", "TOKEN", "from-config");
+		assert_eq!("Bearer from-env", secrets.resolve_with("My provider", "Bearer {TOKEN}", &var, false).unwrap());
+		assert_eq!(
+			"Bearer from-config",
+			secrets.resolve_with("My provider", "Bearer {TOKEN}", &|_| None, false).unwrap()
+		);
+
+		assert!(!format!("{secrets:?}").contains("s3cr3t"));
+	}
+
+	#[test]
+	fn test_resolve_url() {
+		let mut secrets = Secrets::default();
+		secrets.insert("Provider", "API_KEY", "a/b?c#d@e%f g");
+		let template = "wss://provider.io/ws/{API_KEY}?key={API_KEY}";
+		assert_eq!(
+			"wss://provider.io/ws/a%2Fb%3Fc%23d%40e%25f%20g?key=a%2Fb%3Fc%23d%40e%25f%20g",
+			secrets.resolve_with("Provider", template, &|_| None, true).unwrap()
+		);
+		assert_eq!("a/b?c#d@e%f g", secrets.resolve_with("Provider", "{API_KEY}", &|_| None, false).unwrap());
+	}
+
+	#[test]
+	fn test_headers() {
+		let mut secrets = Secrets::default();
+		secrets.insert("Provider", "USER", "alice");
+		secrets.insert("Provider", "PASSWORD", "pass");
+
+		let auth = Auth::Basic { username: "{USER}".to_string(), password: "{PASSWORD}".to_string() };
+		assert_eq!(
+			Some(("Authorization".to_string(), "Basic YWxpY2U6cGFzcw==".to_string())),
+			auth.header("Provider", &secrets).unwrap()
+		);
+
+		let auth: Auth =
+			serde_json::from_str(r#"{ "type": "header", "name": "X-Api-Key", "value": "{USER}" }"#).unwrap();
+		assert_eq!(Some(("X-Api-Key".to_string(), "alice".to_string())), auth.header("Provider", &secrets).unwrap());
+		assert_eq!(None, Auth::Url.header("Provider", &secrets).unwrap());
+	}
+}
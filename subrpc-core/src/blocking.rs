@@ -0,0 +1,76 @@
+//! Blocking wrappers around the async API.
+//!
+//! Those are convenient for apps that do not run an async runtime. Each call spins up
+//! a short lived runtime so they must NOT be called from within an async context.
+//! If you are already running inside tokio, use the async functions directly.
+
+use crate::{Endpoint, LocalData, NetworkOptions, Registry, Result, UpdateOutcome};
+use std::future::Future;
+
+/// Run a future to completion on a fresh current-thread runtime.
+fn block_on<F: Future>(future: F) -> F::Output {
+	tokio::runtime::Builder::new_current_thread()
+		.enable_all()
+		.build()
+		.expect("Failed building the tokio runtime")
+		.block_on(future)
+}
+
+impl Registry {
+	/// Blocking version of [Registry::update].
+	pub fn update_blocking(&mut self) -> Result<UpdateOutcome> {
+		block_on(self.update())
+	}
+
+	/// Blocking version of [Registry::refresh_stats].
+	pub fn refresh_stats_blocking(&mut self) {
+		block_on(self.refresh_stats())
+	}
+
+	/// Blocking version of [Registry::ping_all].
+	pub fn ping_all_blocking(&mut self) {
+		block_on(self.ping_all())
+	}
+
+	/// Blocking version of [Registry::ping].
+	pub fn ping_blocking(e: &Endpoint) -> Result<(bool, Option<f32>)> {
+		block_on(Self::ping(e))
+	}
+
+	/// Blocking version of [Registry::load_from_url].
+	pub fn load_from_url_blocking(url: &str) -> Result<Self> {
+		block_on(Self::load_from_url(url))
+	}
+}
+
+impl LocalData {
+	/// Blocking version of [LocalData::refresh].
+	pub fn refresh_blocking(self) -> Self {
+		block_on(self.refresh())
+	}
+
+	/// Blocking version of [LocalData::refresh_stats].
+	pub fn refresh_stats_blocking(&mut self, opts: &NetworkOptions) {
+		block_on(self.refresh_stats(opts))
+	}
+
+	/// Blocking version of [LocalData::refresh_capabilities].
+	pub fn refresh_capabilities_blocking(&mut self, opts: &NetworkOptions) {
+		block_on(self.refresh_capabilities(opts))
+	}
+}
+
+#[cfg(test)]
+mod test_blocking {
+	use super::*;
+	use std::env;
+
+	#[test]
+	fn test_refresh_blocking() {
+		let file = env::temp_dir().join("subrpc_test_blocking.json");
+		let data = LocalData::init(&file, true).expect("Forced init should work").add_registry(Registry::default());
+		let data = data.refresh_blocking();
+		assert!(data.last_update.is_some());
+		assert_eq!(1, data.registries.len());
+	}
+}
@@ -0,0 +1,157 @@
+//! Capabilities of the endpoints: runtime version, RPC methods and archive support.
+//!
+//! Unlike the ping, the capabilities rarely change so they are only refreshed on demand,
+//! see [Registry::probe](crate::Registry::probe).
+
+use crate::{rpc::RpcClient, Endpoint, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// What an endpoint can do, as found by the last probe
+#[derive(Debug, Default, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+	/// DateTime of the probe
+	pub time: Option<DateTime<Local>>,
+
+	/// Name of the runtime, as returned by `state_getRuntimeVersion`
+	pub spec_name: String,
+
+	/// Version of the runtime, as returned by `state_getRuntimeVersion`
+	pub spec_version: u32,
+
+	/// RPC methods exposed by the endpoint, as returned by `rpc_methods`
+	pub methods: Vec<String>,
+
+	/// Whether the state of old blocks can be queried, `None` if the chain is too young to tell
+	pub archive: Option<bool>,
+}
+
+impl Capabilities {
+	/// Whether the endpoint exposes `method`. A method group such as `chainHead_v1` matches all its
+	/// methods, such as `chainHead_v1_follow`.
+	pub fn supports(&self, method: &str) -> bool {
+		self.methods.iter().any(|m| m == method || m.strip_prefix(method).is_some_and(|rest| rest.starts_with('_')))
+	}
+
+	/// Whether the endpoint is known to be an archive node
+	pub fn is_archive(&self) -> bool {
+		self.archive == Some(true)
+	}
+}
+
+/// Number of blocks kept by a pruned node, with the default settings of the nodes
+pub const PRUNING_BLOCKS: u64 = 256;
+
+/// Query the capabilities of a node.
+///
+/// A node is considered an archive node if it can return the runtime version at the block 1.
+/// Pruned nodes fail that query once the chain is more than [PRUNING_BLOCKS] blocks old.
+pub(crate) async fn probe(client: &mut RpcClient) -> Result<Capabilities> {
+	let version: Value = client.request("state_getRuntimeVersion", vec![]).await?;
+	let methods: Value = client.request("rpc_methods", vec![]).await?;
+	let header: Value = client.request("chain_getHeader", vec![]).await?;
+
+	let best_block = header
+		.get("number")
+		.and_then(Value::as_str)
+		.and_then(|n| u64::from_str_radix(n.trim_start_matches("0x"), 16).ok())
+		.unwrap_or_default();
+	let archive = if best_block > PRUNING_BLOCKS {
+		let hash: String = client.request("chain_getBlockHash", vec![1.into()]).await?;
+		Some(client.request::<Value>("state_getRuntimeVersion", vec![hash.into()]).await.is_ok())
+	} else {
+		None
+	};
+
+	Ok(Capabilities {
+		time: Some(Local::now()),
+		spec_name: version.get("specName").and_then(Value::as_str).unwrap_or_default().to_string(),
+		spec_version: version.get("specVersion").and_then(Value::as_u64).unwrap_or_default() as u32,
+		methods: methods
+			.get("methods")
+			.and_then(Value::as_array)
+			.map(|methods| methods.iter().filter_map(Value::as_str).map(String::from).collect())
+			.unwrap_or_default(),
+		archive,
+	})
+}
+
+/// Capabilities required from the endpoints. The default filter accepts all endpoints.
+///
+/// Endpoints that were never probed do not match a filter requiring anything.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CapabilityFilter {
+	/// Only accept archive nodes
+	pub archive: bool,
+
+	/// Only accept endpoints supporting all those methods or method groups, see [Capabilities::supports]
+	pub methods: Vec<String>,
+
+	/// Only accept endpoints serving at least this runtime version
+	pub min_spec_version: Option<u32>,
+}
+
+impl CapabilityFilter {
+	/// Only accept archive nodes
+	pub fn archive(mut self) -> Self {
+		self.archive = true;
+		self
+	}
+
+	/// Only accept endpoints supporting `method`
+	pub fn method(mut self, method: &str) -> Self {
+		self.methods.push(method.to_string());
+		self
+	}
+
+	/// Only accept endpoints serving at least `spec_version`
+	pub fn min_spec_version(mut self, spec_version: u32) -> Self {
+		self.min_spec_version = Some(spec_version);
+		self
+	}
+
+	/// Whether the filter requires anything
+	pub fn is_empty(&self) -> bool {
+		self == &Self::default()
+	}
+
+	pub fn matches(&self, endpoint: &Endpoint) -> bool {
+		if self.is_empty() {
+			return true;
+		}
+		let Some(capabilities) = &endpoint.stats.capabilities else {
+			return false;
+		};
+		(!self.archive || capabilities.is_archive())
+			&& self.methods.iter().all(|m| capabilities.supports(m))
+			&& self.min_spec_version.is_none_or(|v| capabilities.spec_version >= v)
+	}
+}
+
+#[cfg(test)]
+mod test_capabilities {
+	use super::*;
+
+	#[test]
+	fn test_filter() {
+		let mut endpoint = Endpoint::new("Node", "wss://node", vec![], vec![]);
+		let archive = CapabilityFilter::default().archive();
+		let chain_head = CapabilityFilter::default().method("chainHead_v1");
+		assert!(CapabilityFilter::default().matches(&endpoint));
+		assert!(!archive.matches(&endpoint));
+
+		endpoint.stats.capabilities = Some(Capabilities {
+			spec_version: 1_002_000,
+			methods: vec!["chainHead_v1_follow".to_string(), "system_chain".to_string()],
+			archive: Some(true),
+			..Default::default()
+		});
+		assert!(archive.matches(&endpoint));
+		assert!(chain_head.clone().min_spec_version(1_002_000).matches(&endpoint));
+		assert!(!chain_head.min_spec_version(1_003_000).matches(&endpoint));
+		assert!(CapabilityFilter::default().method("chainHead").matches(&endpoint));
+		assert!(!CapabilityFilter::default().method("chainHead_v1_foll").matches(&endpoint));
+		assert!(CapabilityFilter::default().method("system_chain").matches(&endpoint));
+	}
+}
@@ -0,0 +1,181 @@
+use crate::{resolver::normalize, ChainName};
+use serde::{Deserialize, Serialize};
+
+/// Former name of [Chain]
+#[deprecated(note = "Use Chain, which also describes the chains merged across registries")]
+pub type ChainInfo = Chain;
+
+/// A chain, as described by the registries.
+///
+/// In a registry, chains are indexed by the name used in its `rpc_endpoints`. All the fields are
+/// optional there: see [Registry::chain](crate::Registry::chain) for the defaults. The same chain may come
+/// from several registries, see [Self::is_same] and [LocalData::chains](crate::LocalData::chains).
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Chain {
+	/// Canonical id of the chain, such as `polkadot`
+	#[serde(default, skip_serializing_if = "String::is_empty")]
+	pub id: String,
+
+	/// Name to display, such as `Asset Hub Polkadot`
+	#[serde(default, skip_serializing_if = "String::is_empty")]
+	pub name: String,
+
+	/// Id of the relay chain, for a parachain
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub relay: Option<String>,
+
+	/// Id of the parachain on its relay chain
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub para_id: Option<u32>,
+
+	/// SS58 prefix of the addresses, such as 0 for Polkadot
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub ss58_prefix: Option<u16>,
+
+	/// Symbol of the native token, such as `DOT`
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub token: Option<String>,
+
+	/// Hash of the block 0, such as `0x91b1…90c3` for Polkadot
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub genesis_hash: Option<String>,
+
+	/// Name returned by the `system_chain` RPC, such as `Polkadot`
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub system_chain: Option<String>,
+
+	/// Other names of the chain, such as `dot` for Polkadot, see [ChainResolver](crate::ChainResolver)
+	#[serde(default, skip_serializing_if = "Vec::is_empty")]
+	pub aliases: Vec<String>,
+}
+
+impl Chain {
+	/// A chain only known by its name, its id is the [normalized](normalize) name
+	pub fn named(name: &str) -> Self {
+		Self { id: normalize(name), name: name.to_string(), ..Default::default() }
+	}
+
+	/// Whether `other` is the same chain: same genesis hash if both know it, same id otherwise.
+	/// Both are compared ignoring the case.
+	pub fn is_same(&self, other: &Chain) -> bool {
+		match (&self.genesis_hash, &other.genesis_hash) {
+			(Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+			_ => self.id.eq_ignore_ascii_case(&other.id),
+		}
+	}
+
+	/// Complete this chain with what `other`, the same chain, knows and this one does not.
+	/// The aliases are added, the name of `other` becomes one of them if it differs.
+	pub fn merge(&mut self, other: &Chain) {
+		let names = [&other.name].into_iter().chain(&other.aliases);
+		for alias in names.filter(|a| !a.is_empty()) {
+			let known = |name: &String| normalize(name) == normalize(alias);
+			if !known(&self.name) && !self.aliases.iter().any(known) {
+				self.aliases.push(alias.clone());
+			}
+		}
+		self.relay = self.relay.take().or_else(|| other.relay.clone());
+		self.para_id = self.para_id.or(other.para_id);
+		self.ss58_prefix = self.ss58_prefix.or(other.ss58_prefix);
+		self.token = self.token.take().or_else(|| other.token.clone());
+		self.genesis_hash = self.genesis_hash.take().or_else(|| other.genesis_hash.clone());
+		self.system_chain = self.system_chain.take().or_else(|| other.system_chain.clone());
+	}
+
+	/// Whether the chain is a parachain
+	pub fn is_parachain(&self) -> bool {
+		self.para_id.is_some()
+	}
+
+	/// Check the identity reported by an endpoint against this chain.
+	///
+	/// Hashes are compared ignoring the case, as are the names. Returns the expected and found values
+	/// on mismatch. The genesis hash is only checked if both sides know it.
+	pub fn check(&self, system_chain: &str, genesis_hash: Option<&str>) -> Option<(String, String)> {
+		if let (Some(expected), Some(found)) = (&self.genesis_hash, genesis_hash) {
+			if !expected.eq_ignore_ascii_case(found) {
+				return Some((format!("genesis {expected}"), format!("genesis {found}")));
+			}
+		}
+		match &self.system_chain {
+			Some(expected) if !expected.eq_ignore_ascii_case(system_chain) => {
+				Some((format!("chain '{expected}'"), format!("chain '{system_chain}'")))
+			}
+			_ => None,
+		}
+	}
+
+	/// Whether the genesis hash of the endpoints must be queried
+	pub fn needs_genesis(&self) -> bool {
+		self.genesis_hash.is_some()
+	}
+}
+
+/// A chain of the merged catalog, see [LocalData::chains](crate::LocalData::chains)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CatalogChain {
+	/// The chain, merged from all the registries listing it
+	#[serde(flatten)]
+	pub chain: Chain,
+
+	/// Names under which the registries list the endpoints of the chain
+	pub keys: Vec<ChainName>,
+
+	/// Number of distinct endpoints of the chain
+	pub endpoints: usize,
+}
+
+impl CatalogChain {
+	/// Whether `name` is the name, the id or one of the keys of the chain, ignoring the case and punctuation
+	pub fn is_named(&self, name: &str) -> bool {
+		let name = normalize(name);
+		[&self.chain.name, &self.chain.id].into_iter().chain(&self.keys).any(|n| normalize(n) == name)
+	}
+}
+
+#[cfg(test)]
+mod test_chain {
+	use super::*;
+
+	#[test]
+	fn test_check() {
+		let polkadot = Chain {
+			genesis_hash: Some("0x91b171bb158e2d3848fa23a9f1c25182fb8e20313b2c1eb49219da7a70ce90c3".to_string()),
+			system_chain: Some("Polkadot".to_string()),
+			..Default::default()
+		};
+		let genesis = polkadot.genesis_hash.as_deref().map(str::to_uppercase);
+		assert_eq!(None, polkadot.check("polkadot", genesis.as_deref()));
+
+		let kusama = "0xb0a8d493285c2df73290dfb7e61f870f17b41801197a149ca93654499ea3dafe";
+		let (expected, found) = polkadot.check("Polkadot", Some(kusama)).unwrap();
+		assert!(expected.contains("0x91b1") && found.contains("0xb0a8"));
+		assert_eq!(
+			Some(("chain 'Polkadot'".to_string(), "chain 'Kusama'".to_string())),
+			polkadot.check("Kusama", None)
+		);
+		assert_eq!(None, Chain::default().check("Kusama", Some(kusama)));
+	}
+
+	#[test]
+	fn test_merge() {
+		let mut polkadot = Chain { token: Some("DOT".to_string()), ..Chain::named("Polkadot") };
+		let other = Chain {
+			aliases: vec!["dot".to_string()],
+			ss58_prefix: Some(0),
+			token: Some("XYZ".to_string()),
+			..Chain::named("polkadot")
+		};
+		assert_eq!("polkadot", polkadot.id);
+		assert!(polkadot.is_same(&other));
+		assert!(!polkadot.is_same(&Chain::named("Kusama")));
+
+		polkadot.merge(&other);
+		assert_eq!(vec!["dot"], polkadot.aliases);
+		assert_eq!((Some(0), Some("DOT")), (polkadot.ss58_prefix, polkadot.token.as_deref()));
+
+		let genesis = |hash: &str, name: &str| Chain { genesis_hash: Some(hash.to_string()), ..Chain::named(name) };
+		assert!(genesis("0xAB", "Statemint").is_same(&genesis("0xab", "Asset Hub Polkadot")));
+		assert!(!genesis("0xab", "Polkadot").is_same(&genesis("0xcd", "Polkadot")));
+	}
+}
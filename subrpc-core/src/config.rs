@@ -1,6 +1,353 @@
-use serde::Serialize;
+use crate::{
+	resolver::normalize, EndpointStats, Error, NetworkOptions, Paths, Protocol, Result, Secrets, StorageBackend,
+	DEFAULT_CONCURRENCY, DEFAULT_TIMEOUT,
+};
+use log::*;
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::BTreeMap,
+	env,
+	fmt::Display,
+	fs::{self, File},
+	io::Write,
+	path::{Path, PathBuf},
+	time::Duration,
+};
 
-#[derive(Debug, Serialize, Default)]
-pub struct Config {}
+/// Environment variable overriding the [Config::browser_url]
+pub const ENV_BROWSER_URL: &str = "SUBRPC_BROWSER_URL";
 
-impl Config {}
+/// Strategy used to rank the endpoints
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RankingStrategy {
+	/// Use [EndpointStats::score], which combines availability and latency
+	#[default]
+	Score,
+
+	/// Lowest latency first
+	Latency,
+
+	/// Highest availability first
+	Availability,
+}
+
+impl RankingStrategy {
+	/// Value used to rank the endpoints, the higher the better.
+	/// Endpoints serving another chain always rank 0, lagging or syncing nodes are penalized
+	/// with their [health factor](EndpointStats::health_factor) whatever the strategy.
+	pub fn rank(&self, stats: &EndpointStats) -> f32 {
+		if stats.is_misconfigured() {
+			return 0f32;
+		}
+		match self {
+			RankingStrategy::Score => stats.score(),
+			RankingStrategy::Availability => stats.availability().unwrap_or(0f32) * stats.health_factor(),
+			RankingStrategy::Latency => {
+				if stats.latency.is_finite() && stats.latency > 0f32 {
+					stats.health_factor() / (1f32 + stats.latency)
+				} else {
+					0f32
+				}
+			}
+		}
+	}
+}
+
+/// Where the value of a config item comes from
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigSource {
+	Default,
+	File(PathBuf),
+	Env(String),
+}
+
+impl Display for ConfigSource {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ConfigSource::Default => f.write_str("default"),
+			ConfigSource::File(file) => write!(f, "file: {}", file.display()),
+			ConfigSource::Env(var) => write!(f, "env: {var}"),
+		}
+	}
+}
+
+/// One item of the effective config, see [Config::entries]
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct ConfigEntry {
+	pub key: String,
+	pub value: toml::Value,
+	pub source: ConfigSource,
+}
+
+/// User configuration, stored as toml.
+///
+/// Missing items use their default value.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	/// Timeout in seconds for each network request, such as a ping
+	pub timeout: u64,
+
+	/// Maximum number of network requests running concurrently
+	pub concurrency: usize,
+
+	/// Url used to open an endpoint in a browser. The `{}` marker is substituted by the endpoint url.
+	pub browser_url: String,
+
+	/// Protocols to prefer when picking endpoints, the first is the most preferred.
+	/// Endpoints using a protocol not listed here come last. No preference if empty.
+	pub preferred_protocols: Vec<Protocol>,
+
+	/// If not empty, only endpoints having at least one of those labels are used
+	pub include_labels: Vec<String>,
+
+	/// Endpoints having any of those labels are never used
+	pub exclude_labels: Vec<String>,
+
+	/// Strategy used to rank the endpoints
+	pub ranking: RankingStrategy,
+
+	/// Whether pings also probe the health of the nodes: sync state, peers and best block.
+	/// Lagging or syncing nodes then rank lower.
+	pub health_probe: bool,
+
+	/// Secrets used to authenticate to the endpoints, see [Secrets].
+	/// They are never shown by [Self::entries].
+	#[serde(skip_serializing_if = "Secrets::is_empty")]
+	pub secrets: Secrets,
+
+	/// Local aliases of the chains, in addition to the aliases declared by the registries.
+	/// For instance `Statemint = ["ahp", "asset-hub"]`.
+	#[serde(skip_serializing_if = "BTreeMap::is_empty")]
+	pub aliases: BTreeMap<String, Vec<String>>,
+
+	/// Where the local data is stored: `json`, `sqlite` or `memory`, see [StorageBackend]
+	pub storage: StorageBackend,
+
+	/// Sources of the items that do not use their default value
+	#[serde(skip)]
+	sources: Vec<(String, ConfigSource)>,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Self {
+			timeout: DEFAULT_TIMEOUT.as_secs(),
+			concurrency: DEFAULT_CONCURRENCY,
+			browser_url: "https://polkadot.js.org/apps/?rpc={}".to_string(),
+			preferred_protocols: vec![],
+			include_labels: vec![],
+			exclude_labels: vec![],
+			ranking: RankingStrategy::default(),
+			health_probe: true,
+			secrets: Secrets::default(),
+			aliases: BTreeMap::new(),
+			storage: StorageBackend::default(),
+			sources: vec![],
+		}
+	}
+}
+
+impl Config {
+	/// Default file of the config, see [Paths::config_file]
+	pub fn get_default_file() -> Result<PathBuf> {
+		Ok(Paths::new()?.config_file())
+	}
+
+	/// Parse and validate a config from a toml string.
+	pub fn parse(s: &str) -> Result<Self> {
+		let config: Self = toml::from_str(s)?;
+		config.validate()?;
+		Ok(config)
+	}
+
+	/// Load the config from `file` and apply the overrides from the environment.
+	/// A missing file is not an error: the default config is used.
+	pub fn load(file: &Path) -> Result<Self> {
+		let mut config = if file.exists() {
+			debug!("Loading config from {}", file.display());
+			let s = fs::read_to_string(file)?;
+			let mut config = Self::parse(&s)?;
+			let table: toml::Table = toml::from_str(&s)?;
+			config.sources = table.keys().map(|key| (key.clone(), ConfigSource::File(file.to_path_buf()))).collect();
+			config
+		} else {
+			debug!("No config found at {}, using defaults", file.display());
+			Self::default()
+		};
+
+		if let Ok(browser_url) = env::var(ENV_BROWSER_URL) {
+			config.browser_url = browser_url;
+			config.set_source("browser_url", ConfigSource::Env(ENV_BROWSER_URL.to_string()));
+		}
+
+		config.validate()?;
+		Ok(config)
+	}
+
+	/// Save the config as toml
+	pub fn save(&self, file: &Path) -> Result<()> {
+		let toml = toml::to_string_pretty(self).map_err(|e| Error::Config(e.to_string()))?;
+		if let Some(dir) = file.parent() {
+			fs::create_dir_all(dir)?;
+		}
+		let mut fs = File::create(file)?;
+		fs.write_all(toml.as_bytes())?;
+		Ok(())
+	}
+
+	/// Check that the values make sense
+	pub fn validate(&self) -> Result<()> {
+		if self.timeout == 0 {
+			return Err(Error::Config("timeout must be greater than 0".to_string()));
+		}
+		if self.concurrency == 0 {
+			return Err(Error::Config("concurrency must be greater than 0".to_string()));
+		}
+		if !self.browser_url.contains("{}") {
+			return Err(Error::Config("browser_url must contain the {} marker".to_string()));
+		}
+		if let Some(p) = self.preferred_protocols.iter().enumerate().find_map(|(i, p)| {
+			if self.preferred_protocols[..i].contains(p) {
+				Some(p)
+			} else {
+				None
+			}
+		}) {
+			return Err(Error::Config(format!("preferred_protocols lists {p} more than once")));
+		}
+		if let Some(label) = self.include_labels.iter().find(|l| self.exclude_labels.contains(l)) {
+			return Err(Error::Config(format!("label '{label}' is both included and excluded")));
+		}
+		if let Some((chain, _)) =
+			self.aliases.iter().find(|(_, aliases)| aliases.iter().any(|a| normalize(a).is_empty()))
+		{
+			return Err(Error::Config(format!("the aliases of '{chain}' must contain letters or digits")));
+		}
+		Ok(())
+	}
+
+	fn set_source(&mut self, key: &str, source: ConfigSource) {
+		self.sources.retain(|(k, _)| k != key);
+		self.sources.push((key.to_string(), source));
+	}
+
+	/// Source of a given config item
+	pub fn source(&self, key: &str) -> ConfigSource {
+		self.sources.iter().find(|(k, _)| k == key).map(|(_, s)| s.clone()).unwrap_or(ConfigSource::Default)
+	}
+
+	/// List the effective config items with their source. The secrets are redacted.
+	pub fn entries(&self) -> Vec<ConfigEntry> {
+		let redacted = Self { secrets: self.secrets.redacted(), ..self.clone() };
+		match toml::Value::try_from(redacted) {
+			Ok(toml::Value::Table(table)) => {
+				table.into_iter().map(|(key, value)| ConfigEntry { source: self.source(&key), key, value }).collect()
+			}
+			_ => vec![],
+		}
+	}
+
+	/// [NetworkOptions] matching this config
+	pub fn network_options(&self) -> NetworkOptions {
+		NetworkOptions::new(self.concurrency, Duration::from_secs(self.timeout))
+			.with_secrets(self.secrets.clone())
+			.with_health_probe(self.health_probe)
+	}
+
+	/// Rank of a protocol according to [Self::preferred_protocols], the lower the better.
+	pub fn protocol_rank(&self, protocol: Protocol) -> usize {
+		self.preferred_protocols.iter().position(|p| *p == protocol).unwrap_or(self.preferred_protocols.len())
+	}
+
+	/// Whether the given labels pass the [Self::include_labels] and [Self::exclude_labels] filters.
+	/// Labels are compared ignoring the case.
+	pub fn accepts_labels(&self, labels: &[String]) -> bool {
+		let has = |wanted: &String| labels.iter().any(|l| l.eq_ignore_ascii_case(wanted));
+		(self.include_labels.is_empty() || self.include_labels.iter().any(has)) && !self.exclude_labels.iter().any(has)
+	}
+}
+
+#[cfg(test)]
+mod test_config {
+	use super::*;
+	use std::env;
+
+	#[test]
+	fn test_parse() {
+		let config = Config::parse(
+			r#"
+			timeout = 3
+			preferred_protocols = ["wss", "https"]
+			exclude_labels = ["testnet"]
+			ranking = "latency"
+			"#,
+		)
+		.unwrap();
+		assert_eq!(3, config.timeout);
+		assert_eq!(DEFAULT_CONCURRENCY, config.concurrency);
+		assert_eq!(RankingStrategy::Latency, config.ranking);
+		assert_eq!(0, config.protocol_rank(Protocol::Wss));
+		assert_eq!(2, config.protocol_rank(Protocol::Ws));
+		assert!(config.accepts_labels(&["Mainnet".to_string()]));
+		assert!(!config.accepts_labels(&["Mainnet".to_string(), "TestNet".to_string()]));
+	}
+
+	#[test]
+	fn test_invalid() {
+		assert!(Config::parse("timeout = 0").is_err());
+		assert!(Config::parse("unknown = 1").is_err());
+		assert!(Config::parse(r#"browser_url = "https://example.com""#).is_err());
+		assert!(Config::parse(r#"preferred_protocols = ["wss", "wss"]"#).is_err());
+		assert!(Config::parse(r#"preferred_protocols = ["ftp"]"#).is_err());
+	}
+
+	#[test]
+	fn test_load_sources() {
+		let file = env::temp_dir().join("subrpc_test_config.toml");
+		fs::write(&file, "concurrency = 4\n").unwrap();
+		let config = Config::load(&file).unwrap();
+		assert_eq!(4, config.concurrency);
+		assert_eq!(ConfigSource::File(file.clone()), config.source("concurrency"));
+		assert_eq!(ConfigSource::Default, config.source("timeout"));
+
+		let entries = config.entries();
+		assert!(entries.iter().any(|e| e.key == "concurrency" && e.value == toml::Value::Integer(4)));
+
+		config.save(&file).unwrap();
+		let reloaded = Config::load(&file).unwrap();
+		assert_eq!(config.concurrency, reloaded.concurrency);
+		assert_eq!(ConfigSource::File(file.clone()), reloaded.source("timeout"));
+	}
+
+	#[test]
+	fn test_secrets() {
+		let config = Config::parse(
+			r#"
+			[secrets.OnFinality]
+			API_KEY = "s3cr3t"
+			"#,
+		)
+		.unwrap();
+		assert_eq!(Some("s3cr3t".to_string()), config.network_options().secrets.get("OnFinality", "API_KEY"));
+
+		let secrets = config.entries().into_iter().find(|e| e.key == "secrets").unwrap();
+		assert_eq!("***", secrets.value["OnFinality"]["API_KEY"].as_str().unwrap());
+		assert!(!format!("{config:?}").contains("s3cr3t"));
+	}
+
+	#[test]
+	fn test_aliases() {
+		let config = Config::parse(
+			r#"
+			[aliases]
+			Statemint = ["ahp", "asset-hub"]
+			"#,
+		)
+		.unwrap();
+		assert_eq!(vec!["ahp", "asset-hub"], config.aliases["Statemint"]);
+		assert!(Config::parse("aliases = { Polkadot = [\"-\"] }").is_err());
+	}
+}
@@ -1,4 +1,5 @@
-use crate::{empty_string_array, EndpointStats, EndpointUrl};
+use crate::{empty_string_array, Auth, EndpointStats, EndpointUrl, Result, Secrets};
+use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 
 #[allow(clippy::derived_hash_with_manual_eq)]
@@ -15,11 +16,30 @@ pub struct Endpoint {
 	#[serde(default = "empty_string_array")]
 	pub aliases: Vec<String>,
 
-	/// Endpoint URL
+	/// Endpoint URL. It may contain placeholders such as `{API_KEY}`, see [Self::resolved_url].
 	pub url: EndpointUrl,
 
+	/// How the endpoint authenticates, if it does.
+	/// The registry only describes the auth, the secrets come from the [Secrets].
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub auth: Option<Auth>,
+
 	#[serde(default)]
 	pub stats: EndpointStats,
+
+	/// Labels set locally, used instead of the labels of the registry.
+	/// They are kept when the registry is updated.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub label_overrides: Option<Vec<String>>,
+
+	/// Notes of the user about this endpoint, kept when the registry is updated
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub notes: Option<String>,
+
+	/// DateTime at which the endpoint vanished from its registry.
+	/// Removed endpoints are kept along with their stats but are no longer used.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub removed: Option<DateTime<Local>>,
 }
 
 impl PartialEq for Endpoint {
@@ -31,13 +51,99 @@ impl PartialEq for Endpoint {
 impl Eq for Endpoint {}
 
 impl Endpoint {
+	/// Create a new endpoint.
+	///
+	/// # Panics
+	///
+	/// Panics if the url is invalid, use [Self::try_new] for urls that are not known to be valid.
 	pub fn new(name: &str, url: &str, labels: Vec<String>, aliases: Vec<String>) -> Self {
-		Self {
+		Self::try_new(name, url, labels, aliases).expect("Endpoint url should be valid")
+	}
+
+	/// Create a new endpoint, failing with [Error::InvalidUrl](crate::Error::InvalidUrl) if the url is invalid.
+	pub fn try_new(name: &str, url: &str, labels: Vec<String>, aliases: Vec<String>) -> Result<Self> {
+		Ok(Self {
 			name: name.to_string(),
-			url: EndpointUrl::try_from(url).unwrap(),
+			url: EndpointUrl::try_from(url)?,
 			labels,
 			aliases,
+			auth: None,
 			stats: EndpointStats::default(),
+			label_overrides: None,
+			notes: None,
+			removed: None,
+		})
+	}
+
+	/// Effective labels of the endpoint: the [Self::label_overrides] if any,
+	/// the labels of the registry otherwise.
+	pub fn labels(&self) -> &[String] {
+		self.label_overrides.as_deref().unwrap_or(&self.labels)
+	}
+
+	/// Whether the endpoint vanished from its registry
+	pub fn is_removed(&self) -> bool {
+		self.removed.is_some()
+	}
+
+	/// The url with its placeholders replaced by the percent encoded secrets of this endpoint.
+	/// Fails with [Error::MissingSecret](crate::Error::MissingSecret) if a secret is not set.
+	pub fn resolved_url(&self, secrets: &Secrets) -> Result<EndpointUrl> {
+		EndpointUrl::try_from(secrets.resolve_url(&self.name, &self.url.to_string())?)
+	}
+
+	/// Headers to send to the endpoint, with their placeholders replaced by the secrets of this endpoint.
+	pub fn headers(&self, secrets: &Secrets) -> Result<Vec<(String, String)>> {
+		match &self.auth {
+			Some(auth) => Ok(auth.header(&self.name, secrets)?.into_iter().collect()),
+			None => Ok(vec![]),
 		}
 	}
 }
+
+#[cfg(test)]
+mod test_endpoint {
+	use super::*;
+	use crate::Error;
+
+	#[test]
+	fn test_secrets() {
+		let mut endpoint: Endpoint = serde_json::from_str(
+			r#"{ "name": "Provider", "url": "wss://rpc.provider.io/ws/{API_KEY}", "auth": { "type": "url" } }"#,
+		)
+		.unwrap();
+		assert_eq!("wss://rpc.provider.io/ws/{API_KEY}", endpoint.url.to_string());
+		assert!(matches!(endpoint.resolved_url(&Secrets::default()), Err(Error::MissingSecret { .. })));
+
+		let mut secrets = Secrets::default();
+		secrets.insert("Provider", "API_KEY", "s3cr3t");
+		secrets.insert("Provider", "TOKEN", "t0k3n");
+		assert_eq!("wss://rpc.provider.io/ws/s3cr3t", endpoint.resolved_url(&secrets).unwrap().to_string());
+		assert!(endpoint.headers(&secrets).unwrap().is_empty());
+
+		// The secret stays in its path segment
+		let mut tricky = secrets.clone();
+		tricky.insert("Provider", "API_KEY", "k/../admin?x=1");
+		let url = endpoint.resolved_url(&tricky).unwrap();
+		assert_eq!(("rpc.provider.io", "/ws/k%2F..%2Fadmin%3Fx%3D1", None), (url.host(), url.path(), url.query()));
+		assert_eq!(
+			vec![("Authorization".to_string(), "Bearer k/../admin?x=1".to_string())],
+			Auth::Header { name: "Authorization".to_string(), value: "Bearer {API_KEY}".to_string() }
+				.header("Provider", &tricky)
+				.unwrap()
+				.into_iter()
+				.collect::<Vec<_>>()
+		);
+
+		endpoint.auth = Some(Auth::Header { name: "Authorization".to_string(), value: "Bearer {TOKEN}".to_string() });
+		assert_eq!(
+			vec![("Authorization".to_string(), "Bearer t0k3n".to_string())],
+			endpoint.headers(&secrets).unwrap()
+		);
+
+		// The secrets never end up in the serialized endpoint
+		let json = serde_json::to_string(&endpoint).unwrap();
+		assert!(!json.contains("s3cr3t") && !json.contains("t0k3n"));
+		assert!(json.contains("{API_KEY}"));
+	}
+}
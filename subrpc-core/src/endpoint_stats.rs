@@ -1,12 +1,169 @@
+use crate::{Capabilities, Health};
+use chrono::{DateTime, Duration, Local};
 use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fmt::Display, str::FromStr};
+
+/// Weight of a new latency sample in the exponentially weighted moving average
+pub const LATENCY_ALPHA: f32 = 0.3;
+
+/// Half life, in seconds, of the weight of a ping result in the success ratio: a ping done
+/// one half life ago counts half as much as a ping done now.
+pub const SUCCESS_HALF_LIFE: f64 = 24.0 * 3600.0;
+
+/// Latency, in seconds, for which the latency factor of the score is 0.5
+pub const LATENCY_REFERENCE: f32 = 0.5;
+
+/// Interval between pings the history is sized for, in minutes
+pub const HISTORY_PING_INTERVAL: i64 = 5;
+
+/// Maximum number of samples kept in the history of an endpoint: enough for a [Window::Week]
+/// of pings every [HISTORY_PING_INTERVAL] minutes
+pub const HISTORY_CAPACITY: usize = (7 * 24 * 60 / HISTORY_PING_INTERVAL) as usize;
+
+/// Minimum number of latency samples in the last day for the history to be used in the score
+pub const HISTORY_MIN_SAMPLES: usize = 3;
+
+/// Result of a single ping
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Sample {
+	/// DateTime of the ping
+	pub time: DateTime<Local>,
+
+	/// Whether the endpoint answered
+	pub success: bool,
+
+	/// Latency in seconds, for successful pings
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub latency: Option<f32>,
+}
+
+/// The endpoint answered for another chain than the one it is listed for
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct IdentityMismatch {
+	/// DateTime of the ping that detected the mismatch
+	pub time: DateTime<Local>,
+
+	/// Identity expected by the registry, such as `genesis 0x91b1…`
+	pub expected: String,
+
+	/// Identity reported by the endpoint
+	pub found: String,
+}
+
+impl Display for IdentityMismatch {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "expected {}, found {}", self.expected, self.found)
+	}
+}
+
+/// Time windows used to report on the history of an endpoint
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Window {
+	Hour,
+	Day,
+	Week,
+}
+
+impl Window {
+	pub fn duration(&self) -> Duration {
+		match self {
+			Window::Hour => Duration::hours(1),
+			Window::Day => Duration::days(1),
+			Window::Week => Duration::weeks(1),
+		}
+	}
+}
+
+impl Display for Window {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Window::Hour => "hour",
+			Window::Day => "day",
+			Window::Week => "week",
+		})
+	}
+}
+
+impl FromStr for Window {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_ascii_lowercase().as_str() {
+			"hour" | "h" | "1h" => Ok(Window::Hour),
+			"day" | "d" | "1d" => Ok(Window::Day),
+			"week" | "w" | "1w" => Ok(Window::Week),
+			_ => Err(format!("Invalid window '{s}', expected hour, day or week")),
+		}
+	}
+}
+
+/// Stats of an endpoint computed from the samples of a given [Window]
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct WindowStats {
+	pub window: String,
+
+	/// Number of pings in the window
+	pub samples: usize,
+
+	/// Ratio of successful pings in the window, `None` without samples
+	pub availability: Option<f32>,
+
+	/// Latency percentiles in seconds, `None` without successful pings
+	pub p50: Option<f32>,
+	pub p90: Option<f32>,
+	pub p99: Option<f32>,
+}
+
+/// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[f32], p: f32) -> Option<f32> {
+	if sorted.is_empty() {
+		return None;
+	}
+	let rank = ((p / 100f32) * sorted.len() as f32).ceil() as usize;
+	Some(sorted[rank.clamp(1, sorted.len()) - 1])
+}
 
 #[allow(clippy::derived_hash_with_manual_eq)]
 /// Simple stats to help picking the best endpoint
 #[derive(Debug, Default, Deserialize, Serialize, Clone)]
+#[serde(default)]
 pub struct EndpointStats {
-	pub failures: u16,
-	pub success: u16,
+	/// Total number of failed pings
+	pub failures: u32,
+
+	/// Total number of successful pings
+	pub success: u32,
+
+	/// Exponentially weighted moving average of the latency of the successful pings, in seconds.
+	/// 0 means the latency is unknown.
 	pub latency: f32,
+
+	/// Time decayed number of successful pings, see [SUCCESS_HALF_LIFE]
+	pub weighted_success: f64,
+
+	/// Time decayed number of pings, see [SUCCESS_HALF_LIFE]
+	pub weighted_total: f64,
+
+	/// DateTime of the last successful ping
+	pub last_success: Option<DateTime<Local>>,
+
+	/// DateTime of the last failed ping
+	pub last_failure: Option<DateTime<Local>>,
+
+	/// Most recent ping results, at most [HISTORY_CAPACITY] and no older than a week
+	pub history: VecDeque<Sample>,
+
+	/// Set when the last successful ping found the endpoint serving another chain
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub mismatch: Option<IdentityMismatch>,
+
+	/// Health found by the last successful health probe, see [Health]
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub health: Option<Health>,
+
+	/// Capabilities found by the last probe, see [Registry::probe](crate::Registry::probe)
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub capabilities: Option<Capabilities>,
 }
 
 impl std::hash::Hash for EndpointStats {
@@ -17,18 +174,315 @@ impl std::hash::Hash for EndpointStats {
 }
 
 impl EndpointStats {
+	/// Record the result of a ping done now.
 	pub fn add(&mut self, state: bool, latency: Option<f32>) {
+		self.add_at(state, latency, Local::now())
+	}
+
+	/// Record the result of a ping done at `now`.
+	pub fn add_at(&mut self, state: bool, latency: Option<f32>, now: DateTime<Local>) {
+		self.add_many_at(1, state, latency, now)
+	}
+
+	/// Record `count` results with the same `state` and the average `latency`, such as the responses relayed
+	/// by the [Proxy](crate::Proxy). The counters are updated as if [Self::add] was called `count` times,
+	/// while the history only gets one sample.
+	pub fn add_many(&mut self, count: u32, state: bool, latency: Option<f32>) {
+		self.add_many_at(count, state, latency, Local::now())
+	}
+
+	/// Same as [Self::add_many], for results recorded at `now`.
+	pub fn add_many_at(&mut self, count: u32, state: bool, latency: Option<f32>, now: DateTime<Local>) {
+		if count == 0 {
+			return;
+		}
+		self.seed_weights();
+
+		let decay = match self.last_seen() {
+			Some(last) => {
+				let elapsed = (now - last).num_milliseconds().max(0) as f64 / 1000f64;
+				0.5f64.powf(elapsed / SUCCESS_HALF_LIFE)
+			}
+			None => 1f64,
+		};
+		self.weighted_success = self.weighted_success * decay + if state { count as f64 } else { 0f64 };
+		self.weighted_total = self.weighted_total * decay + count as f64;
+
 		if state {
-			self.success += 1;
-			if let Some(l) = latency {
-				self.latency = (l * self.success as f32 + l) / self.success as f32;
+			self.success = self.success.saturating_add(count);
+			self.last_success = Some(now);
+			if let Some(l) = latency.filter(|l| l.is_finite() && *l >= 0f32) {
+				self.latency =
+					if self.latency > 0f32 { LATENCY_ALPHA * l + (1f32 - LATENCY_ALPHA) * self.latency } else { l };
 			}
 		} else {
-			self.failures += 1;
+			self.failures = self.failures.saturating_add(count);
+			self.last_failure = Some(now);
+		}
+
+		let latency = if state { latency.filter(|l| l.is_finite() && *l >= 0f32) } else { None };
+		self.history.push_back(Sample { time: now, success: state, latency });
+		self.prune_history(now);
+	}
+
+	/// Drop the samples older than a week and keep at most [HISTORY_CAPACITY] samples.
+	fn prune_history(&mut self, now: DateTime<Local>) {
+		let oldest = now - Window::Week.duration();
+		while self.history.front().is_some_and(|s| s.time < oldest) || self.history.len() > HISTORY_CAPACITY {
+			self.history.pop_front();
 		}
 	}
 
+	/// Stats over the given [Window], ending now.
+	pub fn window_stats(&self, window: Window) -> WindowStats {
+		self.window_stats_at(window, Local::now())
+	}
+
+	/// Stats over the given [Window], ending at `now`.
+	pub fn window_stats_at(&self, window: Window, now: DateTime<Local>) -> WindowStats {
+		let start = now - window.duration();
+		let samples: Vec<&Sample> = self.history.iter().filter(|s| s.time >= start && s.time <= now).collect();
+		let success = samples.iter().filter(|s| s.success).count();
+		let mut latencies: Vec<f32> = samples.iter().filter_map(|s| s.latency).collect();
+		latencies.sort_by(|a, b| a.total_cmp(b));
+
+		WindowStats {
+			window: window.to_string(),
+			samples: samples.len(),
+			availability: if samples.is_empty() { None } else { Some(success as f32 / samples.len() as f32) },
+			p50: percentile(&latencies, 50f32),
+			p90: percentile(&latencies, 90f32),
+			p99: percentile(&latencies, 99f32),
+		}
+	}
+
+	/// Whether the endpoint serves another chain than the one it is listed for, see [Self::mismatch]
+	pub fn is_misconfigured(&self) -> bool {
+		self.mismatch.is_some()
+	}
+
+	/// Penalty applied to the score of lagging or syncing nodes, see [Health::factor].
+	/// Endpoints that were never probed are not penalized.
+	pub fn health_factor(&self) -> f32 {
+		self.health.as_ref().map(Health::factor).unwrap_or(1f32)
+	}
+
+	/// DateTime of the last ping, successful or not.
+	pub fn last_seen(&self) -> Option<DateTime<Local>> {
+		self.last_success.max(self.last_failure)
+	}
+
+	/// Stats written by older versions only have the raw counters.
+	/// We use them as starting point for the weighted counters.
+	fn seed_weights(&mut self) {
+		if self.weighted_total <= 0f64 && self.success.saturating_add(self.failures) > 0 {
+			self.weighted_success = self.success as f64;
+			self.weighted_total = (self.success as f64) + (self.failures as f64);
+		}
+	}
+
+	/// Time decayed success ratio in the range `[0, 1]`, or `None` if the endpoint was never pinged.
+	pub fn availability(&self) -> Option<f32> {
+		let (success, total) = if self.weighted_total > 0f64 {
+			(self.weighted_success, self.weighted_total)
+		} else {
+			(self.success as f64, (self.success as f64) + (self.failures as f64))
+		};
+
+		if total > 0f64 {
+			Some((success / total).clamp(0f64, 1f64) as f32)
+		} else {
+			None
+		}
+	}
+
+	/// Score of the endpoint in the range `[0, 1]`, the higher the better.
+	///
+	/// The score is the [availability](Self::availability) multiplied by a latency factor
+	/// `LATENCY_REFERENCE / (LATENCY_REFERENCE + latency)`: an endpoint answering in
+	/// [LATENCY_REFERENCE] seconds sees its score halved. When the history holds at least
+	/// [HISTORY_MIN_SAMPLES] latencies for the last day, the p90 latency of that day is used
+	/// if it is worse than the average, so that endpoints with frequent spikes rank lower.
+	/// The result is then multiplied by the [health factor](Self::health_factor) of the endpoint.
+	/// An unknown latency does not penalize the endpoint. Endpoints that were never pinged
+	/// or that [serve another chain](Self::is_misconfigured) score 0.
+	///
+	/// This function never panics and always returns a finite number.
 	pub fn score(&self) -> f32 {
-		(self.success - self.failures) as f32 * 1f32 / self.latency / 10f32
+		self.score_at(Local::now())
+	}
+
+	/// Same as [Self::score], using the history up to `now`.
+	pub fn score_at(&self, now: DateTime<Local>) -> f32 {
+		if self.is_misconfigured() {
+			return 0f32;
+		}
+		let availability = self.availability().unwrap_or(0f32);
+		let mut latency = if self.latency.is_finite() { self.latency.max(0f32) } else { 0f32 };
+
+		let day_start = now - Window::Day.duration();
+		let day_latencies = self.history.iter().filter(|s| s.time >= day_start && s.latency.is_some()).count();
+		if day_latencies >= HISTORY_MIN_SAMPLES {
+			if let Some(p90) = self.window_stats_at(Window::Day, now).p90 {
+				latency = latency.max(p90);
+			}
+		}
+
+		let score = availability * LATENCY_REFERENCE / (LATENCY_REFERENCE + latency) * self.health_factor();
+
+		if score.is_finite() {
+			score
+		} else {
+			0f32
+		}
+	}
+}
+
+#[cfg(test)]
+mod test_endpoint_stats {
+	use super::*;
+
+	#[test]
+	fn test_latency_ewma() {
+		let mut stats = EndpointStats::default();
+		stats.add(true, Some(1.0));
+		assert_eq!(1.0, stats.latency);
+		stats.add(true, Some(2.0));
+		assert!((stats.latency - 1.3).abs() < 1e-6);
+		stats.add(false, None);
+		assert!((stats.latency - 1.3).abs() < 1e-6);
+	}
+
+	#[test]
+	fn test_score_never_panics() {
+		let mut stats = EndpointStats::default();
+		assert_eq!(0f32, stats.score());
+
+		// more failures than success
+		stats.add(false, None);
+		stats.add(false, None);
+		stats.add(true, Some(0.0));
+		let score = stats.score();
+		assert!(score.is_finite());
+		assert!((0f32..=1f32).contains(&score));
+
+		stats.latency = f32::NAN;
+		assert!(stats.score().is_finite());
+	}
+
+	#[test]
+	fn test_score_ordering() {
+		let mut fast = EndpointStats::default();
+		let mut slow = EndpointStats::default();
+		let mut flaky = EndpointStats::default();
+		for _ in 0..4 {
+			fast.add(true, Some(0.1));
+			slow.add(true, Some(1.0));
+			flaky.add(true, Some(0.1));
+			flaky.add(false, None);
+		}
+		assert!(fast.score() > slow.score());
+		assert!(fast.score() > flaky.score());
+	}
+
+	#[test]
+	fn test_decay() {
+		let start = Local::now() - Duration::days(30);
+		let mut stats = EndpointStats::default();
+		for _ in 0..10 {
+			stats.add_at(false, None, start);
+		}
+		stats.add_at(true, Some(0.1), start + Duration::days(30));
+
+		// a month old failures weight much less than a fresh success
+		assert!(stats.availability().unwrap() > 0.9);
+		assert_eq!(10, stats.failures);
+		assert_eq!(1, stats.success);
+	}
+
+	#[test]
+	fn test_legacy_format() {
+		let json = r#"{ "failures": 3, "success": 1, "latency": 0.25 }"#;
+		let mut stats: EndpointStats = serde_json::from_str(json).unwrap();
+		assert_eq!(Some(0.25), stats.availability());
+		assert!(stats.last_seen().is_none());
+
+		stats.add(true, Some(0.25));
+		assert_eq!(2, stats.success);
+		assert_eq!(Some(0.4), stats.availability());
+	}
+
+	#[test]
+	fn test_percentiles() {
+		let now = Local::now();
+		let mut stats = EndpointStats::default();
+		(1..=100).for_each(|i| stats.add_at(true, Some(i as f32 / 100f32), now - Duration::minutes(100 - i)));
+		stats.add_at(false, None, now);
+
+		let hour = stats.window_stats_at(Window::Hour, now);
+		assert_eq!(62, hour.samples);
+		assert_eq!(Some(0.7), hour.p50);
+		assert_eq!(Some(0.94), hour.p90);
+		assert_eq!(Some(1.0), hour.p99);
+		assert_eq!(Some(61f32 / 62f32), hour.availability);
+
+		let day = stats.window_stats_at(Window::Day, now);
+		assert_eq!(101, day.samples);
+		assert_eq!(Some(0.5), day.p50);
+		assert_eq!(Some(0.99), day.p99);
+	}
+
+	#[test]
+	fn test_history_is_bounded() {
+		let now = Local::now();
+		let mut stats = EndpointStats::default();
+		stats.add_at(true, Some(0.1), now - Duration::days(8));
+		(0..HISTORY_CAPACITY + 10).for_each(|_| stats.add_at(true, Some(0.1), now));
+		assert_eq!(HISTORY_CAPACITY, stats.history.len());
+		assert!(stats.history.iter().all(|s| s.time == now));
+	}
+
+	#[test]
+	fn test_history_holds_a_week() {
+		let now = Local::now();
+		let mut stats = EndpointStats::default();
+		let pings = Window::Week.duration().num_minutes() / HISTORY_PING_INTERVAL;
+		(0..pings)
+			.rev()
+			.for_each(|i| stats.add_at(true, Some(0.1), now - Duration::minutes(i * HISTORY_PING_INTERVAL)));
+		assert_eq!(pings as usize, stats.window_stats_at(Window::Week, now).samples);
+		assert!(stats.history.front().is_some_and(|s| now - s.time > Duration::days(6)));
+	}
+
+	#[test]
+	fn test_add_many() {
+		let now = Local::now();
+		let mut stats = EndpointStats::default();
+		stats.add_many_at(0, false, None, now);
+		assert_eq!(None, stats.availability());
+		stats.add_many_at(9, true, Some(0.1), now);
+		stats.add_at(false, None, now);
+		assert_eq!((9, 1, 2), (stats.success, stats.failures, stats.history.len()));
+		assert!((stats.availability().unwrap() - 0.9).abs() < 1e-6);
+	}
+
+	#[test]
+	fn test_spikes_lower_the_score() {
+		let now = Local::now();
+		let mut steady = EndpointStats::default();
+		let mut spiky = EndpointStats::default();
+		for i in 0..10 {
+			let time = now - Duration::minutes(10 - i);
+			steady.add_at(true, Some(0.2), time);
+			spiky.add_at(true, Some(if i % 3 == 0 { 3.0 } else { 0.05 }), time);
+		}
+		assert!(steady.score_at(now) > spiky.score_at(now));
+	}
+
+	#[test]
+	fn test_window_from_str() {
+		assert_eq!(Ok(Window::Hour), "hour".parse());
+		assert_eq!(Ok(Window::Week), "W".parse());
+		assert!("year".parse::<Window>().is_err());
 	}
 }
@@ -1,57 +1,214 @@
-use std::fmt::Display;
+use std::{fmt::Display, path::PathBuf, str::FromStr};
 
-use anyhow::bail;
-use serde::{Deserialize, Serialize};
+use crate::Error;
+use serde::{Deserialize, Serialize, Serializer};
+use url::Url;
 
-#[derive(PartialEq, PartialOrd, Ord, Hash, Eq, Debug, Deserialize, Serialize, Clone)]
-#[serde(untagged)]
-#[serde(try_from = "&str")]
+/// Url of an endpoint.
+///
+/// The url is parsed and kept in its canonical form: the scheme and host are lowercased and
+/// default ports are dropped so `wss://rpc.polkadot.io` and `WSS://rpc.polkadot.io:443` are
+/// the same endpoint. The canonical form is used for `Eq`, `Hash` and `Ord`.
+///
+/// Besides http(s) and ws(s), local nodes can be reached with `unix:///path/to/node.sock`
+/// and `tcp://host:port`, both speaking newline delimited JSON-RPC.
+#[derive(PartialEq, PartialOrd, Ord, Hash, Eq, Debug, Deserialize, Clone)]
+#[serde(try_from = "String")]
 pub enum EndpointUrl {
-	Http(String),
-	Https(String),
-	Ws(String),
-	Wss(String),
+	Http(Url),
+	Https(Url),
+	Ws(Url),
+	Wss(Url),
+	Tcp(Url),
+	Unix(Url),
 }
 
-impl Display for EndpointUrl {
+/// Transport protocol of an endpoint
+#[derive(PartialEq, Eq, Hash, Debug, Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+	Http,
+	Https,
+	Ws,
+	Wss,
+	Tcp,
+	Unix,
+}
+
+impl Display for Protocol {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Protocol::Http => "http",
+			Protocol::Https => "https",
+			Protocol::Ws => "ws",
+			Protocol::Wss => "wss",
+			Protocol::Tcp => "tcp",
+			Protocol::Unix => "unix",
+		})
+	}
+}
+
+impl FromStr for Protocol {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s.to_ascii_lowercase().as_str() {
+			"http" => Ok(Protocol::Http),
+			"https" => Ok(Protocol::Https),
+			"ws" => Ok(Protocol::Ws),
+			"wss" => Ok(Protocol::Wss),
+			"tcp" => Ok(Protocol::Tcp),
+			"unix" => Ok(Protocol::Unix),
+			_ => Err(format!("Invalid protocol '{s}', expected http, https, ws, wss, tcp or unix")),
+		}
+	}
+}
+
+impl EndpointUrl {
+	pub fn protocol(&self) -> Protocol {
+		match self {
+			EndpointUrl::Http(_) => Protocol::Http,
+			EndpointUrl::Https(_) => Protocol::Https,
+			EndpointUrl::Ws(_) => Protocol::Ws,
+			EndpointUrl::Wss(_) => Protocol::Wss,
+			EndpointUrl::Tcp(_) => Protocol::Tcp,
+			EndpointUrl::Unix(_) => Protocol::Unix,
+		}
+	}
+
+	/// The parsed url
+	pub fn as_url(&self) -> &Url {
 		match self {
-			EndpointUrl::Http(s) | EndpointUrl::Https(s) | EndpointUrl::Ws(s) | EndpointUrl::Wss(s) => f.write_str(s),
+			EndpointUrl::Http(url)
+			| EndpointUrl::Https(url)
+			| EndpointUrl::Ws(url)
+			| EndpointUrl::Wss(url)
+			| EndpointUrl::Tcp(url)
+			| EndpointUrl::Unix(url) => url,
+		}
+	}
+
+	/// Scheme of the url, always lowercase
+	pub fn scheme(&self) -> &str {
+		self.as_url().scheme()
+	}
+
+	/// Host of the url, always lowercase. Empty for unix sockets.
+	pub fn host(&self) -> &str {
+		self.as_url().host_str().unwrap_or_default()
+	}
+
+	/// Port of the url, the default port of the scheme if none is set. None for unix sockets.
+	pub fn port(&self) -> Option<u16> {
+		self.as_url().port_or_known_default()
+	}
+
+	/// Path of the url, `/` if none is set
+	pub fn path(&self) -> &str {
+		self.as_url().path()
+	}
+
+	pub fn query(&self) -> Option<&str> {
+		self.as_url().query()
+	}
+
+	/// Path of the socket of a unix endpoint
+	pub fn socket_path(&self) -> Option<PathBuf> {
+		match self {
+			EndpointUrl::Unix(url) => url.to_file_path().ok(),
+			_ => None,
 		}
 	}
 }
 
+impl Display for EndpointUrl {
+	/// Display the canonical form, without the trailing `/` when there is no path.
+	/// Placeholders such as `{API_KEY}` are shown as is rather than percent encoded, other braces stay encoded.
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		let url = self.as_url();
+		let s = match url.as_str().strip_suffix('/') {
+			Some(s) if url.path() == "/" && url.query().is_none() && url.fragment().is_none() => s,
+			_ => url.as_str(),
+		};
+		f.write_str(&decode_placeholders(s))
+	}
+}
+
+/// Decode the percent encoded braces of the `{PLACEHOLDERS}` of `s`, see [placeholders](crate::placeholders)
+fn decode_placeholders(s: &str) -> String {
+	let mut decoded = String::with_capacity(s.len());
+	let mut rest = s;
+	while let Some(start) = rest.find("%7B") {
+		decoded.push_str(&rest[..start]);
+		rest = &rest[start + 3..];
+		match rest.find("%7D") {
+			Some(end)
+				if end > 0 && rest[..end].chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_') =>
+			{
+				decoded.push_str(&format!("{{{}}}", &rest[..end]));
+				rest = &rest[end + 3..];
+			}
+			_ => decoded.push_str("%7B"),
+		}
+	}
+	decoded.push_str(rest);
+	decoded
+}
+
+impl Serialize for EndpointUrl {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.collect_str(self)
+	}
+}
+
 impl TryFrom<&str> for EndpointUrl {
-	type Error = anyhow::Error;
+	type Error = Error;
 
 	fn try_from(s: &str) -> Result<Self, Self::Error> {
-		if s.starts_with("wss://") {
-			return Ok(EndpointUrl::Wss(s.to_string()));
-		}
+		let invalid = || Error::InvalidUrl(s.to_string());
+		let url = Url::parse(s.trim()).map_err(|_| invalid())?;
 
-		if s.starts_with("ws://") {
-			return Ok(EndpointUrl::Ws(s.to_string()));
+		if url.scheme() == "unix" {
+			// `unix:/path` and `unix:///path` are the same socket
+			if url.host_str().is_some_and(|h| !h.is_empty()) || !url.path().starts_with('/') || url.path().len() < 2 {
+				return Err(invalid());
+			}
+			let url = Url::parse(&format!("unix://{}", url.path())).map_err(|_| invalid())?;
+			return Ok(EndpointUrl::Unix(url));
 		}
 
-		if s.starts_with("https://") {
-			return Ok(EndpointUrl::Https(s.to_string()));
+		// The url parser is forgiving and would read `wss:///path` as the host `path`
+		let authority = s.trim().split_once("://").map(|(_, rest)| rest).unwrap_or_default();
+		if authority.is_empty() || authority.starts_with('/') {
+			return Err(invalid());
 		}
+		let Some(host) = url.host_str().filter(|h| !h.is_empty()).map(str::to_ascii_lowercase) else {
+			return Err(invalid());
+		};
 
-		if s.starts_with("http://") {
-			return Ok(EndpointUrl::Http(s.to_string()));
+		match url.scheme() {
+			"wss" => Ok(EndpointUrl::Wss(url)),
+			"ws" => Ok(EndpointUrl::Ws(url)),
+			"https" => Ok(EndpointUrl::Https(url)),
+			"http" => Ok(EndpointUrl::Http(url)),
+			"tcp" if url.port().is_some() => {
+				// Hosts of non special schemes are not normalized by the parser
+				let mut url = url;
+				url.set_host(Some(&host)).map_err(|_| invalid())?;
+				Ok(EndpointUrl::Tcp(url))
+			}
+			_ => Err(invalid()),
 		}
-
-		// Err(format!("Invalid endpoint: {}", s))
-		bail!("Invalid endpoint: {}", s)
 	}
 }
 
-// /// Serde is using From<T>
-// impl From<&str> for EndpointUrl {
-// 	fn from(s: &str) -> Self {
-// 		Self::try_from(s).unwrap()
-// 	}
-// }
+impl TryFrom<String> for EndpointUrl {
+	type Error = Error;
+
+	fn try_from(s: String) -> Result<Self, Self::Error> {
+		Self::try_from(s.as_str())
+	}
+}
 
 #[cfg(test)]
 mod test_from {
@@ -59,6 +216,84 @@ mod test_from {
 
 	#[test]
 	fn test_from_str() {
-		assert_eq!(EndpointUrl::Wss("wss://foobar".to_string()), EndpointUrl::try_from("wss://foobar").unwrap());
+		assert_eq!(
+			EndpointUrl::Wss(Url::parse("wss://foobar").unwrap()),
+			EndpointUrl::try_from("wss://foobar").unwrap()
+		);
+	}
+
+	#[test]
+	fn test_canonical() {
+		let url = EndpointUrl::try_from("WSS://RPC.Polkadot.io:443").unwrap();
+		assert_eq!(EndpointUrl::try_from("wss://rpc.polkadot.io").unwrap(), url);
+		assert_eq!(EndpointUrl::try_from("wss://rpc.polkadot.io/").unwrap(), url);
+		assert_eq!("wss://rpc.polkadot.io", url.to_string());
+		assert_eq!(("wss", "rpc.polkadot.io", Some(443), "/"), (url.scheme(), url.host(), url.port(), url.path()));
+
+		let url = EndpointUrl::try_from("http://localhost:9933/rpc?key=1").unwrap();
+		assert_eq!(
+			(Protocol::Http, Some(9933), "/rpc", Some("key=1")),
+			(url.protocol(), url.port(), url.path(), url.query())
+		);
+		assert_eq!("http://localhost:9933/rpc?key=1", url.to_string());
+		assert_ne!(
+			EndpointUrl::try_from("wss://rpc.polkadot.io:8443").unwrap(),
+			EndpointUrl::try_from("wss://rpc.polkadot.io").unwrap()
+		);
+	}
+
+	#[test]
+	fn test_invalid() {
+		for url in [
+			"wss://",
+			"wss:///path",
+			"ftp://host",
+			"rpc.polkadot.io",
+			"wss://bad host",
+			"wss://[::1",
+			"tcp://localhost",
+			"unix://",
+			"unix://host/node.sock",
+			"unix:node.sock",
+		] {
+			assert!(EndpointUrl::try_from(url).is_err(), "{url} should be invalid");
+		}
+	}
+
+	#[test]
+	fn test_local_transports() {
+		let url = EndpointUrl::try_from("unix:/tmp/node.sock").unwrap();
+		assert_eq!(EndpointUrl::try_from("unix:///tmp/node.sock").unwrap(), url);
+		assert_eq!("unix:///tmp/node.sock", url.to_string());
+		assert_eq!((Protocol::Unix, "", None), (url.protocol(), url.host(), url.port()));
+		assert_eq!(Some(PathBuf::from("/tmp/node.sock")), url.socket_path());
+
+		let url = EndpointUrl::try_from("tcp://LocalHost:9944").unwrap();
+		assert_eq!("tcp://localhost:9944", url.to_string());
+		assert_eq!((Protocol::Tcp, "localhost", Some(9944)), (url.protocol(), url.host(), url.port()));
+		assert_eq!(None, url.socket_path());
+		assert_eq!(Ok(Protocol::Wss), "WSS".parse());
+		assert!("ftp".parse::<Protocol>().is_err());
+	}
+
+	#[test]
+	fn test_serde() {
+		let url: EndpointUrl = serde_json::from_str(r#""wss://kusama-rpc.polkadot.io:443""#).unwrap();
+		assert_eq!(r#""wss://kusama-rpc.polkadot.io""#, serde_json::to_string(&url).unwrap());
+		assert!(serde_json::from_str::<EndpointUrl>(r#""foo://bar""#).is_err());
+	}
+
+	#[test]
+	fn test_placeholders() {
+		let url = EndpointUrl::try_from("wss://provider.io/ws/{API_KEY}").unwrap();
+		assert_eq!("wss://provider.io/ws/{API_KEY}", url.to_string());
+
+		// Other encoded braces are kept as is
+		let url = "https://provider.io/rpc/%7B%22id%22:1%7D?filter=%7Bjson%7D&key=%7BAPI_KEY%7D";
+		let encoded = EndpointUrl::try_from(url).unwrap();
+		assert_eq!("https://provider.io/rpc/%7B%22id%22:1%7D?filter=%7Bjson%7D&key={API_KEY}", encoded.to_string());
+		assert_eq!(vec!["API_KEY"], crate::placeholders(&encoded.to_string()));
+		let json = serde_json::to_string(&encoded).unwrap();
+		assert_eq!(encoded.to_string(), serde_json::from_str::<EndpointUrl>(&json).unwrap().to_string());
 	}
 }
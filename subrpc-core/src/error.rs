@@ -0,0 +1,104 @@
+use crate::LintIssue;
+use std::{io, path::PathBuf};
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Errors returned by subrpc-core
+#[derive(Debug, Error)]
+pub enum Error {
+	/// The request could not be sent or the response could not be read: connection, DNS, timeout...
+	#[error("Network error: {0}")]
+	Network(reqwest::Error),
+
+	/// The server answered with an error status
+	#[error("HTTP error {status} for {url}")]
+	HttpStatus { url: String, status: u16 },
+
+	/// The data is not valid JSON or does not match the expected format
+	#[error("Invalid JSON: {0}")]
+	Json(#[from] serde_json::Error),
+
+	/// The registry does not follow the registry schema, see [lint](crate::lint)
+	#[error("Invalid registry:\n{}", .0.iter().map(|i| format!("  {i}")).collect::<Vec<_>>().join("\n"))]
+	Schema(Vec<LintIssue>),
+
+	#[error("IO error: {0}")]
+	Io(#[from] io::Error),
+
+	#[error("Invalid url '{0}', expected http(s)://, ws(s)://, tcp://host:port or unix:///path")]
+	InvalidUrl(String),
+
+	/// The RPC call to an endpoint failed
+	#[error("RPC error: {0}")]
+	Rpc(#[from] jsonrpsee::core::ClientError),
+
+	/// The WebSocket connection to an endpoint failed, see [Proxy](crate::Proxy)
+	#[error("WebSocket error: {0}")]
+	WebSocket(Box<tokio_tungstenite::tungstenite::Error>),
+
+	#[error("Invalid config: {0}")]
+	Config(String),
+
+	/// The SQLite database storing the local data failed, see [SqliteStorage](crate::SqliteStorage)
+	#[cfg(feature = "sqlite")]
+	#[error("SQLite error: {0}")]
+	Sqlite(#[from] rusqlite::Error),
+
+	#[error("Invalid toml: {0}")]
+	Toml(#[from] toml::de::Error),
+
+	#[error("No home directory found")]
+	NoHomeDir,
+
+	/// The local data cannot be upgraded to the current version, see [migrate](crate::migrate)
+	#[error("Migration failed: {0}")]
+	Migration(String),
+
+	/// The local data cannot be read and no backup could be restored
+	#[error("The data in {} is corrupt and no backup could be restored: {reason}", .file.display())]
+	CorruptData { file: PathBuf, reason: String },
+
+	#[error("No registry found matching '{0}'")]
+	RegistryNotFound(String),
+
+	/// A secret required to authenticate to an endpoint is set neither in the config nor in the environment
+	#[error("Missing secret {name} for endpoint '{endpoint}', set it in the config or with {env}")]
+	MissingSecret { endpoint: String, name: String, env: String },
+
+	/// The auth of an endpoint could not be applied, such as a header with an invalid name
+	#[error("Invalid auth: {0}")]
+	Auth(String),
+
+	/// No chain name or alias is close to the name, the suggestions are the closest chains
+	#[error("No chain matching '{name}'{}", suggest(.suggestions))]
+	UnknownChain { name: String, suggestions: Vec<String> },
+
+	/// Several chains match the name
+	#[error("'{name}' matches several chains: {}", .candidates.join(", "))]
+	AmbiguousChain { name: String, candidates: Vec<String> },
+}
+
+impl From<reqwest::Error> for Error {
+	fn from(e: reqwest::Error) -> Self {
+		match e.status() {
+			Some(status) => {
+				Error::HttpStatus { url: e.url().map(|u| u.to_string()).unwrap_or_default(), status: status.as_u16() }
+			}
+			None => Error::Network(e),
+		}
+	}
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+	fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+		Error::WebSocket(Box::new(e))
+	}
+}
+
+fn suggest(suggestions: &[String]) -> String {
+	match suggestions {
+		[] => String::new(),
+		suggestions => format!(", did you mean: {}?", suggestions.join(", ")),
+	}
+}
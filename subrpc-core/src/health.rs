@@ -0,0 +1,148 @@
+//! Health probe of the endpoints.
+//!
+//! Answering `system_chain` only tells that a node is up. The probe also asks the node whether it
+//! is syncing and how many peers it has (`system_health`) and what its best block is
+//! (`chain_getHeader`). The best block of an endpoint is then compared to the other endpoints
+//! of the same chain to find the nodes lagging behind, see [best_blocks].
+
+use crate::{rpc::RpcClient, Error, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Lag, in blocks, for which the health factor of the score is 0.5
+pub const LAG_REFERENCE: f32 = 5.0;
+
+/// Health factor of a node that is syncing or has no peers while it should
+pub const UNHEALTHY_FACTOR: f32 = 0.1;
+
+/// What a node reports about itself, see [probe]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NodeHealth {
+	/// Whether the node is syncing
+	pub is_syncing: bool,
+
+	/// Number of peers of the node
+	pub peers: u32,
+
+	/// Whether the node is expected to have peers, false for dev nodes
+	pub should_have_peers: bool,
+
+	/// Number of the best block of the node
+	pub best_block: u64,
+}
+
+/// Health of an endpoint, as found by the last successful probe
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct Health {
+	/// DateTime of the probe
+	pub time: DateTime<Local>,
+
+	/// Whether the node was syncing
+	pub is_syncing: bool,
+
+	/// Number of peers of the node
+	pub peers: u32,
+
+	/// Whether the node is expected to have peers
+	#[serde(default)]
+	pub should_have_peers: bool,
+
+	/// Number of the best block of the node
+	pub best_block: u64,
+
+	/// Number of blocks behind the best endpoint of the same chain probed at the same time
+	pub lag: u64,
+}
+
+impl Health {
+	pub fn new(node: &NodeHealth, lag: u64, time: DateTime<Local>) -> Self {
+		Self {
+			time,
+			is_syncing: node.is_syncing,
+			peers: node.peers,
+			should_have_peers: node.should_have_peers,
+			best_block: node.best_block,
+			lag,
+		}
+	}
+
+	/// Whether the node has no peers while it should
+	pub fn is_isolated(&self) -> bool {
+		self.should_have_peers && self.peers == 0
+	}
+
+	/// Factor in the range `]0, 1]` applied to the score of the endpoint.
+	///
+	/// Nodes that are syncing or isolated get [UNHEALTHY_FACTOR]. Otherwise the factor is
+	/// `LAG_REFERENCE / (LAG_REFERENCE + lag)`: a node [LAG_REFERENCE] blocks behind sees its score halved.
+	pub fn factor(&self) -> f32 {
+		if self.is_syncing || self.is_isolated() {
+			UNHEALTHY_FACTOR
+		} else {
+			LAG_REFERENCE / (LAG_REFERENCE + self.lag as f32)
+		}
+	}
+}
+
+/// Query the health of a node
+pub(crate) async fn probe(client: &mut RpcClient) -> Result<NodeHealth> {
+	let health: Value = client.request("system_health", vec![]).await?;
+	let header: Value = client.request("chain_getHeader", vec![]).await?;
+
+	let invalid = |what: &str, value: &Value| {
+		Error::Rpc(jsonrpsee::core::ClientError::Custom(format!("Invalid {what}: {value}")))
+	};
+	let number = header.get("number").and_then(Value::as_str).ok_or_else(|| invalid("header", &header))?;
+	let best_block =
+		u64::from_str_radix(number.trim_start_matches("0x"), 16).map_err(|_| invalid("header", &header))?;
+
+	Ok(NodeHealth {
+		is_syncing: health.get("isSyncing").and_then(Value::as_bool).ok_or_else(|| invalid("health", &health))?,
+		peers: health.get("peers").and_then(Value::as_u64).unwrap_or_default() as u32,
+		should_have_peers: health.get("shouldHavePeers").and_then(Value::as_bool).unwrap_or(true),
+		best_block,
+	})
+}
+
+/// Best block of each chain among the given probes, by chain id, used to compute the lag of each endpoint.
+pub(crate) fn best_blocks<'a>(probes: impl Iterator<Item = (&'a str, Option<&'a NodeHealth>)>) -> HashMap<String, u64> {
+	let mut best = HashMap::new();
+	for (id, health) in probes {
+		if let Some(health) = health {
+			let block = best.entry(id.to_string()).or_insert(0);
+			*block = health.best_block.max(*block);
+		}
+	}
+	best
+}
+
+#[cfg(test)]
+mod test_health {
+	use super::*;
+
+	fn node(best_block: u64) -> NodeHealth {
+		NodeHealth { is_syncing: false, peers: 8, should_have_peers: true, best_block }
+	}
+
+	#[test]
+	fn test_best_blocks() {
+		let (a, b, c) = (node(100), node(90), node(5));
+		let best = best_blocks(
+			[("polkadot", Some(&a)), ("polkadot", Some(&b)), ("kusama", Some(&c)), ("kusama", None)].into_iter(),
+		);
+		assert_eq!(Some(&100), best.get("polkadot"));
+		assert_eq!(Some(&5), best.get("kusama"));
+	}
+
+	#[test]
+	fn test_factor() {
+		let now = Local::now();
+		assert_eq!(1f32, Health::new(&node(100), 0, now).factor());
+		assert_eq!(0.5f32, Health::new(&node(95), 5, now).factor());
+		assert_eq!(UNHEALTHY_FACTOR, Health::new(&NodeHealth { is_syncing: true, ..node(100) }, 0, now).factor());
+		assert_eq!(UNHEALTHY_FACTOR, Health::new(&NodeHealth { peers: 0, ..node(100) }, 0, now).factor());
+		assert_eq!(1f32, Health::new(&NodeHealth { peers: 0, should_have_peers: false, ..node(100) }, 0, now).factor());
+	}
+}
@@ -1,16 +1,49 @@
+mod auth;
+#[cfg(feature = "blocking")]
+mod blocking;
+mod capabilities;
+mod chain;
 mod config;
 mod endpoint;
 mod endpoint_stats;
 mod endpoint_url;
+mod error;
+mod health;
+mod lint;
 mod local_data;
+mod migration;
+mod network_options;
+mod paths;
+mod proxy;
+mod query;
 mod registry;
+mod resolver;
+mod rpc;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod storage;
 
+pub use auth::*;
+pub use capabilities::*;
+pub use chain::*;
 pub use config::*;
 pub use endpoint::*;
 pub use endpoint_stats::*;
-pub use endpoint_url::EndpointUrl;
+pub use endpoint_url::{EndpointUrl, Protocol};
+pub use error::{Error, Result};
+pub use health::{Health, NodeHealth, LAG_REFERENCE, UNHEALTHY_FACTOR};
+pub use lint::*;
 pub use local_data::*;
+pub use migration::{migrate, MigrationReport, DATA_VERSION, MIGRATIONS};
+pub use network_options::*;
+pub use paths::*;
+pub use proxy::{BoundProxy, Proxy, DEFAULT_PROXY_PORT, PROXY_ERROR_CODE, PROXY_STATS_INTERVAL};
+pub use query::*;
 pub use registry::*;
+pub use resolver::{ChainMatch, ChainResolver, EXACT_SCORE, FUZZY_SCORE, MAX_SUGGESTIONS};
+#[cfg(feature = "sqlite")]
+pub use sqlite::{SqliteStorage, HISTORY_RETENTION_DAYS};
+pub use storage::*;
 
 pub type RegistryUrl = String; // FIXME
 pub type ChainName = String;
@@ -0,0 +1,721 @@
+//! Validation of registry files.
+//!
+//! The registry format is described by the JSON Schema exported as [REGISTRY_JSON_SCHEMA].
+//! [lint] reports the problems found in a registry file with their position, while
+//! [Registry::parse](crate::Registry::parse) uses the same checks to either reject ([ParseMode::Strict])
+//! or clean up ([ParseMode::Lenient]) a registry.
+
+use crate::{placeholders, Auth, Chain, EndpointUrl, Result, REGISTRY_SCHEMA_VERSION};
+use chrono::DateTime;
+use log::*;
+use serde::Serialize;
+use serde_json::Value;
+use std::{collections::HashSet, fmt::Display, fs};
+
+/// JSON Schema describing the registry files
+pub const REGISTRY_JSON_SCHEMA: &str = include_str!("../schema/registry.schema.json");
+
+/// Fields expected at the top level of a registry
+const REGISTRY_FIELDS: &[&str] = &["schema_version", "name", "url", "labels", "last_update", "rpc_endpoints", "chains"];
+
+/// Fields of a registry that only subrpc sets locally, a registry file must not ship them
+const LOCAL_REGISTRY_FIELDS: &[&str] = &["enabled", "etag", "last_modified"];
+
+/// Fields expected in the description of a chain
+const CHAIN_FIELDS: &[&str] = &["id", "name", "relay", "token", "genesis_hash", "system_chain"];
+/// Number fields of a chain, with their maximum
+const CHAIN_NUMBER_FIELDS: &[(&str, u64)] = &[("para_id", u32::MAX as u64), ("ss58_prefix", u16::MAX as u64)];
+
+fn chain_number_max(key: &str) -> Option<u64> {
+	CHAIN_NUMBER_FIELDS.iter().find(|(name, _)| *name == key).map(|(_, max)| *max)
+}
+
+/// Whether `hash` looks like a block hash: `0x` followed by 64 hex digits
+fn is_block_hash(hash: &str) -> bool {
+	hash.strip_prefix("0x").is_some_and(|h| h.len() == 64 && h.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Fields expected in an endpoint
+const ENDPOINT_FIELDS: &[&str] = &["name", "labels", "aliases", "url", "auth"];
+
+/// Fields of an endpoint that only subrpc sets locally, such as its stats: a registry shipping them
+/// could rank its endpoints first or mark them as removed
+const LOCAL_ENDPOINT_FIELDS: &[&str] = &["stats", "label_overrides", "notes", "removed"];
+
+/// How strictly a registry is parsed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+	/// Any issue reported by [lint] is an error
+	Strict,
+
+	/// Unknown fields are ignored, malformed dates and invalid endpoints are dropped
+	#[default]
+	Lenient,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+	Warning,
+	Error,
+}
+
+impl Display for Severity {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			Severity::Warning => "warning",
+			Severity::Error => "error",
+		})
+	}
+}
+
+/// A problem found in a registry file. Lines and columns start at 1.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LintIssue {
+	pub severity: Severity,
+	pub line: usize,
+	pub column: usize,
+	pub message: String,
+}
+
+impl Display for LintIssue {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}:{}: {}: {}", self.line, self.column, self.severity, self.message)
+	}
+}
+
+/// Position of a JSON item in the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Pos {
+	line: usize,
+	column: usize,
+}
+
+/// A JSON value along with its position in the source
+#[derive(Debug)]
+enum Node {
+	Null(Pos),
+	Bool(Pos),
+	Number(Pos, String),
+	String(Pos, String),
+	Array(Pos, Vec<Node>),
+	Object(Pos, Vec<(Pos, String, Node)>),
+}
+
+impl Node {
+	fn pos(&self) -> Pos {
+		match self {
+			Node::Null(p) | Node::Bool(p) | Node::Number(p, _) | Node::String(p, _) => *p,
+			Node::Array(p, _) | Node::Object(p, _) => *p,
+		}
+	}
+
+	fn kind(&self) -> &'static str {
+		match self {
+			Node::Null(_) => "null",
+			Node::Bool(_) => "a boolean",
+			Node::Number(..) => "a number",
+			Node::String(..) => "a string",
+			Node::Array(..) => "an array",
+			Node::Object(..) => "an object",
+		}
+	}
+
+	fn get(&self, key: &str) -> Option<&Node> {
+		match self {
+			Node::Object(_, fields) => fields.iter().find(|(_, k, _)| k == key).map(|(_, _, v)| v),
+			_ => None,
+		}
+	}
+}
+
+/// Minimal JSON parser keeping track of the positions.
+/// It only runs on sources that serde_json already accepted.
+struct Parser<'a> {
+	chars: std::iter::Peekable<std::str::Chars<'a>>,
+	line: usize,
+	column: usize,
+}
+
+impl<'a> Parser<'a> {
+	fn new(s: &'a str) -> Self {
+		Self { chars: s.chars().peekable(), line: 1, column: 1 }
+	}
+
+	fn pos(&self) -> Pos {
+		Pos { line: self.line, column: self.column }
+	}
+
+	fn next(&mut self) -> Option<char> {
+		let c = self.chars.next()?;
+		if c == '\n' {
+			self.line += 1;
+			self.column = 1;
+		} else {
+			self.column += 1;
+		}
+		Some(c)
+	}
+
+	fn skip_ws(&mut self) {
+		while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+			self.next();
+		}
+	}
+
+	fn parse(&mut self) -> Option<Node> {
+		self.skip_ws();
+		let pos = self.pos();
+		match *self.chars.peek()? {
+			'{' => {
+				self.next();
+				let mut fields = vec![];
+				loop {
+					self.skip_ws();
+					match self.chars.peek()? {
+						'}' => {
+							self.next();
+							break;
+						}
+						',' => {
+							self.next();
+						}
+						_ => {
+							let key_pos = self.pos();
+							let Node::String(_, key) = self.parse()? else { return None };
+							self.skip_ws();
+							self.next(); // ':'
+							fields.push((key_pos, key, self.parse()?));
+						}
+					}
+				}
+				Some(Node::Object(pos, fields))
+			}
+			'[' => {
+				self.next();
+				let mut items = vec![];
+				loop {
+					self.skip_ws();
+					match self.chars.peek()? {
+						']' => {
+							self.next();
+							break;
+						}
+						',' => {
+							self.next();
+						}
+						_ => items.push(self.parse()?),
+					}
+				}
+				Some(Node::Array(pos, items))
+			}
+			'"' => {
+				self.next();
+				let mut raw = String::from('"');
+				loop {
+					let c = self.next()?;
+					raw.push(c);
+					match c {
+						'\\' => raw.push(self.next()?),
+						'"' => break,
+						_ => {}
+					}
+				}
+				Some(Node::String(pos, serde_json::from_str(&raw).ok()?))
+			}
+			c => {
+				let mut raw = String::new();
+				while let Some(c) = self.chars.peek().filter(|c| !matches!(c, ',' | '}' | ']') && !c.is_whitespace()) {
+					raw.push(*c);
+					self.next();
+				}
+				Some(match c {
+					'n' => Node::Null(pos),
+					't' | 'f' => Node::Bool(pos),
+					_ => Node::Number(pos, raw),
+				})
+			}
+		}
+	}
+}
+
+struct Linter {
+	issues: Vec<LintIssue>,
+}
+
+impl Linter {
+	fn report(&mut self, severity: Severity, pos: Pos, message: String) {
+		self.issues.push(LintIssue { severity, line: pos.line, column: pos.column, message });
+	}
+
+	fn error(&mut self, pos: Pos, message: String) {
+		self.report(Severity::Error, pos, message)
+	}
+
+	fn warning(&mut self, pos: Pos, message: String) {
+		self.report(Severity::Warning, pos, message)
+	}
+
+	fn check_string_array(&mut self, node: &Node, what: &str) {
+		match node {
+			Node::Array(_, items) => items.iter().filter(|item| !matches!(item, Node::String(..))).for_each(|item| {
+				self.error(item.pos(), format!("{what} must only contain strings, found {}", item.kind()))
+			}),
+			_ => self.error(node.pos(), format!("{what} must be an array of strings, found {}", node.kind())),
+		}
+	}
+
+	fn check_registry(&mut self, root: &Node) {
+		let Node::Object(pos, fields) = root else {
+			self.error(root.pos(), format!("A registry must be an object, found {}", root.kind()));
+			return;
+		};
+
+		for (key_pos, key, value) in fields {
+			match key.as_str() {
+				"schema_version" => match value {
+					Node::Number(p, raw) => match raw.parse::<u32>() {
+						Ok(version) if version > REGISTRY_SCHEMA_VERSION => self.error(
+							*p,
+							format!("Unsupported schema_version {version}, the latest supported is {REGISTRY_SCHEMA_VERSION}"),
+						),
+						Ok(_) => {}
+						Err(_) => self.error(*p, format!("Invalid schema_version '{raw}'")),
+					},
+					_ => self.error(value.pos(), format!("schema_version must be a number, found {}", value.kind())),
+				},
+				"name" => match value {
+					Node::String(_, name) if !name.trim().is_empty() => {}
+					_ => self.error(value.pos(), "name must be a non empty string".to_string()),
+				},
+				"url" => match value {
+					Node::Null(_) => {}
+					Node::String(p, url) if !url.starts_with("http://") && !url.starts_with("https://") => {
+						self.error(*p, format!("Invalid registry url '{url}', expected http or https"))
+					}
+					Node::String(..) => {}
+					_ => self.error(value.pos(), format!("url must be a string or null, found {}", value.kind())),
+				},
+				"labels" => self.check_string_array(value, "labels"),
+				"last_update" | "last_updated" => {
+					if key == "last_updated" {
+						self.warning(*key_pos, "'last_updated' is deprecated, use 'last_update'".to_string());
+					}
+					match value {
+						Node::Null(_) => {}
+						Node::String(p, date) if DateTime::parse_from_rfc3339(date).is_err() => {
+							self.error(*p, format!("Malformed date '{date}', expected RFC 3339 such as 2022-12-06T16:38:00Z"))
+						}
+						Node::String(..) => {}
+						_ => self.error(value.pos(), format!("{key} must be a string or null, found {}", value.kind())),
+					}
+				}
+				"rpc_endpoints" => self.check_rpc_endpoints(value),
+				"chains" => self.check_chains(value),
+				_ if LOCAL_REGISTRY_FIELDS.contains(&key.as_str()) => {
+					self.error(*key_pos, format!("'{key}' is set locally by subrpc, a registry must not set it"))
+				}
+				_ if !REGISTRY_FIELDS.contains(&key.as_str()) => self.warning(*key_pos, format!("Unknown field '{key}'")),
+				_ => {}
+			}
+		}
+
+		if root.get("schema_version").is_none() {
+			self.warning(*pos, format!("Missing schema_version, assuming {REGISTRY_SCHEMA_VERSION}"));
+		}
+		if root.get("name").is_none() {
+			self.error(*pos, "Missing field 'name'".to_string());
+		}
+		if root.get("rpc_endpoints").is_none() {
+			self.error(*pos, "Missing field 'rpc_endpoints'".to_string());
+		}
+	}
+
+	fn check_chains(&mut self, node: &Node) {
+		let Node::Object(_, chains) = node else {
+			self.error(node.pos(), format!("chains must be an object, found {}", node.kind()));
+			return;
+		};
+
+		for (_, chain, info) in chains {
+			let Node::Object(_, fields) = info else {
+				self.error(info.pos(), format!("The chain '{chain}' must be an object, found {}", info.kind()));
+				continue;
+			};
+			for (key_pos, key, value) in fields {
+				match (key.as_str(), value) {
+					("genesis_hash", Node::String(p, hash)) if !is_block_hash(hash) => self.error(
+						*p,
+						format!("Invalid genesis_hash '{hash}' for '{chain}', expected 0x followed by 64 hex digits"),
+					),
+					("aliases", _) => self.check_string_array(value, "aliases"),
+					(_, Node::Number(p, raw)) if chain_number_max(key).is_some() => {
+						let max = chain_number_max(key).unwrap_or_default();
+						if raw.parse::<u64>().map_or(true, |n| n > max) {
+							self.error(*p, format!("{key} must be an integer between 0 and {max}, found {raw}"))
+						}
+					}
+					_ if chain_number_max(key).is_some() => {
+						self.error(value.pos(), format!("{key} must be a number, found {}", value.kind()))
+					}
+					(_, Node::String(..)) if CHAIN_FIELDS.contains(&key.as_str()) => {}
+					_ if CHAIN_FIELDS.contains(&key.as_str()) => {
+						self.error(value.pos(), format!("{key} must be a string, found {}", value.kind()))
+					}
+					_ => self.warning(*key_pos, format!("Unknown field '{key}' in chain '{chain}'")),
+				}
+			}
+		}
+	}
+
+	fn check_rpc_endpoints(&mut self, node: &Node) {
+		let Node::Object(_, chains) = node else {
+			self.error(node.pos(), format!("rpc_endpoints must be an object, found {}", node.kind()));
+			return;
+		};
+
+		for (_, chain, endpoints) in chains {
+			let Node::Array(_, endpoints) = endpoints else {
+				self.error(
+					endpoints.pos(),
+					format!("The endpoints of '{chain}' must be an array, found {}", endpoints.kind()),
+				);
+				continue;
+			};
+
+			let mut seen = HashSet::new();
+			for endpoint in endpoints {
+				self.check_endpoint(chain, endpoint, &mut seen);
+			}
+		}
+	}
+
+	fn check_endpoint(&mut self, chain: &str, node: &Node, seen: &mut HashSet<EndpointUrl>) {
+		let Node::Object(pos, fields) = node else {
+			self.error(node.pos(), format!("An endpoint must be an object, found {}", node.kind()));
+			return;
+		};
+
+		for (key_pos, key, value) in fields {
+			match key.as_str() {
+				"name" if !matches!(value, Node::String(..)) => {
+					self.error(value.pos(), format!("name must be a string, found {}", value.kind()))
+				}
+				"labels" | "aliases" => self.check_string_array(value, key),
+				"url" => match value {
+					Node::String(p, url) => match EndpointUrl::try_from(url.as_str()) {
+						Ok(endpoint_url) => {
+							if !seen.insert(endpoint_url) {
+								self.error(*p, format!("Duplicate endpoint '{url}' for '{chain}'"))
+							}
+						}
+						Err(e) => self.error(*p, e.to_string()),
+					},
+					_ => self.error(value.pos(), format!("url must be a string, found {}", value.kind())),
+				},
+				"auth" => self.check_auth(value, node.get("url")),
+				_ if LOCAL_ENDPOINT_FIELDS.contains(&key.as_str()) => {
+					self.error(*key_pos, format!("'{key}' is set locally by subrpc, a registry must not set it"))
+				}
+				_ if !ENDPOINT_FIELDS.contains(&key.as_str()) => {
+					self.warning(*key_pos, format!("Unknown field '{key}' in endpoint"))
+				}
+				_ => {}
+			}
+		}
+
+		for required in ["name", "url"] {
+			if node.get(required).is_none() {
+				self.error(*pos, format!("Missing field '{required}' in endpoint of '{chain}'"));
+			}
+		}
+	}
+
+	/// Check the shape of an auth and that its secrets are placeholders rather than actual secrets
+	fn check_auth(&mut self, node: &Node, url: Option<&Node>) {
+		let Node::Object(pos, fields) = node else {
+			self.error(node.pos(), format!("auth must be an object, found {}", node.kind()));
+			return;
+		};
+		let (expected, secret): (&[&str], _) = match node.get("type") {
+			Some(Node::String(_, t)) if t == "header" => (&["type", "name", "value"], node.get("value")),
+			Some(Node::String(_, t)) if t == "basic" => (&["type", "username", "password"], node.get("password")),
+			Some(Node::String(_, t)) if t == "url" => (&["type"], url),
+			Some(Node::String(p, t)) => {
+				self.error(*p, format!("Unknown auth type '{t}', expected header, url or basic"));
+				return;
+			}
+			Some(other) => {
+				self.error(other.pos(), format!("auth type must be a string, found {}", other.kind()));
+				return;
+			}
+			None => {
+				self.error(*pos, "Missing field 'type' in auth".to_string());
+				return;
+			}
+		};
+
+		for (key_pos, key, value) in fields {
+			if !expected.contains(&key.as_str()) {
+				self.warning(*key_pos, format!("Unknown field '{key}' in auth"));
+			} else if !matches!(value, Node::String(..)) {
+				self.error(value.pos(), format!("{key} must be a string, found {}", value.kind()));
+			}
+		}
+		for required in expected {
+			if node.get(required).is_none() {
+				self.error(*pos, format!("Missing field '{required}' in auth"));
+			}
+		}
+		if let Some(Node::String(p, secret)) = secret {
+			if placeholders(secret).is_empty() {
+				self.error(
+					*p,
+					"The secret must be a placeholder such as {API_KEY}, secrets do not belong in a registry"
+						.to_string(),
+				);
+			}
+		}
+	}
+}
+
+/// Check a registry file and report all the issues found, sorted by position.
+///
+/// An empty result means the registry is valid.
+pub fn lint(json: &str) -> Vec<LintIssue> {
+	if let Err(e) = serde_json::from_str::<serde_json::Value>(json) {
+		return vec![LintIssue {
+			severity: Severity::Error,
+			line: e.line(),
+			column: e.column(),
+			message: format!("Invalid JSON: {e}"),
+		}];
+	}
+
+	let Some(root) = Parser::new(json).parse() else {
+		return vec![LintIssue { severity: Severity::Error, line: 1, column: 1, message: "Invalid JSON".to_string() }];
+	};
+
+	let mut linter = Linter { issues: vec![] };
+	linter.check_registry(&root);
+	linter.issues.sort_by_key(|i| (i.line, i.column));
+	linter.issues
+}
+
+/// Clean up a registry for [ParseMode::Lenient]: malformed dates and invalid endpoints are dropped.
+pub(crate) fn sanitize(value: &mut Value) {
+	let Some(registry) = value.as_object_mut() else { return };
+
+	if registry.contains_key("last_update") && registry.remove("last_updated").is_some() {
+		warn!("Both last_update and last_updated are set, ignoring last_updated");
+	}
+	for key in ["last_update", "last_updated"] {
+		if let Some(date) = registry.get(key).and_then(|v| v.as_str()) {
+			if DateTime::parse_from_rfc3339(date).is_err() {
+				warn!("Ignoring malformed date '{date}'");
+				registry.insert(key.to_string(), Value::Null);
+			}
+		}
+	}
+
+	for key in LOCAL_REGISTRY_FIELDS {
+		if registry.remove(*key).is_some() {
+			warn!("Ignoring '{key}', it is set locally by subrpc");
+		}
+	}
+
+	if let Some(version) = registry.get("schema_version").and_then(|v| v.as_u64()) {
+		if version > REGISTRY_SCHEMA_VERSION as u64 {
+			warn!("Registry uses schema_version {version}, the latest supported is {REGISTRY_SCHEMA_VERSION}");
+		}
+	}
+
+	if let Some(chains) = registry.get_mut("chains").and_then(|v| v.as_object_mut()) {
+		chains.retain(|chain, info| {
+			let valid = serde_json::from_value::<Chain>(info.clone())
+				.is_ok_and(|info| info.genesis_hash.as_deref().is_none_or(is_block_hash));
+			if !valid {
+				warn!("Ignoring invalid chain '{chain}': {info}");
+			}
+			valid
+		});
+	}
+
+	if let Some(chains) = registry.get_mut("rpc_endpoints").and_then(|v| v.as_object_mut()) {
+		for (chain, endpoints) in chains.iter_mut() {
+			if let Some(endpoints) = endpoints.as_array_mut() {
+				endpoints.retain_mut(|e| {
+					if let Some(e) = e.as_object_mut() {
+						for key in LOCAL_ENDPOINT_FIELDS {
+							if e.remove(*key).is_some() {
+								warn!("Ignoring '{key}' in an endpoint of '{chain}', it is set locally by subrpc");
+							}
+						}
+					}
+					if let Some(auth) = e.get("auth") {
+						if serde_json::from_value::<Auth>(auth.clone()).is_err() {
+							warn!("Ignoring invalid endpoint of '{chain}', its auth is invalid: {e}");
+							return false;
+						}
+					}
+					let name = e.get("name").and_then(|n| n.as_str());
+					let url = e.get("url").and_then(|u| u.as_str());
+					match (name, url.map(EndpointUrl::try_from)) {
+						(Some(_), Some(Ok(_))) => true,
+						_ => {
+							warn!("Ignoring invalid endpoint of '{chain}': {e}");
+							false
+						}
+					}
+				});
+			}
+		}
+	}
+}
+
+/// Read a registry from a url (http or https) or from a file.
+pub async fn read_source(source: &str) -> Result<String> {
+	if source.starts_with("http://") || source.starts_with("https://") {
+		Ok(reqwest::get(source).await?.error_for_status()?.text().await?)
+	} else {
+		Ok(fs::read_to_string(source)?)
+	}
+}
+
+#[cfg(test)]
+mod test_lint {
+	use super::*;
+
+	fn messages(json: &str) -> Vec<(usize, usize, Severity, String)> {
+		lint(json).into_iter().map(|i| (i.line, i.column, i.severity, i.message)).collect()
+	}
+
+	#[test]
+	fn test_valid() {
+		assert!(lint(include_str!("../../registry/subrpc.json")).is_empty());
+		assert!(lint(include_str!("../../registry/parity.json")).is_empty());
+	}
+
+	#[test]
+	fn test_invalid_json() {
+		let issues = lint("{\n  \"name\": \"foo\",\n}");
+		assert_eq!(1, issues.len());
+		assert_eq!((3, 1), (issues[0].line, issues[0].column));
+	}
+
+	#[test]
+	fn test_issues() {
+		let json = r#"{
+  "schema_version": 1,
+  "name": "Test",
+  "color": "blue",
+  "last_update": "2022-12-06Z16:38:00T",
+  "rpc_endpoints": {
+    "Polkadot": [
+      { "name": "A", "url": "wss://rpc.polkadot.io" },
+      { "name": "B", "url": "wss://rpc.polkadot.io" },
+      { "name": "C", "url": "foo://bar", "extra": 1 }
+    ]
+  }
+}"#;
+		let issues = messages(json);
+		assert_eq!(5, issues.len(), "{issues:#?}");
+		assert_eq!((4, 3, Severity::Warning), (issues[0].0, issues[0].1, issues[0].2));
+		assert!(issues[0].3.contains("color"));
+		assert_eq!((5, 18, Severity::Error), (issues[1].0, issues[1].1, issues[1].2));
+		assert!(issues[1].3.contains("Malformed date"));
+		assert_eq!((9, 29, Severity::Error), (issues[2].0, issues[2].1, issues[2].2));
+		assert!(issues[2].3.contains("Duplicate"));
+		assert_eq!((10, 29, Severity::Error), (issues[3].0, issues[3].1, issues[3].2));
+		assert_eq!((10, 42, Severity::Warning), (issues[4].0, issues[4].1, issues[4].2));
+	}
+
+	#[test]
+	fn test_auth() {
+		let json = r#"{
+  "schema_version": 1,
+  "name": "Test",
+  "rpc_endpoints": {
+    "Polkadot": [
+      { "name": "A", "url": "wss://a.io/{API_KEY}", "auth": { "type": "url" } },
+      { "name": "B", "url": "wss://b.io", "auth": { "type": "header", "name": "X-Api-Key", "value": "{API_KEY}" } },
+      { "name": "C", "url": "wss://c.io", "auth": { "type": "basic", "username": "me", "password": "s3cr3t" } },
+      { "name": "D", "url": "wss://d.io", "auth": { "type": "url" } },
+      { "name": "E", "url": "wss://e.io", "auth": { "type": "cookie" } }
+    ]
+  }
+}"#;
+		let issues = messages(json);
+		assert_eq!(3, issues.len(), "{issues:#?}");
+		assert_eq!((8, 100), (issues[0].0, issues[0].1));
+		assert!(issues[0].3.contains("placeholder"));
+		assert_eq!((9, 29), (issues[1].0, issues[1].1));
+		assert!(issues[2].3.contains("Unknown auth type"));
+	}
+
+	#[test]
+	fn test_local_fields() {
+		let json = r#"{
+  "schema_version": 1,
+  "name": "Test",
+  "enabled": true,
+  "etag": "\"v1\"",
+  "rpc_endpoints": {
+    "Polkadot": [
+      { "name": "A", "url": "wss://a.io", "stats": { "success": 1000, "latency": 0.001 }, "removed": "2024-01-01T00:00:00Z" }
+    ]
+  }
+}"#;
+		let issues = messages(json);
+		assert_eq!(4, issues.len(), "{issues:#?}");
+		assert!(issues.iter().all(|i| i.2 == Severity::Error && i.3.contains("set locally")));
+		assert_eq!((8, 43), (issues[2].0, issues[2].1));
+
+		let reg = crate::Registry::parse(json, ParseMode::Lenient).unwrap();
+		assert!(reg.enabled && reg.etag.is_none());
+		let endpoint = &reg.rpc_endpoints["Polkadot"][0];
+		assert_eq!((0, false), (endpoint.stats.success, endpoint.is_removed()));
+	}
+
+	#[test]
+	fn test_chains() {
+		let json = r#"{
+  "schema_version": 1,
+  "name": "Test",
+  "chains": {
+    "Polkadot": { "genesis_hash": "0x91b171bb158e2d3848fa23a9f1c25182fb8e20313b2c1eb49219da7a70ce90c3" },
+    "Kusama": { "genesis_hash": "0xb0a8", "system_chain": "Kusama", "ss58": 2 },
+    "Westend": { "aliases": ["wnd", 1], "para_id": -1 },
+    "Rococo": { "ss58_prefix": 70000 }
+  },
+  "rpc_endpoints": {}
+}"#;
+		let issues = messages(json);
+		assert_eq!(5, issues.len(), "{issues:#?}");
+		assert_eq!((6, 33, Severity::Error), (issues[0].0, issues[0].1, issues[0].2));
+		assert_eq!((6, 69, Severity::Warning), (issues[1].0, issues[1].1, issues[1].2));
+		assert_eq!((7, 37, Severity::Error), (issues[2].0, issues[2].1, issues[2].2));
+		assert_eq!((7, 52, Severity::Error), (issues[3].0, issues[3].1, issues[3].2));
+		assert_eq!((8, 32, Severity::Error), (issues[4].0, issues[4].1, issues[4].2));
+		assert!(issues[4].3.contains("between 0 and 65535"), "{}", issues[4].3);
+
+		let reg = crate::Registry::parse(json, ParseMode::Lenient).unwrap();
+		assert_eq!(vec!["Polkadot"], reg.chains.keys().collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn test_schema_version() {
+		let issues = messages(r#"{ "schema_version": 42, "name": "Test", "rpc_endpoints": {} }"#);
+		assert_eq!(1, issues.len());
+		assert!(issues[0].3.contains("Unsupported schema_version 42"));
+
+		let issues = messages(r#"{ "name": "Test", "rpc_endpoints": {} }"#);
+		assert_eq!(Severity::Warning, issues[0].2);
+	}
+
+	#[test]
+	fn test_schema_is_json() {
+		let schema: Value = serde_json::from_str(REGISTRY_JSON_SCHEMA).unwrap();
+		assert_eq!(Some(REGISTRY_SCHEMA_VERSION as u64), schema["properties"]["schema_version"]["const"].as_u64());
+	}
+}
@@ -1,15 +1,46 @@
-use anyhow::Result;
 use chrono::{DateTime, Local};
+use futures::{stream, StreamExt};
 use log::*;
 use serde::{Deserialize, Serialize};
 use std::{
 	collections::{HashMap, HashSet},
-	fs::{self, File},
-	io::{Read, Write},
+	ffi::OsStr,
+	fs::{self, File, OpenOptions, TryLockError},
+	io::Write,
 	path::{Path, PathBuf},
+	process,
+	sync::{Arc, Mutex, PoisonError},
 };
 
-use crate::{endpoint::Endpoint, Registry};
+use crate::{
+	endpoint::Endpoint,
+	registry::{ping_endpoints, probe_endpoints, record_pings, record_probes},
+	resolver::normalize,
+	CatalogChain, Chain, ChainMatch, ChainResolver, Config, EndpointQuery, Error, JsonStorage, MigrationReport,
+	NetworkOptions, Paths, Registry, Result, Storage, UpdateOutcome, DATA_VERSION,
+};
+
+/// Number of backups of the local data kept by [LocalData::save]
+pub const BACKUPS: usize = 3;
+
+/// Lock files held by this process, see [LocalData::lock]
+static HELD_LOCKS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Advisory lock on the local data, see [LocalData::lock]. The lock is released when dropped.
+#[derive(Debug)]
+pub struct DataLock {
+	_file: File,
+	path: PathBuf,
+}
+
+impl Drop for DataLock {
+	fn drop(&mut self) {
+		let mut held = HELD_LOCKS.lock().unwrap_or_else(PoisonError::into_inner);
+		if let Some(i) = held.iter().position(|path| path == &self.path) {
+			held.swap_remove(i);
+		}
+	}
+}
 
 /// Local user data collected from the various regitries.
 ///
@@ -18,6 +49,10 @@ use crate::{endpoint::Endpoint, Registry};
 /// a fresh list of endpoints.
 #[derive(PartialEq, Debug, Deserialize, Serialize)]
 pub struct LocalData {
+	/// Version of the layout of the data, see [migrate](crate::migrate)
+	#[serde(default)]
+	pub version: u32,
+
 	/// File where the local data are stored
 	pub file: PathBuf,
 
@@ -26,110 +61,395 @@ pub struct LocalData {
 
 	/// DateTime of the last update of the data
 	pub last_update: Option<DateTime<Local>>,
+
+	/// Backup the data was restored from by [Self::load], because the file was corrupt
+	#[serde(skip)]
+	pub recovered_from: Option<PathBuf>,
+
+	/// Where the data is loaded from and saved to. If not set, the data is stored as JSON in [Self::file].
+	#[serde(skip)]
+	pub storage: Option<Arc<dyn Storage>>,
 }
 
 impl LocalData {
-	pub fn get_default_file() -> PathBuf {
-		let home = dirs::home_dir().expect("Failed fetching home dir");
-		let dir = Path::new(&home).join(".subrpc");
-		let _ = fs::create_dir_all(&dir);
-		dir.join("data.json")
+	/// Default file of the local data, see [Paths::data_file]. The directory is created by the first save.
+	pub fn get_default_file() -> Result<PathBuf> {
+		Ok(Paths::new()?.data_file())
 	}
 
-	/// Returns true if the local file exists
+	/// Returns true if some data was saved already
 	pub fn initialized(&self) -> bool {
-		self.file.exists()
+		self.storage().exists()
 	}
 
-	/// Initialize a DB based on a given file.
+	/// Initialize a DB based on a given file, stored as JSON.
 	/// After initializing a DB, you should ensure it contains
 	/// at least one registry and call the [Self::refresh] function.
 	pub fn init(file: &Path, force: bool) -> Result<Self> {
-		debug!("Initializing local data in {} with force: {:?}", file.display(), force);
+		Self::init_with(Arc::new(JsonStorage::new(file)), force)
+	}
 
-		let data = Self { file: file.to_path_buf(), ..Default::default() };
+	/// Same as [Self::init] but using the given [Storage], see [StorageBackend::open](crate::StorageBackend::open)
+	pub fn init_with(storage: Arc<dyn Storage>, force: bool) -> Result<Self> {
+		debug!("Initializing local data in {} with force: {:?}", storage.location(), force);
 
-		if file.exists() && !force {
-			info!("File already exists: {}", file.display());
+		let file = storage.file().map(Path::to_path_buf).unwrap_or_default();
+		let data = Self { file, storage: Some(storage.clone()), ..Default::default() };
+
+		if storage.exists() && !force {
+			info!("Data already exists: {}", storage.location());
 			data.load()
 		} else {
 			data.save()
 		}
 	}
 
-	/// Load [LocalData] and deserialize data from [file].
+	/// Where the data is loaded from and saved to: the [Self::storage] if set, otherwise a [JsonStorage] of [Self::file]
+	pub fn storage(&self) -> Arc<dyn Storage> {
+		self.storage.clone().unwrap_or_else(|| Arc::new(JsonStorage::new(&self.file)))
+	}
+
+	/// Path next to `file`, with `suffix` appended to its name
+	pub(crate) fn sibling(file: &Path, suffix: &str) -> PathBuf {
+		let mut name = file.file_name().unwrap_or(OsStr::new("data.json")).to_owned();
+		name.push(suffix);
+		file.with_file_name(name)
+	}
+
+	/// Path of the backup number `n`, `1` being the most recent, of the data stored in `file`
+	pub fn backup_file(file: &Path, n: usize) -> PathBuf {
+		Self::sibling(file, &format!(".bak.{n}"))
+	}
+
+	/// Take an exclusive advisory lock on the data stored in `file`, waiting for the other processes holding it.
+	/// The id of the process holding the lock is written in the lock file and logged while waiting,
+	/// see [Self::lock_with] to notify the user instead.
+	///
+	/// Hold the lock around load-modify-save cycles so that concurrent processes do not overwrite
+	/// each other's changes. Reading the data does not require the lock since saves are atomic.
+	pub fn lock(file: &Path) -> Result<DataLock> {
+		Self::lock_with(file, |holder| {
+			let holder = holder.map(|pid| format!("process {pid}")).unwrap_or(String::from("another process"));
+			warn!("Waiting for {holder} to release the lock on {}", file.display());
+		})
+	}
+
+	/// Same as [Self::lock], calling `waiting` with the id of the process holding the lock, if known,
+	/// before waiting for it
+	pub fn lock_with(file: &Path, waiting: impl FnOnce(Option<u32>)) -> Result<DataLock> {
+		let lock_file = Self::sibling(file, ".lock");
+		debug!("Locking {}", lock_file.display());
+		if let Some(dir) = lock_file.parent() {
+			fs::create_dir_all(dir)?;
+		}
+		let mut lock = OpenOptions::new().create(true).truncate(false).read(true).write(true).open(&lock_file)?;
+		match lock.try_lock() {
+			Ok(()) => {}
+			Err(TryLockError::WouldBlock) => {
+				waiting(fs::read_to_string(&lock_file).ok().and_then(|pid| pid.trim().parse().ok()));
+				lock.lock()?;
+			}
+			Err(TryLockError::Error(e)) => return Err(e.into()),
+		}
+		lock.set_len(0)?;
+		write!(lock, "{}", process::id())?;
+
+		HELD_LOCKS.lock().unwrap_or_else(PoisonError::into_inner).push(lock_file.clone());
+		Ok(DataLock { _file: lock, path: lock_file })
+	}
+
+	/// Same as [Self::lock], unless this process holds the lock already, in which case `None` is returned
+	pub(crate) fn lock_unless_held(file: &Path) -> Result<Option<DataLock>> {
+		let lock_file = Self::sibling(file, ".lock");
+		if HELD_LOCKS.lock().unwrap_or_else(PoisonError::into_inner).contains(&lock_file) {
+			return Ok(None);
+		}
+		Self::lock(file).map(Some)
+	}
+
+	/// Upgrade the data stored in `file` to [DATA_VERSION] and save it, unless `dry_run` is set.
+	/// The report lists the changes, it is empty if the data is up to date.
+	pub fn migrate_file(file: &Path, dry_run: bool) -> Result<MigrationReport> {
+		JsonStorage::new(file).migrate(dry_run)
+	}
+
+	/// Load the data from its [Storage], see [Self::storage].
+	///
+	/// The [JsonStorage] restores the most recent backup that can be read if the file is corrupt,
+	/// see [Self::recovered_from].
 	pub fn load(self) -> Result<Self> {
-		debug!("Loading data from {}", self.file.display());
-		let mut fs = File::open(self.file)?;
-		let mut s = String::new();
-		fs.read_to_string(&mut s)?;
-		serde_json::from_str(&s).map_err(anyhow::Error::msg)
+		let storage = self.storage();
+		debug!("Loading data from {}", storage.location());
+		let data = storage.load()?;
+		Ok(Self {
+			file: storage.file().map(Path::to_path_buf).unwrap_or(data.file.clone()),
+			storage: Some(storage),
+			..data
+		})
 	}
 
 	/// Loops through each registry, each network/chain, each endpoint
 	/// and update the endpoints lists.
-	pub fn refresh(mut self) -> Self {
+	pub async fn refresh(self) -> Self {
+		self.refresh_with(&NetworkOptions::default()).await
+	}
+
+	/// Same as [Self::refresh] but using the given [NetworkOptions].
+	/// The registries are updated concurrently and the outcome of each update is
+	/// available in [Registry::last_outcome].
+	pub async fn refresh_with(mut self, opts: &NetworkOptions) -> Self {
 		debug!("Refreshing registries");
 
-		self.registries.iter_mut().for_each(|(_registry_name, reg)| {
-			debug!(" - {} - enabled: {:?}", &reg.name, &reg.enabled);
-			// println!("reg = {:?}", &reg);
-			match reg.update() {
-				Ok(_) => {
-					info!("Update of '{}' OK", reg.name);
-				}
-				Err(e) => {
-					// eprintln!("{e:?}");
-					error!("Update registry '{}' failed: {e:?}", reg.name);
+		stream::iter(self.registries.values_mut())
+			.for_each_concurrent(opts.limit(), |reg| async move {
+				debug!(" - {} - enabled: {:?}", &reg.name, &reg.enabled);
+				match reg.update_with(opts).await {
+					Ok(outcome) => {
+						info!("Update of '{}' OK: {outcome}", reg.name);
+					}
+					Err(e) => {
+						error!("Update registry '{}' failed: {e:?}", reg.name);
+					}
 				}
-			}
-		});
+			})
+			.await;
 
 		self.last_update = Some(Local::now());
 		self
 	}
 
+	/// Ping the endpoints of all the enabled registries and refresh their stats.
+	///
+	/// All endpoints are pinged concurrently, with at most [NetworkOptions::concurrency]
+	/// requests in flight whatever the number of registries. The lag of an endpoint is computed against
+	/// the endpoints of the same [chain](Self::chains), whatever its name in their registries.
+	pub async fn refresh_stats(&mut self, opts: &NetworkOptions) {
+		let chains: Vec<Chain> = self.chains().into_iter().map(|c| c.chain).collect();
+		let mut registries: Vec<&mut Registry> = self.registries.values_mut().filter(|reg| reg.enabled).collect();
+		registries.sort_by(|a, b| a.name.cmp(&b.name));
+		let ids: Vec<_> = registries.iter().map(|reg| reg.chain_ids(&chains)).collect();
+
+		let results = ping_endpoints(registries.iter().flat_map(|reg| reg.chain_endpoints()), opts).await;
+		let endpoints = registries
+			.into_iter()
+			.zip(&ids)
+			.flat_map(|(reg, ids)| reg.endpoints_mut().map(move |(key, e)| (ids[key].clone(), e)));
+		record_pings(endpoints, results)
+	}
+
+	/// Probe the [Capabilities](crate::Capabilities) of the endpoints of all the enabled registries,
+	/// with at most [NetworkOptions::concurrency] requests in flight.
+	pub async fn refresh_capabilities(&mut self, opts: &NetworkOptions) {
+		let mut registries: Vec<&mut Registry> = self.registries.values_mut().filter(|reg| reg.enabled).collect();
+		registries.sort_by(|a, b| a.name.cmp(&b.name));
+
+		let results = probe_endpoints(registries.iter().flat_map(|reg| reg.endpoints()), opts).await;
+		record_probes(registries.into_iter().flat_map(|reg| reg.endpoints_mut()).map(|(_, e)| e), results)
+	}
+
 	/// Add a new registry. Registries are identitfied by their names, make sure the name is unique.
 	pub fn add_registry(mut self, registry: Registry) -> Self {
 		self.registries.insert(registry.name.clone(), registry);
 		self
 	}
 
-	/// Save the current state to file
-	pub fn save(self) -> Result<Self> {
-		debug!("Saving data to {}", self.file.display());
-		let json = serde_json::to_string_pretty(&self)?;
-		let mut fs = File::create(&self.file)?;
-		fs.write_all(json.as_bytes())?;
+	/// Find the key of a registry given its name or its url.
+	/// The name is first looked up as is, then ignoring the case.
+	fn find_registry(&self, name_or_url: &str) -> Option<String> {
+		if self.registries.contains_key(name_or_url) {
+			return Some(name_or_url.to_string());
+		}
+
+		self.registries
+			.iter()
+			.find(|(_, reg)| reg.name.eq_ignore_ascii_case(name_or_url))
+			.or_else(|| self.registries.iter().find(|(_, reg)| reg.url.as_deref() == Some(name_or_url)))
+			.map(|(key, _)| key.clone())
+	}
+
+	/// Remove a registry, identified by its name or its url, and all its endpoints.
+	pub fn remove_registry(mut self, name_or_url: &str) -> Result<Self> {
+		let key = self.find_registry(name_or_url).ok_or(Error::RegistryNotFound(name_or_url.to_string()))?;
+		debug!("Removing registry '{key}'");
+		self.registries.remove(&key);
+		Ok(self)
+	}
+
+	/// Enable or disable a registry, identified by its name or its url.
+	/// The endpoints of a disabled registry are ignored and the registry is no longer updated.
+	pub fn set_registry_enabled(mut self, name_or_url: &str, enabled: bool) -> Result<Self> {
+		let key = self.find_registry(name_or_url).ok_or(Error::RegistryNotFound(name_or_url.to_string()))?;
+		debug!("Setting registry '{key}' enabled: {enabled}");
+		if let Some(reg) = self.registries.get_mut(&key) {
+			reg.enabled = enabled;
+		}
+		Ok(self)
+	}
+
+	/// Save the current state to its [Storage], see [Self::storage].
+	///
+	/// The [JsonStorage] first writes the data to a temporary file next to the target, which is then renamed.
+	/// An interrupted save therefore never leaves a truncated file behind. The previous file
+	/// is kept as the most recent backup, see [Self::backup_file].
+	pub fn save(mut self) -> Result<Self> {
+		let storage = self.storage();
+		debug!("Saving data to {}", storage.location());
+		self.version = DATA_VERSION;
+		storage.save(&self)?;
 		Ok(self)
 	}
 
 	/// Get a list of endpoints matching an optional filter. If not
 	/// `chain` filter is passed, all endpoints are returned.
+	/// Endpoints that vanished from their registry are not returned.
+	/// See [Self::query] for the other filters.
 	pub fn get_endpoints(&self, chain: Option<&str>) -> HashSet<Endpoint> {
+		self.query(&Self::chain_query(chain))
+	}
+
+	/// Get the endpoints of the enabled registries matching the given [EndpointQuery].
+	/// Endpoints that vanished from their registry are not returned.
+	///
+	/// The chain of the query may be the name, the id or any of the names used by the registries
+	/// for the same chain, see [Self::chains]: the endpoints of all the registries are then returned.
+	pub fn query(&self, query: &EndpointQuery) -> HashSet<Endpoint> {
+		let names: Option<Vec<String>> = query.chain.as_ref().map(|name| {
+			let keys = self.chains().into_iter().find(|c| c.is_named(name)).map(|c| c.keys);
+			keys.unwrap_or_else(|| vec![name.clone()]).iter().map(|key| normalize(key)).collect()
+		});
+		let query = EndpointQuery { chain: None, ..query.clone() };
+
 		let mut endpoint_vec: HashSet<Endpoint> = HashSet::new();
 		self.registries.iter().for_each(|(_, reg)| {
 			if !reg.enabled {
 				// skipping
 			} else {
-				reg.rpc_endpoints
-					.iter()
-					.filter(|(c, _)| {
-						if let Some(filter) = chain {
-							c.as_str().to_ascii_lowercase() == filter.to_ascii_lowercase()
-						} else {
-							true
-						}
-					})
-					.for_each(|(_, e)| {
-						let ee = e.clone();
-						endpoint_vec.extend(ee);
-					});
+				reg.rpc_endpoints.iter().for_each(|(chain, e)| {
+					if names.as_ref().is_some_and(|names| !names.contains(&normalize(chain))) {
+						return;
+					}
+					let ee = e.iter().filter(|e| !e.is_removed() && query.matches(chain, reg, e)).cloned();
+					endpoint_vec.extend(ee);
+				});
 			}
 		});
 		endpoint_vec
 	}
 
+	/// Catalog of the chains of the enabled registries, sorted by name.
+	///
+	/// A chain listed by several registries, possibly under different names, appears once: chains are
+	/// merged when they are [the same](crate::Chain::is_same), the first registry by name providing the
+	/// display name. The aliases of the endpoints become aliases of their chain.
+	pub fn chains(&self) -> Vec<CatalogChain> {
+		let mut registries: Vec<&Registry> = self.registries.values().filter(|reg| reg.enabled).collect();
+		registries.sort_by(|a, b| a.name.cmp(&b.name));
+
+		let mut catalog: Vec<(CatalogChain, HashSet<String>)> = vec![];
+		for reg in registries {
+			let mut chains: Vec<_> = reg.rpc_endpoints.iter().collect();
+			chains.sort_by_key(|(chain, _)| *chain);
+			for (key, endpoints) in chains {
+				let mut chain = reg.chain(key);
+				let endpoints: Vec<&Endpoint> = endpoints.iter().filter(|e| !e.is_removed()).collect();
+				chain.merge(&Chain {
+					aliases: endpoints.iter().flat_map(|e| e.aliases.iter().cloned()).collect(),
+					..Default::default()
+				});
+
+				let index = match catalog.iter().position(|(c, _)| c.chain.is_same(&chain)) {
+					Some(index) => {
+						let (entry, _) = &mut catalog[index];
+						entry.chain.merge(&chain);
+						if !entry.keys.contains(key) {
+							entry.keys.push(key.clone());
+						}
+						index
+					}
+					None => {
+						catalog.push((CatalogChain { chain, keys: vec![key.clone()], endpoints: 0 }, HashSet::new()));
+						catalog.len() - 1
+					}
+				};
+				catalog[index].1.extend(endpoints.iter().map(|e| e.url.to_string()));
+			}
+		}
+
+		let mut chains: Vec<CatalogChain> =
+			catalog.into_iter().map(|(chain, urls)| CatalogChain { endpoints: urls.len(), ..chain }).collect();
+		chains.sort_by(|a, b| a.chain.name.cmp(&b.chain.name));
+		chains
+	}
+
+	/// [ChainResolver] knowing the [chains](Self::chains) of the enabled registries by their name. Their ids,
+	/// the names used by the registries and their aliases are aliases, as are the local aliases of the [Config].
+	pub fn chain_resolver(&self, config: &Config) -> ChainResolver {
+		let mut resolver = ChainResolver::new();
+		let chains = self.chains();
+		for c in &chains {
+			resolver.add_chain(&c.chain.name);
+			[&c.chain.id]
+				.into_iter()
+				.chain(&c.keys)
+				.chain(&c.chain.aliases)
+				.for_each(|alias| resolver.add_alias(&c.chain.name, alias));
+		}
+		config.aliases.iter().for_each(|(name, aliases)| {
+			let chain = chains.iter().find(|c| c.is_named(name)).map(|c| c.chain.name.as_str()).unwrap_or(name);
+			aliases.iter().for_each(|alias| resolver.add_alias(chain, alias));
+		});
+		resolver
+	}
+
+	/// Resolve a chain name typed by the user, see [ChainResolver::resolve]
+	pub fn resolve_chain(&self, name: &str, config: &Config) -> Result<ChainMatch> {
+		self.chain_resolver(config).resolve(name)
+	}
+
+	fn chain_query(chain: Option<&str>) -> EndpointQuery {
+		EndpointQuery { chain: chain.map(String::from), ..Default::default() }
+	}
+
+	/// Get the best endpoints, ranked by [EndpointStats::score](crate::EndpointStats::score), the best first.
+	///
+	/// Endpoints with the same score are ordered by name and then by url so the result is deterministic.
+	/// Endpoints listed by several registries under the same url are returned only once.
+	/// The `chain` filter behaves as in [Self::get_endpoints] and `n` limits the number of results.
+	/// See [Self::best_endpoints_for] for the other filters and the settings of the [Config].
+	pub fn best_endpoints(&self, chain: Option<&str>, n: Option<usize>) -> Vec<Endpoint> {
+		self.best_endpoints_for(&Self::chain_query(chain), n, &Config::default())
+	}
+
+	/// Get the best endpoints matching the given [EndpointQuery], ranked as in [Self::best_endpoints]
+	/// but using the label filters, preferred protocols and ranking strategy of the given [Config].
+	///
+	/// Endpoints are grouped by preferred protocol first and then ranked within each group.
+	pub fn best_endpoints_for(&self, query: &EndpointQuery, n: Option<usize>, config: &Config) -> Vec<Endpoint> {
+		let mut endpoints: Vec<(f32, Endpoint)> = self
+			.query(query)
+			.into_iter()
+			.filter(|e| config.accepts_labels(e.labels()))
+			.map(|e| (config.ranking.rank(&e.stats), e))
+			.collect();
+		endpoints.sort_by(|(rank_a, a), (rank_b, b)| {
+			config
+				.protocol_rank(a.url.protocol())
+				.cmp(&config.protocol_rank(b.url.protocol()))
+				.then_with(|| rank_b.total_cmp(rank_a))
+				.then_with(|| a.name.cmp(&b.name))
+				.then_with(|| a.url.cmp(&b.url))
+		});
+
+		let mut seen = HashSet::new();
+		let mut endpoints: Vec<Endpoint> =
+			endpoints.into_iter().map(|(_, e)| e).filter(|e| seen.insert(e.url.clone())).collect();
+
+		if let Some(n) = n {
+			endpoints.truncate(n);
+		}
+		endpoints
+	}
+
 	/// Print the list of registries.
 	///
 	/// See also [Self::print_summary].
@@ -151,23 +471,233 @@ impl LocalData {
 			);
 			println!("      rpc endpoints: {:?}", reg.rpc_endpoints.len());
 			println!("      last update: {:?}", reg.last_update);
+			if let Some(outcome) = &reg.last_outcome {
+				println!("      status: {outcome}");
+				if let UpdateOutcome::Updated(report) = outcome {
+					report.added.iter().for_each(|e| println!("        + {e}"));
+					report.removed.iter().for_each(|e| println!("        - {e}"));
+					report.changed.iter().for_each(|e| println!("        ~ {e}"));
+				}
+			}
 		})
 	}
+
+	/// Names of the registries whose endpoints changed during the last [Self::refresh], sorted.
+	pub fn changed_registries(&self) -> Vec<&str> {
+		let mut names: Vec<&str> = self
+			.registries
+			.values()
+			.filter(|reg| reg.last_outcome.as_ref().is_some_and(|o| o.changed()))
+			.map(|reg| reg.name.as_str())
+			.collect();
+		names.sort();
+		names
+	}
 }
 
 impl Default for LocalData {
 	fn default() -> Self {
-		Self { file: Self::get_default_file(), registries: HashMap::new(), last_update: None }
+		Self {
+			version: DATA_VERSION,
+			file: Self::get_default_file().unwrap_or_default(),
+			registries: HashMap::new(),
+			last_update: None,
+			recovered_from: None,
+			storage: None,
+		}
 	}
 }
 
 #[cfg(test)]
 mod test_local_data {
 	use super::*;
+	use crate::Protocol;
+	use std::env;
+
+	fn endpoint(name: &str, url: &str, success: u32, failures: u32, latency: f32) -> Endpoint {
+		let mut e = Endpoint::new(name, url, vec![], vec![]);
+		(0..success).for_each(|_| e.stats.add(true, Some(latency)));
+		(0..failures).for_each(|_| e.stats.add(false, None));
+		e
+	}
+
+	#[test]
+	fn test_best_endpoints() {
+		let mut reg = Registry::new("Test", "http://localhost");
+		reg.rpc_endpoints = HashMap::from([(
+			"Polkadot".to_string(),
+			vec![
+				endpoint("Slow", "wss://slow", 5, 0, 2.0),
+				endpoint("Fast", "wss://fast", 5, 0, 0.1),
+				endpoint("B", "wss://b", 5, 0, 1.0),
+				endpoint("A", "wss://a", 5, 0, 1.0),
+			],
+		)]);
+		let data =
+			LocalData { file: env::temp_dir().join("subrpc_test_best.json"), ..Default::default() }.add_registry(reg);
+
+		let names: Vec<String> = data.best_endpoints(Some("polkadot"), None).into_iter().map(|e| e.name).collect();
+		assert_eq!(vec!["Fast", "A", "B", "Slow"], names);
+
+		let best = data.best_endpoints(Some("polkadot"), Some(1));
+		assert_eq!(1, best.len());
+		assert_eq!("Fast", best[0].name);
+
+		assert!(data.best_endpoints(Some("kusama"), Some(1)).is_empty());
+	}
 
 	#[test]
-	fn test_builder() {
-		let data = LocalData::init(&LocalData::get_default_file(), true)
+	fn test_best_endpoints_for_config() {
+		let mut reg = Registry::new("Test", "http://localhost");
+		let mut testnet = endpoint("Testnet", "wss://testnet", 5, 0, 0.1);
+		testnet.labels = vec!["testnet".to_string()];
+		reg.rpc_endpoints = HashMap::from([(
+			"Polkadot".to_string(),
+			vec![
+				testnet,
+				endpoint("Https", "https://fast", 5, 0, 0.1),
+				endpoint("Wss", "wss://slow", 5, 0, 2.0),
+				endpoint("Flaky", "wss://flaky", 1, 4, 0.05),
+			],
+		)]);
+		let data = LocalData { file: env::temp_dir().join("subrpc_test_best_config.json"), ..Default::default() }
+			.add_registry(reg);
+
+		let config = Config::parse(
+			r#"
+			preferred_protocols = ["wss"]
+			exclude_labels = ["testnet"]
+			"#,
+		)
+		.unwrap();
+		let names: Vec<String> = data
+			.best_endpoints_for(&EndpointQuery::new().chain("polkadot"), None, &config)
+			.into_iter()
+			.map(|e| e.name)
+			.collect();
+		assert_eq!(vec!["Wss", "Flaky", "Https"], names);
+
+		let config = Config::parse(r#"ranking = "latency""#).unwrap();
+		let best = data.best_endpoints_for(&EndpointQuery::new().chain("polkadot"), Some(1), &config);
+		assert_eq!("Flaky", best[0].name);
+	}
+
+	#[test]
+	fn test_query() {
+		let mut parity = Registry::new("Parity", "http://localhost/parity.json");
+		parity.labels = vec!["mainnet".to_string()];
+		parity.rpc_endpoints =
+			HashMap::from([("Polkadot".to_string(), vec![endpoint("Parity", "wss://parity", 5, 0, 0.2)])]);
+		let mut other = Registry::new("Other", "http://localhost/other.json");
+		other.rpc_endpoints = HashMap::from([
+			("Polkadot".to_string(), vec![endpoint("Dwellir", "https://dwellir", 5, 0, 0.1)]),
+			("Westend".to_string(), vec![endpoint("Westend", "wss://westend", 5, 0, 0.1)]),
+		]);
+		let data = LocalData { file: env::temp_dir().join("subrpc_test_query.json"), ..Default::default() }
+			.add_registry(parity)
+			.add_registry(other);
+
+		let names = |query: EndpointQuery| -> Vec<String> {
+			data.best_endpoints_for(&query, None, &Config::default()).into_iter().map(|e| e.name).collect()
+		};
+		assert_eq!(vec!["Dwellir", "Parity"], names(EndpointQuery::new().chain("polkadot")));
+		assert_eq!(vec!["Parity"], names(EndpointQuery::new().label("mainnet")));
+		assert_eq!(vec!["Dwellir", "Westend"], names(EndpointQuery::new().exclude_label("mainnet")));
+		assert_eq!(vec!["Dwellir", "Westend"], names(EndpointQuery::new().provider("other")));
+		assert_eq!(vec!["Westend", "Parity"], names(EndpointQuery::new().protocol(Protocol::Wss)));
+		assert!(names(EndpointQuery::new().min_score(0.9)).is_empty());
+	}
+
+	#[test]
+	fn test_resolve_chain() {
+		let mut parity = Registry::new("Parity", "http://localhost/parity.json");
+		parity.rpc_endpoints = HashMap::from([
+			("Polkadot".to_string(), vec![endpoint("Parity", "wss://polkadot", 5, 0, 0.2)]),
+			(
+				"BridgeHub".to_string(),
+				vec![Endpoint::new("Parity", "wss://bridge-hub", vec![], vec!["bridge".to_string()])],
+			),
+			("Statemint".to_string(), vec![endpoint("Parity", "wss://statemint", 5, 0, 0.2)]),
+		]);
+		parity.chains =
+			HashMap::from([("Polkadot".to_string(), Chain { aliases: vec!["dot".to_string()], ..Default::default() })]);
+		let data = LocalData { file: env::temp_dir().join("subrpc_test_resolve.json"), ..Default::default() }
+			.add_registry(parity);
+		let config = Config::parse("aliases = { Statemint = [\"Asset Hub Polkadot\"] }").unwrap();
+
+		let chain = |name: &str| data.resolve_chain(name, &config).map(|m| m.chain);
+		assert_eq!("Polkadot", chain("DOT").unwrap());
+		assert_eq!("BridgeHub", chain("bridge").unwrap());
+		assert_eq!("Statemint", chain("asset-hub-polkadot").unwrap());
+		assert!(matches!(chain("kusama"), Err(Error::UnknownChain { .. })));
+		assert!(data.resolve_chain("asset-hub-polkadot", &Config::default()).is_err());
+	}
+
+	#[test]
+	fn test_chains() {
+		let genesis = "0x68d56f15f85d3136970ec16946040bc1752654e906147f7e43e9d539d7c3de2f";
+		let mut parity = Registry::new("Parity", "http://localhost/parity.json");
+		parity.rpc_endpoints = HashMap::from([
+			("Polkadot".to_string(), vec![endpoint("Parity", "wss://polkadot", 5, 0, 0.2)]),
+			("Statemint".to_string(), vec![endpoint("Parity", "wss://statemint", 5, 0, 0.2)]),
+		]);
+		parity.chains = HashMap::from([(
+			"Statemint".to_string(),
+			Chain { genesis_hash: Some(genesis.to_string()), para_id: Some(1000), ..Default::default() },
+		)]);
+		let mut other = Registry::new("Other", "http://localhost/other.json");
+		other.rpc_endpoints = HashMap::from([
+			(
+				"polkadot".to_string(),
+				vec![endpoint("Parity", "wss://polkadot", 5, 0, 0.2), endpoint("Dwellir", "wss://dwellir", 5, 0, 0.1)],
+			),
+			("Asset Hub Polkadot".to_string(), vec![endpoint("Dwellir", "wss://dwellir-ahp", 5, 0, 0.1)]),
+		]);
+		other.chains = HashMap::from([(
+			"Asset Hub Polkadot".to_string(),
+			Chain { genesis_hash: Some(genesis.to_uppercase()), token: Some("DOT".to_string()), ..Default::default() },
+		)]);
+		let data = LocalData { file: env::temp_dir().join("subrpc_test_chains.json"), ..Default::default() }
+			.add_registry(parity)
+			.add_registry(other);
+
+		let chains = data.chains();
+		assert_eq!(2, chains.len(), "{chains:#?}");
+		let (ahp, polkadot) = (&chains[0], &chains[1]);
+		assert_eq!(("Asset Hub Polkadot", 2), (ahp.chain.name.as_str(), ahp.endpoints));
+		assert_eq!(vec!["Asset Hub Polkadot", "Statemint"], ahp.keys);
+		assert_eq!((Some(1000), Some("DOT")), (ahp.chain.para_id, ahp.chain.token.as_deref()));
+		assert_eq!(("polkadot", 2), (polkadot.chain.name.as_str(), polkadot.endpoints));
+
+		assert_eq!(2, data.query(&EndpointQuery::new().chain("statemint")).len());
+		assert_eq!(2, data.get_endpoints(Some("Polkadot")).len());
+		assert_eq!("Asset Hub Polkadot", data.resolve_chain("Statemint", &Config::default()).unwrap().chain);
+	}
+
+	#[test]
+	fn test_remove_and_enable() {
+		let file = env::temp_dir().join("subrpc_test_remove.json");
+		let data = LocalData::init(&file, true)
+			.expect("Forced init should work")
+			.add_registry(Registry::new("Reg 1", "http://localhost/reg1.json"))
+			.add_registry(Registry::new("Reg 2", "http://localhost/reg2.json"));
+
+		let data = data.set_registry_enabled("reg 1", false).expect("Found by name");
+		assert!(!data.registries["Reg 1"].enabled);
+		let data = data.set_registry_enabled("http://localhost/reg1.json", true).expect("Found by url");
+		assert!(data.registries["Reg 1"].enabled);
+
+		let data = data.remove_registry("http://localhost/reg2.json").expect("Found by url").save().unwrap();
+		assert!(data.remove_registry("Reg 2").is_err());
+
+		let data = LocalData::init(&file, false).unwrap().load().unwrap();
+		assert_eq!(vec!["Reg 1"], data.registries.keys().collect::<Vec<_>>());
+		assert!(!file.with_extension("json.tmp").exists());
+	}
+
+	#[tokio::test]
+	async fn test_builder() {
+		let data = LocalData::init(&env::temp_dir().join("subrpc_test_builder.json"), true)
             .expect("Forced init should work")
             .save()
             .expect("Saving data should work")
@@ -175,17 +705,84 @@ mod test_local_data {
             .add_registry(Registry::new("SubRPC", "https://raw.githubusercontent.com/chevdor/subrpc/master/registry/subrpc.json"))
             .add_registry(Registry::new("SubRPC Gist", "https://gist.githubusercontent.com/chevdor/a8b381911c28f6de02dde62ed1a17dec/raw/6992b0a2924f80f691e4844c1731564f0e2a62ec/data.json"))
             .refresh()
+            .await
             .save().expect("Saving works");
 		println!("{data:#?}");
 	}
 
 	#[test]
-	fn test_merge() {
-		let data = LocalData::init(&LocalData::get_default_file(), true)
+	fn test_backups_and_recovery() {
+		let dir = env::temp_dir().join("subrpc_test_recovery");
+		let _ = fs::remove_dir_all(&dir);
+		fs::create_dir_all(&dir).unwrap();
+		let file = dir.join("data.json");
+
+		let mut data = LocalData::init(&file, true).unwrap();
+		for i in 0..5 {
+			data = data.add_registry(Registry::new(&format!("Reg {i}"), "http://localhost/reg.json")).save().unwrap();
+		}
+		assert!((1..=BACKUPS).all(|n| LocalData::backup_file(&file, n).exists()));
+		assert!(!LocalData::backup_file(&file, BACKUPS + 1).exists());
+
+		fs::write(&file, r#"{ "file": "data.json", "registr"#).unwrap();
+		let data = LocalData::init(&file, false).unwrap();
+		assert_eq!(Some(LocalData::backup_file(&file, 1)), data.recovered_from);
+		assert_eq!(4, data.registries.len());
+		assert!(dir.join("data.json.corrupt").exists());
+		assert_eq!(4, LocalData { file: file.clone(), ..Default::default() }.load().unwrap().registries.len());
+
+		// The recovery takes the lock, unless this process holds it already
+		fs::write(&file, "{").unwrap();
+		let lock = LocalData::lock(&file).unwrap();
+		assert_eq!(Some(LocalData::backup_file(&file, 1)), LocalData::init(&file, false).unwrap().recovered_from);
+		drop(lock);
+
+		(1..=BACKUPS).for_each(|n| fs::write(LocalData::backup_file(&file, n), "").unwrap());
+		fs::write(&file, "").unwrap();
+		assert!(matches!(LocalData::init(&file, false), Err(Error::CorruptData { .. })));
+	}
+
+	#[test]
+	fn test_migrate_file() {
+		let file = env::temp_dir().join("subrpc_test_migrate.json");
+		let v0 = r#"{ "file": "data.json", "registries": {}, "last_update": null }"#;
+		fs::write(&file, v0).unwrap();
+
+		let report = LocalData::migrate_file(&file, true).unwrap();
+		assert_eq!((0, DATA_VERSION), (report.from, report.to));
+		assert_eq!(v0, fs::read_to_string(&file).unwrap());
+
+		LocalData::migrate_file(&file, false).unwrap();
+		assert!(LocalData::migrate_file(&file, true).unwrap().is_empty());
+		assert_eq!(DATA_VERSION, LocalData { file, ..Default::default() }.load().unwrap().version);
+	}
+
+	#[test]
+	fn test_lock() {
+		let file = env::temp_dir().join("subrpc_test_lock.json");
+		let lock = LocalData::lock(&file).unwrap();
+		assert_eq!(process::id().to_string(), fs::read_to_string(LocalData::sibling(&file, ".lock")).unwrap());
+		let (tx, rx) = std::sync::mpsc::channel();
+		let waiting = std::thread::spawn(move || {
+			let mut holder = None;
+			let _lock = LocalData::lock_with(&file, |pid| holder = pid).unwrap();
+			tx.send(holder).unwrap();
+		});
+
+		assert!(rx.recv_timeout(std::time::Duration::from_millis(200)).is_err());
+		drop(lock);
+		assert_eq!(Ok(Some(process::id())), rx.recv_timeout(std::time::Duration::from_secs(5)));
+		waiting.join().unwrap();
+	}
+
+	#[tokio::test]
+	async fn test_merge() {
+		let data = LocalData::init(&env::temp_dir().join("subrpc_test_merge.json"), true)
             .expect("Forced init should work")
             .add_registry(Registry::new("SubRPC Gist 1", "https://gist.githubusercontent.com/chevdor/a8b381911c28f6de02dde62ed1a17dec/raw/6992b0a2924f80f691e4844c1731564f0e2a62ec/data.json"))
             .add_registry(Registry::new("SubRPC Gist 2", "https://gist.githubusercontent.com/chevdor/a8b381911c28f6de02dde62ed1a17dec/raw/6992b0a2924f80f691e4844c1731564f0e2a62ec/data2.json"))
             .refresh()
+            .await
             .save().expect("Saving works");
 		assert_eq!(2, data.registries.len());
 		println!("{data:#?}");
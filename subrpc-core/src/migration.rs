@@ -0,0 +1,262 @@
+//! Migrations of the local data.
+//!
+//! The local data is stored as JSON along with the `version` of its layout. When loading data using
+//! an older layout, the [MIGRATIONS] are applied in order on the raw JSON before it is deserialized,
+//! each of them upgrading the data by one version. Data without `version` is considered version 0.
+
+use crate::{EndpointUrl, Error, Result};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Version of the layout of the local data written by this version of subrpc
+pub const DATA_VERSION: u32 = 1;
+
+/// Upgrade of the local data from one version to the next
+pub struct Migration {
+	/// Version upgraded by the migration, to `from + 1`
+	pub from: u32,
+
+	/// What the migration does
+	pub description: &'static str,
+
+	/// Apply the migration to the raw data, describing each change
+	apply: fn(&mut Map<String, Value>, &mut Vec<String>),
+}
+
+/// All the migrations, in order
+pub const MIGRATIONS: &[Migration] = &[Migration {
+	from: 0,
+	description: "Add the version, store the canonical endpoint urls and upgrade the endpoint stats",
+	apply: v0_to_v1,
+}];
+
+/// Outcome of [migrate]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MigrationReport {
+	/// Version of the data before the migration
+	pub from: u32,
+
+	/// Version of the data after the migration
+	pub to: u32,
+
+	/// Changes made to the data, in order
+	pub changes: Vec<String>,
+}
+
+impl MigrationReport {
+	/// Whether the data was already up to date
+	pub fn is_empty(&self) -> bool {
+		self.from == self.to
+	}
+}
+
+/// Upgrade the raw local data to [DATA_VERSION].
+///
+/// Fails if the data is not an object or was written by a more recent version of subrpc.
+pub fn migrate(data: &mut Value) -> Result<MigrationReport> {
+	let Value::Object(data) = data else {
+		return Err(Error::Migration(format!("The local data must be an object, found {data}")));
+	};
+	let from = match data.get("version") {
+		None => 0,
+		Some(version) => version
+			.as_u64()
+			.and_then(|v| u32::try_from(v).ok())
+			.ok_or_else(|| Error::Migration(format!("Invalid version {version}")))?,
+	};
+	if from > DATA_VERSION {
+		return Err(Error::Migration(format!(
+			"The local data uses version {from}, the latest supported is {DATA_VERSION}. Please upgrade subrpc."
+		)));
+	}
+
+	let mut changes = vec![];
+	for migration in MIGRATIONS.iter().filter(|m| m.from >= from) {
+		changes.push(format!(
+			"Migrating from version {} to {}: {}",
+			migration.from,
+			migration.from + 1,
+			migration.description
+		));
+		(migration.apply)(data, &mut changes);
+		data.insert("version".to_string(), Value::from(migration.from + 1));
+	}
+	Ok(MigrationReport { from, to: DATA_VERSION, changes })
+}
+
+/// Apply `f` to each registry with its name
+fn for_each_registry(data: &mut Map<String, Value>, mut f: impl FnMut(&str, &mut Map<String, Value>)) {
+	if let Some(Value::Object(registries)) = data.get_mut("registries") {
+		for (name, registry) in registries.iter_mut() {
+			if let Value::Object(registry) = registry {
+				f(name, registry);
+			}
+		}
+	}
+}
+
+/// Version 0 is the layout written by subrpc 0.6 and before. Compared to version 1:
+/// - the endpoint urls were stored as written in the registries, they are now stored in their canonical form,
+///   see [EndpointUrl]. Endpoints whose url can no longer be parsed are dropped.
+/// - the stats only had the `failures`, `success` and `latency` counters, see [v0_stats]
+/// - endpoints could neither be removed nor have notes, they are all kept as active and without notes
+fn v0_to_v1(data: &mut Map<String, Value>, changes: &mut Vec<String>) {
+	for_each_registry(data, |name, registry| {
+		let Some(Value::Object(chains)) = registry.get_mut("rpc_endpoints") else {
+			return;
+		};
+		let mut kept = 0;
+		for (chain, endpoints) in chains.iter_mut() {
+			let Value::Array(endpoints) = endpoints else {
+				continue;
+			};
+			endpoints.retain_mut(|endpoint| {
+				let Value::Object(endpoint) = endpoint else {
+					return true;
+				};
+				let url = endpoint.get("url").and_then(Value::as_str).unwrap_or_default().to_string();
+				let prefix = format!("Registry '{name}', {chain} endpoint {url}");
+				match EndpointUrl::try_from(url.as_str()) {
+					Ok(canonical) if canonical.to_string() != url => {
+						changes.push(format!("{prefix}: stored the url as {canonical}"));
+						endpoint.insert("url".to_string(), Value::from(canonical.to_string()));
+					}
+					Ok(_) => {}
+					Err(e) => {
+						changes.push(format!("{prefix}: dropped, {e}"));
+						return false;
+					}
+				}
+				v0_stats(endpoint.entry("stats").or_insert_with(|| Value::Object(Map::new())), &prefix, changes);
+				kept += 1;
+				true
+			});
+		}
+		changes.push(format!("Registry '{name}': kept {kept} endpoint(s) as active and without notes"));
+	});
+}
+
+/// The stats of version 0 only have the `failures` and `success` counters and a `latency` computed as
+/// `latency * (success + 1) / success`, which is scaled back. The weighted counts are seeded from the counters
+/// so that the endpoints keep their ranking, the history starts empty.
+fn v0_stats(stats: &mut Value, endpoint: &str, changes: &mut Vec<String>) {
+	let Value::Object(stats) = stats else {
+		return;
+	};
+	let count = |key| stats.get(key).and_then(Value::as_u64).unwrap_or_default();
+	let (success, failures) = (count("success"), count("failures"));
+
+	if let Some(latency) = stats.get("latency").and_then(Value::as_f64).filter(|l| *l > 0f64 && success > 0) {
+		let fixed = latency * success as f64 / (success + 1) as f64;
+		stats.insert("latency".to_string(), Value::from(fixed));
+		changes.push(format!("{endpoint}: fixed the latency from {latency:.4}s to {fixed:.4}s"));
+	}
+	if success + failures > 0 {
+		changes.push(format!(
+			"{endpoint}: seeded the weighted counts from {success} success(es) and {failures} failure(s)"
+		));
+	}
+	stats.entry("weighted_success").or_insert(Value::from(success as f64));
+	stats.entry("weighted_total").or_insert(Value::from((success + failures) as f64));
+	stats.entry("last_success").or_insert(Value::Null);
+	stats.entry("last_failure").or_insert(Value::Null);
+	stats.entry("history").or_insert(Value::Array(vec![]));
+}
+
+#[cfg(test)]
+mod test_migration {
+	use super::*;
+	use crate::LocalData;
+	use serde_json::json;
+
+	/// Data written by subrpc 0.6 after adding a registry, updating it and pinging its endpoints 3 times
+	fn v0() -> Value {
+		json!({
+			"file": "/tmp/v0/home/.subrpc/data.json",
+			"registries": {
+				"Local": {
+					"enabled": true,
+					"name": "Local",
+					"url": "http://127.0.0.1:8711/local.json",
+					"labels": ["Local"],
+					"last_update": "2023-01-20T11:14:00Z",
+					"rpc_endpoints": {
+						"Kusama": [
+							{
+								"name": "Down",
+								"labels": ["ksm"],
+								"aliases": [],
+								"url": "http://127.0.0.1:1",
+								"stats": { "failures": 3, "success": 0, "latency": 0.0 }
+							}
+						],
+						"Polkadot": [
+							{
+								"name": "Local",
+								"labels": [],
+								"aliases": [],
+								"url": "http://127.0.0.1:8711/rpc",
+								"stats": { "failures": 0, "success": 3, "latency": 0.0013333334 }
+							},
+							{
+								"name": "Parity",
+								"labels": [],
+								"aliases": [],
+								"url": "wss://RPC.polkadot.io:443",
+								"stats": { "failures": 3, "success": 0, "latency": 0.0 }
+							}
+						]
+					}
+				}
+			},
+			"last_update": "2026-10-18T07:06:24.711023077Z"
+		})
+	}
+
+	#[test]
+	fn test_migrate() {
+		let mut data = v0();
+		let report = migrate(&mut data).unwrap();
+		assert_eq!((0, DATA_VERSION), (report.from, report.to));
+		let changes = report.changes.join("\n");
+		assert!(changes.contains("wss://RPC.polkadot.io:443: stored the url as wss://rpc.polkadot.io"), "{changes}");
+		assert!(changes.contains("fixed the latency from 0.0013s to 0.0010s"), "{changes}");
+		assert!(changes.contains("seeded the weighted counts from 3 success(es) and 0 failure(s)"), "{changes}");
+		assert!(changes.contains("kept 3 endpoint(s) as active and without notes"), "{changes}");
+
+		let local_data: LocalData = serde_json::from_value(data.clone()).unwrap();
+		assert_eq!(DATA_VERSION, local_data.version);
+		let registry = &local_data.registries["Local"];
+		let polkadot = &registry.rpc_endpoints["Polkadot"];
+		assert_eq!("wss://rpc.polkadot.io", polkadot[1].url.to_string());
+		assert!(polkadot.iter().all(|e| !e.is_removed() && e.notes.is_none()));
+
+		let stats = &polkadot[0].stats;
+		assert!((stats.latency - 0.001).abs() < 1e-6, "{}", stats.latency);
+		assert_eq!((3f64, 3f64), (stats.weighted_success, stats.weighted_total));
+		assert_eq!(Some(1f32), stats.availability());
+		assert!(stats.history.is_empty());
+		assert_eq!(Some(0f32), registry.rpc_endpoints["Kusama"][0].stats.availability());
+
+		let report = migrate(&mut data).unwrap();
+		assert!(report.is_empty() && report.changes.is_empty());
+	}
+
+	#[test]
+	fn test_invalid_url() {
+		let mut data = v0();
+		data["registries"]["Local"]["rpc_endpoints"]["Kusama"][0]["url"] = json!("wss://");
+		let changes = migrate(&mut data).unwrap().changes.join("\n");
+		assert!(changes.contains("Kusama endpoint wss://: dropped"), "{changes}");
+
+		let local_data: LocalData = serde_json::from_value(data).unwrap();
+		assert!(local_data.registries["Local"].rpc_endpoints["Kusama"].is_empty());
+	}
+
+	#[test]
+	fn test_unsupported() {
+		assert!(matches!(migrate(&mut json!({ "version": DATA_VERSION + 1 })), Err(Error::Migration(_))));
+		assert!(matches!(migrate(&mut json!({ "version": "one" })), Err(Error::Migration(_))));
+		assert!(matches!(migrate(&mut json!([])), Err(Error::Migration(_))));
+	}
+}
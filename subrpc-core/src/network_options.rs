@@ -0,0 +1,58 @@
+use crate::Secrets;
+use std::time::Duration;
+
+/// Default number of requests allowed to run at the same time
+pub const DEFAULT_CONCURRENCY: usize = 16;
+
+/// Default timeout for a single request
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Options controlling how the network requests (registry updates, pings) are issued.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkOptions {
+	/// Maximum number of requests running concurrently. A value of 0 is treated as 1.
+	pub concurrency: usize,
+
+	/// Timeout applied to each individual request
+	pub timeout: Duration,
+
+	/// Secrets used to authenticate to the endpoints
+	pub secrets: Secrets,
+
+	/// Whether pings also probe the health of the nodes, see [Registry::ping_chain](crate::Registry::ping_chain)
+	pub health_probe: bool,
+}
+
+impl NetworkOptions {
+	pub fn new(concurrency: usize, timeout: Duration) -> Self {
+		Self { concurrency, timeout, ..Default::default() }
+	}
+
+	/// Use the given secrets to authenticate to the endpoints
+	pub fn with_secrets(mut self, secrets: Secrets) -> Self {
+		self.secrets = secrets;
+		self
+	}
+
+	/// Enable or disable the health probe
+	pub fn with_health_probe(mut self, health_probe: bool) -> Self {
+		self.health_probe = health_probe;
+		self
+	}
+
+	/// Effective concurrency limit, never 0.
+	pub(crate) fn limit(&self) -> usize {
+		self.concurrency.max(1)
+	}
+}
+
+impl Default for NetworkOptions {
+	fn default() -> Self {
+		Self {
+			concurrency: DEFAULT_CONCURRENCY,
+			timeout: DEFAULT_TIMEOUT,
+			secrets: Secrets::default(),
+			health_probe: true,
+		}
+	}
+}
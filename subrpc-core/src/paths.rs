@@ -0,0 +1,252 @@
+//! Location of the files of subrpc.
+//!
+//! By default, the files follow the XDG base directories: the config goes to `$XDG_CONFIG_HOME/subrpc`
+//! and the local data to `$XDG_DATA_HOME/subrpc`. When those variables are not set, the defaults
+//! of the platform are used, such as `~/.config` and `~/.local/share` on Linux. subrpc keeps no cache,
+//! `$XDG_CACHE_HOME` is not used.
+//!
+//! Setting [ENV_HOME] puts all the files in a single directory instead.
+//! Older versions of subrpc stored everything in `~/.subrpc`, see [Paths::migrate_legacy].
+
+use crate::{Error, LocalData, Result};
+use log::*;
+use serde::Serialize;
+use std::{
+	env,
+	ffi::{OsStr, OsString},
+	fs, io,
+	path::{Path, PathBuf},
+};
+
+/// Environment variable setting the directory holding all the files of subrpc
+pub const ENV_HOME: &str = "SUBRPC_HOME";
+
+/// Name of the directories of subrpc in the base directories
+const APP_DIR: &str = "subrpc";
+
+/// Directories holding the files of subrpc. The directories are not created until something is saved.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Paths {
+	/// Directory of the [Config](crate::Config)
+	pub config_dir: PathBuf,
+
+	/// Directory of the [LocalData]
+	pub data_dir: PathBuf,
+
+	/// Directory used by older versions, `~/.subrpc`, whose files are moved by [Self::migrate_legacy].
+	/// Not set when the home directory is unknown.
+	pub legacy_dir: Option<PathBuf>,
+}
+
+/// Base directories of the platform, used when the XDG variables are not set
+#[derive(Debug, Default)]
+struct BaseDirs {
+	home: Option<PathBuf>,
+	config: Option<PathBuf>,
+	data: Option<PathBuf>,
+}
+
+impl BaseDirs {
+	fn platform() -> Self {
+		Self { home: dirs::home_dir(), config: dirs::config_dir(), data: dirs::data_dir() }
+	}
+}
+
+impl Paths {
+	/// Directories according to [ENV_HOME] and the XDG variables of the environment
+	pub fn new() -> Result<Self> {
+		Self::from_env(BaseDirs::platform(), |var| env::var_os(var))
+	}
+
+	/// Directories according to `var`, returning the value of the environment variables,
+	/// and to the `base` directories when the variables are not set
+	fn from_env(base: BaseDirs, var: impl Fn(&str) -> Option<OsString>) -> Result<Self> {
+		let home = base.home;
+		let legacy_dir = home.as_ref().map(|home| home.join(".subrpc"));
+		if let Some(dir) = var(ENV_HOME).filter(|dir| !dir.is_empty()) {
+			let dir = PathBuf::from(dir);
+			return Ok(Self { config_dir: dir.clone(), data_dir: dir, legacy_dir });
+		}
+
+		// Relative paths in the XDG variables are invalid and must be ignored
+		let base_dir = |name: &str, default: Option<PathBuf>, fallback: &str| -> Result<PathBuf> {
+			let dir = var(name)
+				.map(PathBuf::from)
+				.filter(|dir| dir.is_absolute())
+				.or(default)
+				.or_else(|| home.as_ref().map(|home| home.join(fallback)))
+				.ok_or(Error::NoHomeDir)?;
+			Ok(dir.join(APP_DIR))
+		};
+		Ok(Self {
+			config_dir: base_dir("XDG_CONFIG_HOME", base.config, ".config")?,
+			data_dir: base_dir("XDG_DATA_HOME", base.data, ".local/share")?,
+			legacy_dir,
+		})
+	}
+
+	/// Default file of the [Config](crate::Config)
+	pub fn config_file(&self) -> PathBuf {
+		self.config_dir.join("config.toml")
+	}
+
+	/// Default file of the [LocalData]
+	pub fn data_file(&self) -> PathBuf {
+		self.data_dir.join("data.json")
+	}
+
+	/// Move the config and the local data, with their backups, from the [legacy directory](Self::legacy_dir)
+	/// to their current directories. Files already present at the destination are never overwritten.
+	///
+	/// The [lock](LocalData::lock) of the data is held at both locations while moving the files.
+	/// Returns the files moved, at their new location. The legacy directory is removed once empty.
+	pub fn migrate_legacy(&self) -> Result<Vec<PathBuf>> {
+		let Some(legacy_dir) = &self.legacy_dir else {
+			return Ok(vec![]);
+		};
+		if !legacy_dir.is_dir() || [&self.config_dir, &self.data_dir].contains(&legacy_dir) {
+			return Ok(vec![]);
+		}
+
+		let locks = [legacy_dir.join("data.json"), self.data_file()]
+			.iter()
+			.map(|file| LocalData::lock(file))
+			.collect::<Result<Vec<_>>>()?;
+		let mut moved = vec![];
+		for entry in fs::read_dir(legacy_dir)? {
+			let file = entry?.path();
+			let Some(name) = file.file_name().and_then(OsStr::to_str) else {
+				continue;
+			};
+			let dir = match name {
+				_ if name.ends_with(".lock") => continue,
+				_ if name.starts_with("config.") => &self.config_dir,
+				_ if name.starts_with("data.") => &self.data_dir,
+				_ => continue,
+			};
+
+			let target = dir.join(name);
+			if target.exists() {
+				warn!("Not moving {}, {} already exists", file.display(), target.display());
+				continue;
+			}
+			debug!("Moving {} to {}", file.display(), target.display());
+			fs::create_dir_all(dir)?;
+			move_file(&file, &target)?;
+			moved.push(target);
+		}
+		drop(locks);
+
+		// The lock files are no longer used once the data has moved
+		if !legacy_dir.join("data.json").exists() {
+			for entry in fs::read_dir(legacy_dir)? {
+				let file = entry?.path();
+				if file.extension() == Some(OsStr::new("lock")) {
+					fs::remove_file(&file)?;
+				}
+			}
+		}
+		if fs::remove_dir(legacy_dir).is_err() {
+			info!("Some files were left in {}", legacy_dir.display());
+		}
+		Ok(moved)
+	}
+}
+
+/// Rename `from` into `to`, copying it when they are not on the same file system
+fn move_file(from: &Path, to: &Path) -> io::Result<()> {
+	fs::rename(from, to).or_else(|_| {
+		fs::copy(from, to)?;
+		fs::remove_file(from)
+	})
+}
+
+#[cfg(test)]
+mod test_paths {
+	use super::*;
+	use std::collections::HashMap;
+
+	fn paths_with(base: BaseDirs, vars: &[(&str, &str)]) -> Paths {
+		let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+		Paths::from_env(base, |var| vars.get(var).map(OsString::from)).unwrap()
+	}
+
+	fn home() -> BaseDirs {
+		BaseDirs { home: Some(PathBuf::from("/home/user")), ..Default::default() }
+	}
+
+	#[test]
+	fn test_from_env() {
+		let paths = paths_with(home(), &[("XDG_CONFIG_HOME", "/xdg/config"), ("XDG_DATA_HOME", "/xdg/data")]);
+		assert_eq!(PathBuf::from("/xdg/config/subrpc/config.toml"), paths.config_file());
+		assert_eq!(PathBuf::from("/xdg/data/subrpc/data.json"), paths.data_file());
+		assert_eq!(Some(PathBuf::from("/home/user/.subrpc")), paths.legacy_dir);
+
+		let paths = paths_with(home(), &[(ENV_HOME, "/opt/subrpc"), ("XDG_DATA_HOME", "/xdg/data")]);
+		assert_eq!(PathBuf::from("/opt/subrpc/data.json"), paths.data_file());
+		assert_eq!(PathBuf::from("/opt/subrpc/config.toml"), paths.config_file());
+		assert_eq!(Some(PathBuf::from("/home/user/.subrpc")), paths.legacy_dir);
+
+		// Relative XDG paths are ignored in favor of the defaults of the platform, then of the home directory
+		let paths = paths_with(home(), &[("XDG_DATA_HOME", "relative"), ("XDG_CONFIG_HOME", "")]);
+		assert_eq!(PathBuf::from("/home/user/.local/share/subrpc"), paths.data_dir);
+		assert_eq!(PathBuf::from("/home/user/.config/subrpc"), paths.config_dir);
+		let base = BaseDirs { data: Some(PathBuf::from("/Library/Application Support")), ..home() };
+		let paths = paths_with(base, &[("XDG_DATA_HOME", "relative")]);
+		assert_eq!(PathBuf::from("/Library/Application Support/subrpc"), paths.data_dir);
+
+		assert!(matches!(Paths::from_env(BaseDirs::default(), |_| None), Err(Error::NoHomeDir)));
+	}
+
+	#[test]
+	fn test_migrate_legacy() {
+		let root = env::temp_dir().join("subrpc_test_paths");
+		let _ = fs::remove_dir_all(&root);
+		let legacy_dir = root.join(".subrpc");
+		fs::create_dir_all(&legacy_dir).unwrap();
+		["config.toml", "data.json", "data.json.bak.1", "data.json.lock"]
+			.iter()
+			.for_each(|name| fs::write(legacy_dir.join(name), name).unwrap());
+		let paths = Paths {
+			config_dir: root.join("config"),
+			data_dir: root.join("data"),
+			legacy_dir: Some(legacy_dir.clone()),
+		};
+
+		let mut moved = paths.migrate_legacy().unwrap();
+		moved.sort();
+		assert_eq!(vec![paths.config_file(), paths.data_file(), paths.data_dir.join("data.json.bak.1")], moved);
+		assert_eq!("data.json", fs::read_to_string(paths.data_file()).unwrap());
+		assert!(!legacy_dir.exists());
+		assert!(paths.migrate_legacy().unwrap().is_empty());
+
+		// Processes using the data at either location are waited for
+		fs::create_dir_all(&legacy_dir).unwrap();
+		fs::write(legacy_dir.join("data.json.bak.2"), "bak").unwrap();
+		let lock = LocalData::lock(&legacy_dir.join("data.json")).unwrap();
+		let migrating = {
+			let paths = paths.clone();
+			std::thread::spawn(move || paths.migrate_legacy().unwrap())
+		};
+		std::thread::sleep(std::time::Duration::from_millis(200));
+		assert!(legacy_dir.join("data.json.bak.2").exists());
+		drop(lock);
+		assert_eq!(vec![paths.data_dir.join("data.json.bak.2")], migrating.join().unwrap());
+		assert!(!legacy_dir.exists());
+
+		fs::create_dir_all(&legacy_dir).unwrap();
+		fs::write(legacy_dir.join("data.json"), "old").unwrap();
+		assert!(paths.migrate_legacy().unwrap().is_empty());
+		assert_eq!("data.json", fs::read_to_string(paths.data_file()).unwrap());
+
+		// The legacy files move to the directory set by SUBRPC_HOME too, unless it is the legacy directory
+		let base = || BaseDirs { home: Some(root.clone()), ..Default::default() };
+		let paths =
+			Paths::from_env(base(), |var| (var == ENV_HOME).then(|| legacy_dir.clone().into_os_string())).unwrap();
+		assert!(paths.migrate_legacy().unwrap().is_empty());
+		let home = root.join("home");
+		let paths = Paths::from_env(base(), |var| (var == ENV_HOME).then(|| home.clone().into_os_string())).unwrap();
+		assert_eq!(vec![home.join("data.json")], paths.migrate_legacy().unwrap());
+		assert_eq!("old", fs::read_to_string(home.join("data.json")).unwrap());
+	}
+}
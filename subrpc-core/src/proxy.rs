@@ -0,0 +1,841 @@
+//! Local JSON-RPC proxy balancing the requests over the endpoints of the [LocalData].
+//!
+//! The [Proxy] listens for WebSocket connections, such as `ws://127.0.0.1:9955/polkadot`: the path names the chain,
+//! given by its name or one of its aliases, see [LocalData::resolve_chain]. Other names get a 404.
+//! The root path serves the default chain of the proxy.
+//!
+//! Each connection is forwarded to the best WebSocket endpoint of the chain, ranked by [Config]. When that endpoint
+//! drops, the proxy connects to the next one, sends the requests left unanswered again and renews the subscriptions,
+//! which keep the ids known to the client. Requests submitting or broadcasting transactions are never sent twice:
+//! they fail instead, and their subscriptions end, as do the `chainHead` follow subscriptions whose state cannot be
+//! restored, along with the pending calls made on them.
+//!
+//! The latency of the responses and the failures of the endpoints are recorded in their [EndpointStats](crate::EndpointStats)
+//! and saved every [PROXY_STATS_INTERVAL].
+
+use crate::{Config, Endpoint, EndpointQuery, Error, LocalData, Protocol, Result, Storage};
+use futures::{future, SinkExt, StreamExt};
+use log::*;
+use serde_json::{json, Value};
+use std::{
+	collections::{HashMap, HashSet},
+	future::Future,
+	net::{Ipv4Addr, SocketAddr},
+	sync::{Arc, Mutex, PoisonError, RwLock},
+	time::{Duration, Instant},
+};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::{
+	tungstenite::{
+		client::IntoClientRequest,
+		handshake::server::{ErrorResponse, Request, Response},
+		http::{HeaderName, HeaderValue, StatusCode},
+		protocol::{frame::coding::CloseCode, CloseFrame},
+		Message,
+	},
+	MaybeTlsStream, WebSocketStream,
+};
+
+/// Default port of the [Proxy]. The nodes usually serve their RPC on 9944, so that a local node can run alongside.
+pub const DEFAULT_PROXY_PORT: u16 = 9955;
+
+/// Interval at which the [Proxy] saves the stats of the endpoints
+pub const PROXY_STATS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Code of the errors returned by the [Proxy] to the requests it could not forward
+pub const PROXY_ERROR_CODE: i64 = -32050;
+
+type Upstream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Local WebSocket JSON-RPC proxy, see the [module](self) documentation.
+///
+/// ```no_run
+/// # async fn run() -> subrpc_core::Result<()> {
+/// use subrpc_core::{Config, JsonStorage, Proxy};
+/// use std::sync::Arc;
+///
+/// let storage = Arc::new(JsonStorage::new(&subrpc_core::LocalData::get_default_file()?));
+/// Proxy::new(storage, Config::default()).chain("polkadot").bind().await?.run().await
+/// # }
+/// ```
+pub struct Proxy {
+	storage: Arc<dyn Storage>,
+	config: Config,
+	addr: SocketAddr,
+	chain: Option<String>,
+	stats_interval: Duration,
+}
+
+impl Proxy {
+	/// Proxy of the endpoints stored in `storage`, listening on `127.0.0.1:`[DEFAULT_PROXY_PORT]
+	pub fn new(storage: Arc<dyn Storage>, config: Config) -> Self {
+		Self {
+			storage,
+			config,
+			addr: SocketAddr::from((Ipv4Addr::LOCALHOST, DEFAULT_PROXY_PORT)),
+			chain: None,
+			stats_interval: PROXY_STATS_INTERVAL,
+		}
+	}
+
+	/// Listen on `addr`. Port 0 picks a free port, see [BoundProxy::local_addr].
+	pub fn addr(mut self, addr: SocketAddr) -> Self {
+		self.addr = addr;
+		self
+	}
+
+	/// Serve `chain` on the root path, the other chains remain available by path
+	pub fn chain(mut self, chain: &str) -> Self {
+		self.chain = Some(chain.to_string());
+		self
+	}
+
+	/// Save the stats of the endpoints at this interval instead of [PROXY_STATS_INTERVAL]
+	pub fn stats_interval(mut self, interval: Duration) -> Self {
+		self.stats_interval = interval;
+		self
+	}
+
+	/// Load the data and start listening
+	pub async fn bind(self) -> Result<BoundProxy> {
+		let data = LocalData { storage: Some(self.storage.clone()), ..Default::default() }.load()?;
+		if let Some(chain) = &self.chain {
+			data.resolve_chain(chain, &self.config)?;
+		}
+		let listener = TcpListener::bind(self.addr).await?;
+		info!("Proxy listening on {}", listener.local_addr()?);
+		let state = State {
+			storage: self.storage,
+			config: self.config,
+			chain: self.chain,
+			data: RwLock::new(data),
+			stats: Mutex::new(HashMap::new()),
+		};
+		Ok(BoundProxy { listener, state: Arc::new(state), stats_interval: self.stats_interval })
+	}
+}
+
+/// A [Proxy] ready to accept connections
+pub struct BoundProxy {
+	listener: TcpListener,
+	state: Arc<State>,
+	stats_interval: Duration,
+}
+
+impl BoundProxy {
+	/// Address the proxy listens on
+	pub fn local_addr(&self) -> Result<SocketAddr> {
+		Ok(self.listener.local_addr()?)
+	}
+
+	/// Serve the connections forever
+	pub async fn run(self) -> Result<()> {
+		self.run_until(future::pending::<()>()).await
+	}
+
+	/// Serve the connections until `shutdown` completes, then save the stats
+	pub async fn run_until<F: Future>(self, shutdown: F) -> Result<()> {
+		let mut interval = tokio::time::interval(self.stats_interval);
+		interval.tick().await;
+		tokio::pin!(shutdown);
+		loop {
+			tokio::select! {
+				accepted = self.listener.accept() => match accepted {
+					Ok((stream, peer)) => {
+						debug!("Connection from {peer}");
+						tokio::spawn(serve(self.state.clone(), stream));
+					}
+					Err(e) => warn!("Failed accepting a connection: {e}"),
+				},
+				_ = interval.tick() => {
+					if let Err(e) = self.state.clone().save_stats().await {
+						error!("Failed saving the stats of the endpoints: {e}");
+					}
+				}
+				_ = &mut shutdown => break,
+			}
+		}
+		self.state.clone().save_stats().await
+	}
+}
+
+/// Outcome of the requests sent to an endpoint since the stats were last saved
+#[derive(Debug, Default, Clone, Copy)]
+struct UrlStats {
+	success: u32,
+	failures: u32,
+	latency: f32,
+}
+
+struct State {
+	storage: Arc<dyn Storage>,
+	config: Config,
+	chain: Option<String>,
+	data: RwLock<LocalData>,
+	stats: Mutex<HashMap<String, UrlStats>>,
+}
+
+impl State {
+	/// Chain served on `path`
+	fn chain(&self, path: &str) -> Result<String> {
+		let name = path.trim_matches('/');
+		let name = match (name, &self.chain) {
+			("", Some(chain)) => chain,
+			("", None) => return Err(Error::UnknownChain { name: "/".to_string(), suggestions: vec![] }),
+			(name, _) => name,
+		};
+		let data = self.data.read().unwrap_or_else(PoisonError::into_inner);
+		Ok(data.resolve_chain(name, &self.config)?.chain)
+	}
+
+	/// WebSocket endpoints of `chain`, the best first
+	fn endpoints(&self, chain: &str) -> Vec<Endpoint> {
+		let data = self.data.read().unwrap_or_else(PoisonError::into_inner);
+		let mut endpoints = data.best_endpoints_for(&EndpointQuery::new().chain(chain), None, &self.config);
+		endpoints.retain(|e| matches!(e.url.protocol(), Protocol::Ws | Protocol::Wss));
+		endpoints
+	}
+
+	fn record(&self, endpoint: &Endpoint, latency: Option<Duration>) {
+		let mut stats = self.stats.lock().unwrap_or_else(PoisonError::into_inner);
+		let stats = stats.entry(endpoint.url.to_string()).or_default();
+		match latency {
+			Some(latency) => {
+				stats.success += 1;
+				stats.latency += latency.as_secs_f32();
+			}
+			None => stats.failures += 1,
+		}
+	}
+
+	/// Record the stats collected since the last save in the stored data, which the proxy then uses.
+	///
+	/// The data is reloaded under the [lock](LocalData::lock) so that the changes made meanwhile are kept.
+	/// Each endpoint gets one sample with the average latency of its responses, weighted by their number,
+	/// and one sample for its failures, see [EndpointStats::add_many](crate::EndpointStats::add_many).
+	async fn save_stats(self: Arc<Self>) -> Result<()> {
+		let stats = std::mem::take(&mut *self.stats.lock().unwrap_or_else(PoisonError::into_inner));
+		if stats.is_empty() {
+			return Ok(());
+		}
+		let state = self.clone();
+		let data = tokio::task::spawn_blocking(move || -> Result<LocalData> {
+			let _lock = state.storage.file().map(LocalData::lock).transpose()?;
+			let mut data = LocalData { storage: Some(state.storage.clone()), ..Default::default() }.load()?;
+			data.registries.values_mut().flat_map(|reg| reg.rpc_endpoints.values_mut().flatten()).for_each(|e| {
+				if let Some(s) = stats.get(&e.url.to_string()) {
+					e.stats.add_many(s.success, true, Some(s.latency / s.success.max(1) as f32));
+					e.stats.add_many(s.failures, false, None);
+				}
+			});
+			data.save()
+		})
+		.await
+		.map_err(|e| Error::Io(std::io::Error::other(e)))??;
+		debug!("Saved the stats of the proxied endpoints");
+		*self.data.write().unwrap_or_else(PoisonError::into_inner) = data;
+		Ok(())
+	}
+}
+
+/// Serve a connection: route it to its chain and forward its messages until the client leaves
+async fn serve(state: Arc<State>, stream: TcpStream) {
+	let mut chain = None;
+	// The type of the error is imposed by tungstenite
+	#[allow(clippy::result_large_err)]
+	let callback = |request: &Request, response: Response| -> std::result::Result<Response, ErrorResponse> {
+		match state.chain(request.uri().path()) {
+			Ok(name) => {
+				chain = Some(name);
+				Ok(response)
+			}
+			Err(e) => {
+				let mut response = ErrorResponse::new(Some(e.to_string()));
+				*response.status_mut() = StatusCode::NOT_FOUND;
+				Err(response)
+			}
+		}
+	};
+	let client = match tokio_tungstenite::accept_hdr_async(stream, callback).await {
+		Ok(client) => client,
+		Err(e) => {
+			debug!("WebSocket handshake failed: {e}");
+			return;
+		}
+	};
+	let Some(chain) = chain else {
+		return;
+	};
+	let endpoints = state.endpoints(&chain);
+	info!("Proxying {chain} over {} endpoints", endpoints.len());
+	forward(&state, client, &chain, &endpoints).await
+}
+
+/// Connect to the first endpoint that accepts the connection, starting at `next` and trying each endpoint once
+async fn connect(state: &State, endpoints: &[Endpoint], next: &mut usize) -> Option<(Endpoint, Upstream)> {
+	for _ in 0..endpoints.len() {
+		let endpoint = &endpoints[*next % endpoints.len()];
+		*next += 1;
+		let timeout = Duration::from_secs(state.config.timeout);
+		match tokio::time::timeout(timeout, connect_endpoint(endpoint, &state.config)).await {
+			Ok(Ok(upstream)) => {
+				info!("Forwarding to {}", endpoint.url);
+				return Some((endpoint.clone(), upstream));
+			}
+			Ok(Err(e)) => warn!("Failed connecting to {}: {e}", endpoint.url),
+			Err(_) => warn!("Timeout connecting to {}", endpoint.url),
+		}
+		state.record(endpoint, None);
+	}
+	None
+}
+
+async fn connect_endpoint(endpoint: &Endpoint, config: &Config) -> Result<Upstream> {
+	let url = endpoint.resolved_url(&config.secrets)?;
+	let mut request = url.to_string().into_client_request()?;
+	for (name, value) in endpoint.headers(&config.secrets)? {
+		let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| Error::Auth(e.to_string()))?;
+		let value = HeaderValue::from_str(&value).map_err(|e| Error::Auth(e.to_string()))?;
+		request.headers_mut().insert(name, value);
+	}
+	let (upstream, _) = tokio_tungstenite::connect_async(request).await?;
+	Ok(upstream)
+}
+
+/// Forward the messages between the client and the endpoints, failing over to the next endpoint when one drops.
+///
+/// A connection that drops before the endpoint sent anything and within the [timeout](Config::timeout) makes no
+/// progress. After a full pass over the endpoints without progress, the client is disconnected rather than
+/// reconnecting and replaying its requests forever.
+async fn forward(state: &State, client: WebSocketStream<TcpStream>, chain: &str, endpoints: &[Endpoint]) {
+	let (mut client_tx, mut client_rx) = client.split();
+	let mut session = Session::default();
+	let mut next = 0;
+	let mut fruitless = 0;
+
+	loop {
+		let upstream = if fruitless < endpoints.len() { connect(state, endpoints, &mut next).await } else { None };
+		let Some((endpoint, upstream)) = upstream else {
+			warn!("No endpoint available for {chain}");
+			for error in session.fail_pending() {
+				let _ = client_tx.send(Message::text(error)).await;
+			}
+			let reason = format!("No endpoint available for {chain}");
+			let close = CloseFrame { code: CloseCode::Again, reason: reason.into() };
+			let _ = client_tx.send(Message::Close(Some(close))).await;
+			return;
+		};
+		let (mut upstream_tx, mut upstream_rx) = upstream.split();
+		let connected = Instant::now();
+		let mut progress = false;
+
+		let (replay, errors) = session.failover();
+		for error in errors {
+			if client_tx.send(Message::text(error)).await.is_err() {
+				return;
+			}
+		}
+		let mut dropped = false;
+		for message in replay {
+			dropped |= upstream_tx.send(Message::text(message)).await.is_err();
+		}
+
+		while !dropped {
+			tokio::select! {
+				message = client_rx.next() => match message {
+					Some(Ok(Message::Text(text))) => {
+						dropped = upstream_tx.send(Message::text(session.on_client(&text))).await.is_err();
+					}
+					Some(Ok(Message::Close(_))) | None | Some(Err(_)) => {
+						debug!("Client left {chain}");
+						let _ = upstream_tx.send(Message::Close(None)).await;
+						return;
+					}
+					Some(Ok(_)) => {}
+				},
+				message = upstream_rx.next() => match message {
+					Some(Ok(Message::Text(text))) => {
+						progress = true;
+						let (message, latencies) = session.on_upstream(&text);
+						latencies.into_iter().for_each(|latency| state.record(&endpoint, Some(latency)));
+						if let Some(message) = message {
+							if client_tx.send(Message::text(message)).await.is_err() {
+								return;
+							}
+						}
+					}
+					Some(Ok(Message::Close(_))) | None | Some(Err(_)) => dropped = true,
+					Some(Ok(_)) => {}
+				},
+			}
+		}
+		warn!("Lost the connection to {}, failing over", endpoint.url);
+		state.record(&endpoint, None);
+		if progress || connected.elapsed() >= Duration::from_secs(state.config.timeout) {
+			fruitless = 0;
+		} else {
+			fruitless += 1;
+		}
+	}
+}
+
+/// Short name of a method, lowercase and without its namespace: `chain_subscribeNewHeads` is `subscribenewheads`
+fn short_name(method: &str) -> String {
+	method.rsplit('_').next().unwrap_or(method).to_lowercase()
+}
+
+/// Whether `method` starts a subscription
+fn is_subscription(method: &str) -> bool {
+	let name = short_name(method);
+	name.starts_with("subscribe") || name.starts_with("watch") || name.contains("andwatch") || name == "follow"
+}
+
+/// Whether `method` submits or broadcasts a transaction, which must not be sent twice
+fn is_submission(method: &str) -> bool {
+	let name = short_name(method);
+	name.starts_with("submit") || name == "broadcast"
+}
+
+/// Whether the subscriptions started by `method` can be renewed on another endpoint
+fn is_renewable(method: &str) -> bool {
+	!is_submission(method) && short_name(method) != "follow"
+}
+
+/// Whether `method` ends a subscription
+fn is_unsubscription(method: &str) -> bool {
+	let name = short_name(method);
+	["unsubscribe", "unwatch", "unfollow"].iter().any(|prefix| name.starts_with(prefix))
+}
+
+fn method(message: &Value) -> &str {
+	message.get("method").and_then(Value::as_str).unwrap_or_default()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum RequestKind {
+	Call,
+	Subscribe,
+	/// Renewal of the subscription known to the client by this id
+	Renew(Value),
+}
+
+/// Request forwarded to the endpoint and not answered yet
+#[derive(Debug, Clone)]
+struct Pending {
+	request: Value,
+	sent: Instant,
+	kind: RequestKind,
+}
+
+/// Subscription of the client
+#[derive(Debug, Clone)]
+struct Subscription {
+	request: Value,
+	upstream_id: Value,
+}
+
+/// State of the JSON-RPC session of a client, kept across the endpoints it is forwarded to
+#[derive(Debug, Default)]
+struct Session {
+	/// Requests not answered yet, by id
+	pending: HashMap<String, Pending>,
+
+	/// Subscriptions by the id known to the client
+	subscriptions: HashMap<String, Subscription>,
+
+	/// Ids known to the client of the subscriptions of the current endpoint, by the id of the endpoint
+	client_ids: HashMap<String, Value>,
+
+	renewals: u64,
+}
+
+impl Session {
+	/// Track a message of the client and return it as it must be sent to the endpoint
+	fn on_client(&mut self, text: &str) -> String {
+		let Ok(mut message) = serde_json::from_str::<Value>(text) else {
+			return text.to_string();
+		};
+		match &mut message {
+			Value::Array(requests) => requests.iter_mut().for_each(|request| *request = self.track(request)),
+			request => *request = self.track(request),
+		}
+		message.to_string()
+	}
+
+	fn track(&mut self, request: &Value) -> Value {
+		let method = method(request);
+		if let Some(id) = request.get("id") {
+			let kind = if is_subscription(method) { RequestKind::Subscribe } else { RequestKind::Call };
+			self.pending.insert(id.to_string(), Pending { request: request.clone(), sent: Instant::now(), kind });
+		}
+		if is_unsubscription(method) {
+			if let Some(subscription) =
+				request["params"].get(0).and_then(|id| self.subscriptions.remove(&id.to_string()))
+			{
+				self.client_ids.remove(&subscription.upstream_id.to_string());
+				return self.rewrite(request, &subscription.upstream_id);
+			}
+		}
+		match request["params"].get(0).and_then(|id| self.subscriptions.get(&id.to_string())) {
+			Some(subscription) => self.rewrite(request, &subscription.upstream_id),
+			None => request.clone(),
+		}
+	}
+
+	/// `request` with the first param, a subscription id, replaced with `upstream_id`
+	fn rewrite(&self, request: &Value, upstream_id: &Value) -> Value {
+		let mut request = request.clone();
+		request["params"][0] = upstream_id.clone();
+		request
+	}
+
+	/// Process a message of the endpoint, returning the message to send to the client, if any,
+	/// and the latency of the requests it answers
+	fn on_upstream(&mut self, text: &str) -> (Option<String>, Vec<Duration>) {
+		let Ok(message) = serde_json::from_str::<Value>(text) else {
+			return (Some(text.to_string()), vec![]);
+		};
+		let mut latencies = vec![];
+		let message = match message {
+			Value::Array(responses) => {
+				let responses: Vec<Value> =
+					responses.into_iter().filter_map(|response| self.receive(response, &mut latencies)).collect();
+				(!responses.is_empty()).then_some(Value::Array(responses))
+			}
+			response => self.receive(response, &mut latencies),
+		};
+		(message.map(|m| m.to_string()), latencies)
+	}
+
+	fn receive(&mut self, mut message: Value, latencies: &mut Vec<Duration>) -> Option<Value> {
+		let is_response = message.get("result").is_some() || message.get("error").is_some();
+		match message.get("id") {
+			Some(id) if is_response => {
+				let Some(pending) = self.pending.remove(&id.to_string()) else {
+					return Some(message);
+				};
+				latencies.push(pending.sent.elapsed());
+				let upstream_id = message.get("result").cloned();
+				match (pending.kind, upstream_id) {
+					(RequestKind::Subscribe, Some(upstream_id)) => {
+						self.client_ids.insert(upstream_id.to_string(), upstream_id.clone());
+						self.subscriptions
+							.insert(upstream_id.to_string(), Subscription { request: pending.request, upstream_id });
+					}
+					(RequestKind::Renew(client_id), Some(upstream_id)) => {
+						debug!("Renewed the subscription {client_id} as {upstream_id}");
+						self.client_ids.insert(upstream_id.to_string(), client_id.clone());
+						if let Some(subscription) = self.subscriptions.get_mut(&client_id.to_string()) {
+							subscription.upstream_id = upstream_id;
+						}
+						return None;
+					}
+					(RequestKind::Renew(client_id), None) => {
+						warn!("Failed renewing the subscription {client_id}: {}", message["error"]);
+						self.subscriptions.remove(&client_id.to_string());
+						return None;
+					}
+					_ => {}
+				}
+				Some(message)
+			}
+			_ => {
+				let upstream_id = message["params"].get("subscription").map(Value::to_string);
+				if let Some(client_id) = upstream_id.and_then(|id| self.client_ids.get(&id)) {
+					message["params"]["subscription"] = client_id.clone();
+				}
+				Some(message)
+			}
+		}
+	}
+
+	/// Prepare the session for a new endpoint. Returns the requests to send to the endpoint: the pending requests
+	/// and the renewals of the subscriptions, and the errors to send to the client for the requests that cannot be retried.
+	fn failover(&mut self) -> (Vec<String>, Vec<String>) {
+		self.client_ids.clear();
+		let ended: HashSet<String> = self
+			.subscriptions
+			.iter()
+			.filter(|(_, subscription)| !is_renewable(method(&subscription.request)))
+			.map(|(id, _)| id.clone())
+			.collect();
+		// Calls made on an ended subscription, such as `chainHead_v1_header` on a follow subscription, fail with it
+		let errors = self.fail(|pending| {
+			is_submission(method(&pending.request))
+				|| pending.request["params"].get(0).is_some_and(|id| ended.contains(&id.to_string()))
+		});
+		self.subscriptions.retain(|id, subscription| {
+			let renewable = !ended.contains(id);
+			if !renewable {
+				warn!("Ending the subscription {id} to {}", method(&subscription.request));
+			}
+			renewable
+		});
+		self.pending.retain(|_, pending| !matches!(pending.kind, RequestKind::Renew(_)));
+
+		let mut replay: Vec<String> = self
+			.pending
+			.values_mut()
+			.map(|pending| {
+				pending.sent = Instant::now();
+				pending.request.to_string()
+			})
+			.collect();
+		for (client_id, subscription) in &self.subscriptions {
+			self.renewals += 1;
+			let id = Value::from(format!("subrpc-renewal-{}", self.renewals));
+			let mut request = subscription.request.clone();
+			request["id"] = id.clone();
+			replay.push(request.to_string());
+			let client_id = serde_json::from_str(client_id).unwrap_or_default();
+			let kind = RequestKind::Renew(client_id);
+			self.pending.insert(id.to_string(), Pending { request, sent: Instant::now(), kind });
+		}
+		(replay, errors)
+	}
+
+	/// Errors to send to the client for all the pending requests
+	fn fail_pending(&mut self) -> Vec<String> {
+		self.fail(|_| true)
+	}
+
+	/// Forget the pending requests matching `filter` and return the errors to send to the client
+	fn fail(&mut self, filter: impl Fn(&Pending) -> bool) -> Vec<String> {
+		let failed: Vec<String> = self.pending.iter().filter(|(_, p)| filter(p)).map(|(id, _)| id.clone()).collect();
+		failed
+			.into_iter()
+			.filter_map(|id| self.pending.remove(&id))
+			.filter(|pending| !matches!(pending.kind, RequestKind::Renew(_)))
+			.map(|pending| {
+				let message = "The endpoint dropped the connection before answering";
+				json!({ "jsonrpc": "2.0", "id": pending.request["id"], "error": { "code": PROXY_ERROR_CODE, "message": message } })
+					.to_string()
+			})
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod test_proxy {
+	use super::*;
+	use crate::{MemoryStorage, Registry};
+	use tokio::sync::oneshot;
+
+	#[test]
+	fn test_methods() {
+		assert!(is_subscription("chain_subscribeNewHeads"));
+		assert!(is_subscription("author_submitAndWatchExtrinsic"));
+		assert!(is_subscription("chainHead_v1_follow"));
+		assert!(!is_subscription("chain_unsubscribeNewHeads") && !is_subscription("system_name"));
+		assert!(is_unsubscription("chainHead_v1_unfollow") && is_unsubscription("state_unsubscribeStorage"));
+		assert!(!is_renewable("transactionWatch_v1_submitAndWatch") && !is_renewable("chainHead_v1_follow"));
+		assert!(is_renewable("state_subscribeStorage"));
+		assert!(is_submission("transaction_v1_broadcast") && is_submission("transaction_unstable_broadcast"));
+		assert!(is_submission("author_submitExtrinsic") && !is_submission("transaction_v1_stop"));
+	}
+
+	#[test]
+	fn test_session() {
+		let mut session = Session::default();
+		session.on_client(r#"{"jsonrpc":"2.0","id":1,"method":"chain_subscribeNewHeads","params":[]}"#);
+		session.on_client(r#"{"jsonrpc":"2.0","id":2,"method":"author_submitExtrinsic","params":["0x00"]}"#);
+		let (response, latencies) = session.on_upstream(r#"{"jsonrpc":"2.0","id":1,"result":"a"}"#);
+		assert_eq!((true, 1), (response.is_some(), latencies.len()));
+
+		let (replay, errors) = session.failover();
+		assert_eq!(1, errors.len(), "{errors:?}");
+		assert!(errors[0].contains(r#""id":2"#));
+		assert_eq!(1, replay.len(), "{replay:?}");
+		let renewal: Value = serde_json::from_str(&replay[0]).unwrap();
+		assert_eq!(("chain_subscribeNewHeads", true), (method(&renewal), renewal["id"].is_string()));
+
+		let renewed = json!({ "jsonrpc": "2.0", "id": renewal["id"], "result": "b" });
+		assert_eq!(None, session.on_upstream(&renewed.to_string()).0);
+		let notification = r#"{"jsonrpc":"2.0","method":"chain_newHead","params":{"subscription":"b","result":{}}}"#;
+		let notification: Value = serde_json::from_str(&session.on_upstream(notification).0.unwrap()).unwrap();
+		assert_eq!("a", notification["params"]["subscription"]);
+
+		let unsubscribe =
+			session.on_client(r#"{"jsonrpc":"2.0","id":3,"method":"chain_unsubscribeNewHeads","params":["a"]}"#);
+		assert!(unsubscribe.contains(r#""params":["b"]"#));
+		assert!(session.subscriptions.is_empty());
+	}
+
+	#[test]
+	fn test_follow_failover() {
+		let mut session = Session::default();
+		session.on_client(r#"{"jsonrpc":"2.0","id":1,"method":"chainHead_v1_follow","params":[true]}"#);
+		session.on_upstream(r#"{"jsonrpc":"2.0","id":1,"result":"f"}"#);
+		let header =
+			session.on_client(r#"{"jsonrpc":"2.0","id":2,"method":"chainHead_v1_header","params":["f","0x01"]}"#);
+		assert!(header.contains(r#""params":["f","0x01"]"#));
+		session.on_client(r#"{"jsonrpc":"2.0","id":3,"method":"transaction_v1_broadcast","params":["0x00"]}"#);
+		session.on_client(r#"{"jsonrpc":"2.0","id":4,"method":"system_name","params":[]}"#);
+
+		let (replay, mut errors) = session.failover();
+		errors.sort();
+		assert_eq!(2, errors.len(), "{errors:?}");
+		assert!(errors[0].contains(r#""id":2"#) && errors[1].contains(r#""id":3"#), "{errors:?}");
+		assert_eq!(1, replay.len(), "{replay:?}");
+		assert!(replay[0].contains("system_name"));
+		assert!(session.subscriptions.is_empty());
+	}
+
+	/// Node answering its port to the requests and notifying new heads, dropping the connection after `requests` requests
+	async fn node(requests: Option<usize>) -> SocketAddr {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		tokio::spawn(async move {
+			while let Ok((stream, _)) = listener.accept().await {
+				let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+				let mut count = 0;
+				while let Some(Ok(Message::Text(text))) = ws.next().await {
+					count += 1;
+					if requests.is_some_and(|n| count > n) {
+						break;
+					}
+					let request: Value = serde_json::from_str(&text).unwrap();
+					let subscription = format!("heads-{}", addr.port());
+					let result = if method(&request) == "chain_subscribeNewHeads" {
+						json!(subscription)
+					} else {
+						json!(addr.port())
+					};
+					let response = json!({ "jsonrpc": "2.0", "id": request["id"], "result": result });
+					ws.send(Message::text(response.to_string())).await.unwrap();
+					if result.is_string() {
+						let params = json!({ "subscription": subscription, "result": { "port": addr.port() } });
+						let notification = json!({ "jsonrpc": "2.0", "method": "chain_newHead", "params": params });
+						ws.send(Message::text(notification.to_string())).await.unwrap();
+					}
+				}
+			}
+		});
+		addr
+	}
+
+	async fn next_message(client: &mut WebSocketStream<MaybeTlsStream<TcpStream>>) -> Value {
+		match client.next().await {
+			Some(Ok(Message::Text(text))) => serde_json::from_str(&text).unwrap(),
+			other => panic!("Unexpected message {other:?}"),
+		}
+	}
+
+	#[tokio::test]
+	async fn test_failover() {
+		let (flaky, stable) = (node(Some(2)).await, node(None).await);
+		let mut reg = Registry::new("Test", "http://localhost/test.json");
+		let mut endpoints = vec![];
+		for (name, addr, latency) in [("Flaky", flaky, 0.1), ("Stable", stable, 1.0)] {
+			let mut endpoint = Endpoint::new(name, &format!("ws://{addr}"), vec![], vec![]);
+			(0..5).for_each(|_| endpoint.stats.add(true, Some(latency)));
+			endpoints.push(endpoint);
+		}
+		reg.rpc_endpoints = HashMap::from([("Polkadot".to_string(), endpoints)]);
+		let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+		LocalData { storage: Some(storage.clone()), ..Default::default() }.add_registry(reg).save().unwrap();
+
+		let proxy =
+			Proxy::new(storage.clone(), Config::default()).addr("127.0.0.1:0".parse().unwrap()).bind().await.unwrap();
+		let addr = proxy.local_addr().unwrap();
+		let (shutdown, stop) = oneshot::channel::<()>();
+		let running = tokio::spawn(proxy.run_until(stop));
+
+		let test = async {
+			assert!(tokio_tungstenite::connect_async(format!("ws://{addr}/kusama")).await.is_err());
+			assert!(tokio_tungstenite::connect_async(format!("ws://{addr}/polka")).await.is_err());
+			let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/polkadot")).await.unwrap();
+			let subscribe = json!({ "jsonrpc": "2.0", "id": 1, "method": "chain_subscribeNewHeads", "params": [] });
+			client.send(Message::text(subscribe.to_string())).await.unwrap();
+			let subscription = next_message(&mut client).await["result"].clone();
+			assert_eq!(json!(format!("heads-{}", flaky.port())), subscription);
+			assert_eq!(flaky.port(), next_message(&mut client).await["params"]["result"]["port"]);
+
+			let request = json!({ "jsonrpc": "2.0", "id": 2, "method": "system_name", "params": [] });
+			client.send(Message::text(request.to_string())).await.unwrap();
+			assert_eq!(flaky.port(), next_message(&mut client).await["result"]);
+
+			// The flaky node drops the connection: the request is answered by the stable one,
+			// whose notifications use the id of the subscription known to the client
+			let request = json!({ "jsonrpc": "2.0", "id": 3, "method": "system_name", "params": [] });
+			client.send(Message::text(request.to_string())).await.unwrap();
+			let (mut answered, mut notified) = (false, false);
+			while !(answered && notified) {
+				let message = next_message(&mut client).await;
+				if message["id"] == 3 {
+					assert_eq!(stable.port(), message["result"]);
+					answered = true;
+				} else {
+					assert_eq!(subscription, message["params"]["subscription"]);
+					assert_eq!(stable.port(), message["params"]["result"]["port"]);
+					notified = true;
+				}
+			}
+		};
+		tokio::time::timeout(Duration::from_secs(10), test).await.expect("The proxy should answer");
+		shutdown.send(()).unwrap();
+		running.await.unwrap().unwrap();
+
+		let data = storage.load().unwrap();
+		let endpoints = &data.registries["Test"].rpc_endpoints["Polkadot"];
+		// Each response counts: the flaky node answered 2 requests before failing once
+		let flaky = &endpoints[0].stats;
+		assert_eq!((7, 1), (flaky.success, flaky.failures));
+		assert!((flaky.availability().unwrap() - 7f32 / 8f32).abs() < 1e-3, "{:?}", flaky.availability());
+		assert_eq!((7, 0), (endpoints[1].stats.success, endpoints[1].stats.failures));
+	}
+
+	/// Node closing each connection right after accepting it, counting the connections
+	async fn closing_node(connections: Arc<std::sync::atomic::AtomicUsize>) -> SocketAddr {
+		let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		tokio::spawn(async move {
+			while let Ok((stream, _)) = listener.accept().await {
+				if let Ok(mut ws) = tokio_tungstenite::accept_async(stream).await {
+					connections.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+					let _ = ws.close(None).await;
+				}
+			}
+		});
+		addr
+	}
+
+	#[tokio::test]
+	async fn test_no_progress() {
+		let connections = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+		let mut reg = Registry::new("Test", "http://localhost/test.json");
+		let mut endpoints = vec![];
+		for name in ["First", "Second"] {
+			let addr = closing_node(connections.clone()).await;
+			endpoints.push(Endpoint::new(name, &format!("ws://{addr}"), vec![], vec![]));
+		}
+		reg.rpc_endpoints = HashMap::from([("Polkadot".to_string(), endpoints)]);
+		let storage: Arc<dyn Storage> = Arc::new(MemoryStorage::new());
+		LocalData { storage: Some(storage.clone()), ..Default::default() }.add_registry(reg).save().unwrap();
+
+		let proxy =
+			Proxy::new(storage.clone(), Config::default()).addr("127.0.0.1:0".parse().unwrap()).bind().await.unwrap();
+		let addr = proxy.local_addr().unwrap();
+		let (shutdown, stop) = oneshot::channel::<()>();
+		let running = tokio::spawn(proxy.run_until(stop));
+
+		// The proxy gives up after trying each endpoint once instead of reconnecting forever
+		let test = async {
+			let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{addr}/polkadot")).await.unwrap();
+			let request = json!({ "jsonrpc": "2.0", "id": 1, "method": "system_name", "params": [] });
+			let _ = client.send(Message::text(request.to_string())).await;
+			loop {
+				match client.next().await {
+					Some(Ok(Message::Close(Some(frame)))) => break frame.code,
+					Some(Ok(Message::Text(text))) => assert!(text.contains(&PROXY_ERROR_CODE.to_string()), "{text}"),
+					other => panic!("Unexpected message {other:?}"),
+				}
+			}
+		};
+		let code = tokio::time::timeout(Duration::from_secs(10), test).await.expect("The proxy should give up");
+		assert_eq!(CloseCode::Again, code);
+		assert_eq!(2, connections.load(std::sync::atomic::Ordering::SeqCst));
+		shutdown.send(()).unwrap();
+		running.await.unwrap().unwrap();
+	}
+}
@@ -0,0 +1,137 @@
+use crate::{resolver::normalize, CapabilityFilter, Endpoint, Protocol, Registry};
+
+/// Selection of endpoints, see [LocalData::query](crate::LocalData::query).
+///
+/// An empty query matches all the endpoints. Names and labels are compared ignoring the case.
+/// Chain names also ignore the punctuation, see [ChainResolver](crate::ChainResolver) to resolve aliases.
+/// ```
+/// use subrpc_core::{EndpointQuery, Protocol};
+///
+/// let query = EndpointQuery::new().chain("Polkadot").label("mainnet").exclude_label("testnet").protocol(Protocol::Wss);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct EndpointQuery {
+	/// Only endpoints of this chain
+	pub chain: Option<String>,
+
+	/// If not empty, only endpoints having at least one of those labels,
+	/// either on the endpoint or on its registry
+	pub include_labels: Vec<String>,
+
+	/// Endpoints having any of those labels, on the endpoint or on its registry, are left out
+	pub exclude_labels: Vec<String>,
+
+	/// If not empty, only endpoints whose name or registry name is one of those
+	pub providers: Vec<String>,
+
+	/// If not empty, only endpoints using one of those protocols
+	pub protocols: Vec<Protocol>,
+
+	/// Only endpoints whose [score](crate::EndpointStats::score) is at least this value
+	pub min_score: Option<f32>,
+
+	/// Capabilities required from the endpoints
+	pub capabilities: CapabilityFilter,
+}
+
+impl EndpointQuery {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Only endpoints of `chain`
+	pub fn chain(mut self, chain: &str) -> Self {
+		self.chain = Some(chain.to_string());
+		self
+	}
+
+	/// Only endpoints having `label`. Calling it several times accepts any of the labels.
+	pub fn label(mut self, label: &str) -> Self {
+		self.include_labels.push(label.to_string());
+		self
+	}
+
+	/// Leave out the endpoints having `label`
+	pub fn exclude_label(mut self, label: &str) -> Self {
+		self.exclude_labels.push(label.to_string());
+		self
+	}
+
+	/// Only endpoints named `provider` or coming from the registry named `provider`.
+	/// Calling it several times accepts any of the providers.
+	pub fn provider(mut self, provider: &str) -> Self {
+		self.providers.push(provider.to_string());
+		self
+	}
+
+	/// Only endpoints using `protocol`. Calling it several times accepts any of the protocols.
+	pub fn protocol(mut self, protocol: Protocol) -> Self {
+		self.protocols.push(protocol);
+		self
+	}
+
+	/// Only endpoints scoring at least `score`
+	pub fn min_score(mut self, score: f32) -> Self {
+		self.min_score = Some(score);
+		self
+	}
+
+	/// Only endpoints having the capabilities required by `filter`
+	pub fn capabilities(mut self, filter: CapabilityFilter) -> Self {
+		self.capabilities = filter;
+		self
+	}
+
+	/// Whether `endpoint`, listed for `chain` by `registry`, matches the query
+	pub fn matches(&self, chain: &str, registry: &Registry, endpoint: &Endpoint) -> bool {
+		let has_label = |wanted: &String| {
+			endpoint.labels().iter().chain(&registry.labels).any(|label| label.eq_ignore_ascii_case(wanted))
+		};
+
+		self.chain.as_ref().is_none_or(|c| normalize(c) == normalize(chain))
+			&& (self.include_labels.is_empty() || self.include_labels.iter().any(has_label))
+			&& !self.exclude_labels.iter().any(has_label)
+			&& (self.providers.is_empty()
+				|| self
+					.providers
+					.iter()
+					.any(|p| p.eq_ignore_ascii_case(&endpoint.name) || p.eq_ignore_ascii_case(&registry.name)))
+			&& (self.protocols.is_empty() || self.protocols.contains(&endpoint.url.protocol()))
+			&& self.min_score.is_none_or(|min| endpoint.stats.score() >= min)
+			&& self.capabilities.matches(endpoint)
+	}
+}
+
+#[cfg(test)]
+mod test_query {
+	use super::*;
+
+	#[test]
+	fn test_matches() {
+		let mut registry = Registry::new("Parity", "https://example.com/parity.json");
+		registry.labels = vec!["Mainnet".to_string()];
+		let mut endpoint = Endpoint::new("OnFinality", "wss://rpc.example.com", vec!["archive".to_string()], vec![]);
+		let matches = |query: EndpointQuery, endpoint: &Endpoint| query.matches("Polkadot", &registry, endpoint);
+
+		assert!(matches(EndpointQuery::new(), &endpoint));
+		assert!(matches(EndpointQuery::new().chain("polkadot"), &endpoint));
+		assert!(!matches(EndpointQuery::new().chain("Kusama"), &endpoint));
+		assert!(EndpointQuery::new().chain("asset-hub-polkadot").matches("Asset Hub Polkadot", &registry, &endpoint));
+
+		assert!(matches(EndpointQuery::new().label("mainnet"), &endpoint));
+		assert!(matches(EndpointQuery::new().label("testnet").label("ARCHIVE"), &endpoint));
+		assert!(!matches(EndpointQuery::new().label("testnet"), &endpoint));
+		assert!(!matches(EndpointQuery::new().exclude_label("mainnet"), &endpoint));
+
+		assert!(matches(EndpointQuery::new().provider("parity"), &endpoint));
+		assert!(matches(EndpointQuery::new().provider("onfinality"), &endpoint));
+		assert!(!matches(EndpointQuery::new().provider("Dwellir"), &endpoint));
+
+		assert!(matches(EndpointQuery::new().protocol(Protocol::Https).protocol(Protocol::Wss), &endpoint));
+		assert!(!matches(EndpointQuery::new().protocol(Protocol::Ws), &endpoint));
+
+		assert!(!matches(EndpointQuery::new().min_score(0.1), &endpoint));
+		endpoint.stats.add(true, Some(0.1));
+		assert!(matches(EndpointQuery::new().min_score(0.1), &endpoint));
+	}
+}
@@ -1,9 +1,10 @@
-use crate::{default_true, empty_string_array, ChainName, RegistryUrl};
-use crate::{endpoint::Endpoint, EndpointUrl};
-use anyhow::Result;
+use crate::{capabilities, health, Capabilities, EndpointStats, Health, NodeHealth};
+use crate::{default_true, empty_string_array, Chain, ChainName, IdentityMismatch, RegistryUrl};
+use crate::{endpoint::Endpoint, lint, rpc::RpcClient, EndpointUrl, Error, NetworkOptions, ParseMode, Result};
 use chrono::{DateTime, Local};
-use jsonrpsee::{core::client::ClientT, http_client::HttpClientBuilder, rpc_params, ws_client::WsClientBuilder};
+use futures::{stream, StreamExt};
 use log::*;
+use reqwest::{header, StatusCode};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fmt::Display;
@@ -11,12 +12,126 @@ use std::time::Instant;
 use std::{
 	fs::File,
 	io::{Read, Write},
-	path::PathBuf,
+	path::{Path, PathBuf},
 };
-use tokio::runtime::Runtime;
+
+/// Latest version of the registry schema, see [REGISTRY_JSON_SCHEMA](crate::REGISTRY_JSON_SCHEMA)
+pub const REGISTRY_SCHEMA_VERSION: u32 = 1;
+
+fn default_schema_version() -> u32 {
+	REGISTRY_SCHEMA_VERSION
+}
+
+/// An endpoint reported in a [MergeReport]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EndpointChange {
+	pub chain: ChainName,
+	pub name: String,
+	pub url: EndpointUrl,
+}
+
+impl EndpointChange {
+	fn new(chain: &str, endpoint: &Endpoint) -> Self {
+		Self { chain: chain.to_string(), name: endpoint.name.clone(), url: endpoint.url.clone() }
+	}
+}
+
+impl Display for EndpointChange {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} - {} {}", self.chain, self.name, self.url)
+	}
+}
+
+/// Changes to the endpoints of a registry, see [Registry::merge]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct MergeReport {
+	/// Endpoints that are new, or that came back after being removed
+	pub added: Vec<EndpointChange>,
+
+	/// Endpoints that vanished from the registry
+	pub removed: Vec<EndpointChange>,
+
+	/// Endpoints whose name, labels or aliases changed
+	pub changed: Vec<EndpointChange>,
+}
+
+impl MergeReport {
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+	}
+}
+
+impl Display for MergeReport {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{} added, {} removed, {} changed", self.added.len(), self.removed.len(), self.changed.len())
+	}
+}
+
+/// Result of a successful ping, see [Registry::ping_chain]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PingResult {
+	/// Latency in seconds
+	pub latency: f32,
+
+	/// Name of the chain returned by `system_chain`
+	pub system_chain: String,
+
+	/// Hash of the block 0, only queried when the chain declares a genesis hash
+	pub genesis_hash: Option<String>,
+
+	/// Set when the endpoint serves another chain than the expected one
+	pub mismatch: Option<IdentityMismatch>,
+
+	/// Health of the node, when [NetworkOptions::health_probe] is enabled and the node answered the probe
+	pub health: Option<NodeHealth>,
+}
+
+/// Result of the last [Registry::update]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UpdateOutcome {
+	/// The registry was fetched and its endpoints changed
+	Updated(MergeReport),
+
+	/// The registry was fetched but its endpoints did not change
+	Unchanged,
+
+	/// The server answered 304 Not Modified, nothing was downloaded
+	NotModified,
+
+	/// The registry is disabled or has no url
+	Skipped,
+
+	/// The update failed
+	Failed(String),
+}
+
+impl UpdateOutcome {
+	/// Whether the endpoints of the registry changed
+	pub fn changed(&self) -> bool {
+		matches!(self, UpdateOutcome::Updated(_))
+	}
+}
+
+impl Display for UpdateOutcome {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			UpdateOutcome::Updated(report) => write!(f, "updated: {report}"),
+			UpdateOutcome::Unchanged => f.write_str("unchanged"),
+			UpdateOutcome::NotModified => f.write_str("not modified"),
+			UpdateOutcome::Skipped => f.write_str("skipped"),
+			UpdateOutcome::Failed(e) => write!(f, "failed: {e}"),
+		}
+	}
+}
 
 #[derive(Eq, Debug, Deserialize, Serialize)]
 pub struct Registry {
+	/// Version of the schema of the registry. Registries without version are considered
+	/// to use the current version.
+	#[serde(default = "default_schema_version")]
+	pub schema_version: u32,
+
 	/// Data won't be pulled from a disabled registry
 	#[serde(default = "default_true")]
 	pub enabled: bool,
@@ -32,10 +147,37 @@ pub struct Registry {
 	pub labels: Vec<String>,
 
 	/// DateTime of the last update of the data
+	#[serde(alias = "last_updated")]
 	pub last_update: Option<DateTime<Local>>,
 
 	/// Items of the registry
 	pub rpc_endpoints: HashMap<ChainName, Vec<Endpoint>>,
+
+	/// Optional description of the chains, indexed by the names used in [Self::rpc_endpoints].
+	/// Their identity is used to check that the endpoints serve the right chain.
+	#[serde(default, skip_serializing_if = "HashMap::is_empty")]
+	pub chains: HashMap<ChainName, Chain>,
+
+	/// `ETag` returned by the server on the last download, sent back as `If-None-Match`
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub etag: Option<String>,
+
+	/// `Last-Modified` returned by the server on the last download, sent back as `If-Modified-Since`
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub last_modified: Option<String>,
+
+	/// Outcome of the last update, not persisted
+	#[serde(skip)]
+	pub last_outcome: Option<UpdateOutcome>,
+}
+
+/// Mark an endpoint as removed, reporting it unless it was already removed.
+fn mark_removed(mut endpoint: Endpoint, chain: &str, now: DateTime<Local>, report: &mut MergeReport) -> Endpoint {
+	if !endpoint.is_removed() {
+		report.removed.push(EndpointChange::new(chain, &endpoint));
+		endpoint.removed = Some(now);
+	}
+	endpoint
 }
 
 impl PartialEq for Registry {
@@ -47,25 +189,49 @@ impl PartialEq for Registry {
 impl Registry {
 	pub fn new(name: &str, url: &str) -> Self {
 		Self {
+			schema_version: REGISTRY_SCHEMA_VERSION,
 			name: name.to_string(),
 			url: Some(url.to_string()),
 			rpc_endpoints: HashMap::new(),
+			chains: HashMap::new(),
 			enabled: true,
 			last_update: None,
 			labels: vec![],
+			etag: None,
+			last_modified: None,
+			last_outcome: None,
 		}
 	}
 
-	/// Fetch the information from located at the registry's url and update the registry items
-	pub fn update(&mut self) -> Result<()> {
+	/// Fetch the information from located at the registry's url and update the registry items.
+	///
+	/// The request is conditional when the registry was downloaded before: if the server answers
+	/// `304 Not Modified`, the registry items are left untouched.
+	pub async fn update(&mut self) -> Result<UpdateOutcome> {
+		self.update_with(&NetworkOptions::default()).await
+	}
+
+	/// Same as [Self::update] but using the given [NetworkOptions].
+	///
+	/// The outcome is also stored in [Self::last_outcome].
+	pub async fn update_with(&mut self, opts: &NetworkOptions) -> Result<UpdateOutcome> {
+		let res = self.fetch(opts).await;
+		self.last_outcome = Some(match &res {
+			Ok(outcome) => outcome.clone(),
+			Err(e) => UpdateOutcome::Failed(e.to_string()),
+		});
+		res
+	}
+
+	async fn fetch(&mut self, opts: &NetworkOptions) -> Result<UpdateOutcome> {
 		if !self.enabled {
 			warn!("Registry is disabled, skipping...");
-			return Ok(());
+			return Ok(UpdateOutcome::Skipped);
 		}
 
 		if self.url.is_none() {
 			warn!("Registry '{}' has no URL, skipping...", self.name);
-			return Ok(());
+			return Ok(UpdateOutcome::Skipped);
 		}
 
 		// reg.items.iter().for_each(|(name, endpoints)| {
@@ -76,72 +242,302 @@ impl Registry {
 		// });
 
 		if let Some(registry_url) = &self.url {
-			let reg = reqwest::blocking::get(registry_url)?.json::<Registry>()?;
+			let client = reqwest::Client::builder().timeout(opts.timeout).build()?;
+			let mut request = client.get(registry_url);
+			// Without items, there is nothing to keep so we always download the registry
+			if !self.rpc_endpoints.is_empty() {
+				if let Some(etag) = &self.etag {
+					request = request.header(header::IF_NONE_MATCH, etag);
+				}
+				if let Some(last_modified) = &self.last_modified {
+					request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+				}
+			}
+
+			let response = request.send().await?;
+			if response.status() == StatusCode::NOT_MODIFIED {
+				debug!("Registry '{}' not modified", self.name);
+				return Ok(UpdateOutcome::NotModified);
+			}
+			let response = response.error_for_status()?;
+
+			let validator = |name| response.headers().get(name).and_then(|v| v.to_str().ok()).map(String::from);
+			let etag = validator(header::ETAG);
+			let last_modified = validator(header::LAST_MODIFIED);
+
+			let json = response.text().await?;
+			let reg = Registry::parse(&json, ParseMode::Lenient)?;
+			self.etag = etag;
+			self.last_modified = last_modified;
+			self.chains = reg.chains;
 
-			self.rpc_endpoints = reg.rpc_endpoints;
+			let report = self.merge(reg.rpc_endpoints);
 			debug!("Found {:?} items", self.rpc_endpoints.len());
+			if report.is_empty() {
+				debug!("Registry '{}' unchanged", self.name);
+				Ok(UpdateOutcome::Unchanged)
+			} else {
+				Ok(UpdateOutcome::Updated(report))
+			}
 		} else {
 			log::warn!("No URL, skipping...");
+			Ok(UpdateOutcome::Skipped)
 		}
+	}
 
-		Ok(())
+	/// Merge freshly downloaded endpoints into the registry.
+	///
+	/// Endpoints are matched by url within each chain. Matching endpoints take the name, labels,
+	/// aliases and auth from upstream while keeping their local stats, label overrides and notes.
+	/// New endpoints start without any of that local state, whatever upstream sent.
+	/// Endpoints that vanished upstream are kept and marked as removed.
+	pub fn merge(&mut self, upstream: HashMap<ChainName, Vec<Endpoint>>) -> MergeReport {
+		let mut report = MergeReport::default();
+		let now = Local::now();
+		let mut local = std::mem::take(&mut self.rpc_endpoints);
+
+		for (chain, endpoints) in upstream {
+			let mut previous: Vec<Option<Endpoint>> =
+				local.remove(&chain).unwrap_or_default().into_iter().map(Some).collect();
+
+			let mut merged: Vec<Endpoint> = endpoints
+				.into_iter()
+				.map(|fresh| {
+					let Some(mut endpoint) = previous
+						.iter_mut()
+						.find(|e| e.as_ref().is_some_and(|e| e.url == fresh.url))
+						.and_then(Option::take)
+					else {
+						report.added.push(EndpointChange::new(&chain, &fresh));
+						return Endpoint {
+							stats: EndpointStats::default(),
+							label_overrides: None,
+							notes: None,
+							removed: None,
+							..fresh
+						};
+					};
+
+					if endpoint.is_removed() {
+						report.added.push(EndpointChange::new(&chain, &fresh));
+					} else if endpoint.name != fresh.name
+						|| endpoint.labels != fresh.labels
+						|| endpoint.aliases != fresh.aliases
+						|| endpoint.auth != fresh.auth
+					{
+						report.changed.push(EndpointChange::new(&chain, &fresh));
+					}
+					endpoint.name = fresh.name;
+					endpoint.labels = fresh.labels;
+					endpoint.aliases = fresh.aliases;
+					endpoint.auth = fresh.auth;
+					endpoint.removed = None;
+					endpoint
+				})
+				.collect();
+
+			merged.extend(previous.into_iter().flatten().map(|e| mark_removed(e, &chain, now, &mut report)));
+			self.rpc_endpoints.insert(chain, merged);
+		}
+
+		// Chains that vanished upstream
+		for (chain, endpoints) in local {
+			let endpoints = endpoints.into_iter().map(|e| mark_removed(e, &chain, now, &mut report)).collect();
+			self.rpc_endpoints.insert(chain, endpoints);
+		}
+
+		report
+	}
+
+	/// The chain listed as `name` in [Self::rpc_endpoints], as described by [Self::chains].
+	/// Its id defaults to the [normalized](crate::resolver::normalize) name and its display name to `name`.
+	pub fn chain(&self, name: &str) -> Chain {
+		let defaults = Chain::named(name);
+		match self.chains.get(name) {
+			Some(chain) => Chain {
+				id: if chain.id.is_empty() { defaults.id } else { chain.id.clone() },
+				name: if chain.name.is_empty() { defaults.name } else { chain.name.clone() },
+				..chain.clone()
+			},
+			None => defaults,
+		}
+	}
+
+	/// Iterate over all endpoints that are not removed, sorted by chain name so the order is stable.
+	pub(crate) fn endpoints(&self) -> impl Iterator<Item = &Endpoint> {
+		self.chain_endpoints().map(|(_, e)| e)
+	}
+
+	/// Same as [Self::endpoints], along with the [Chain] of their chain if the registry has one.
+	pub(crate) fn chain_endpoints(&self) -> impl Iterator<Item = (Option<&Chain>, &Endpoint)> {
+		let mut chains: Vec<_> = self.rpc_endpoints.iter().collect();
+		chains.sort_by(|a, b| a.0.cmp(b.0));
+		chains.into_iter().flat_map(|(chain, endpoints)| {
+			let info = self.chains.get(chain);
+			endpoints.iter().filter(|e| !e.is_removed()).map(move |e| (info, e))
+		})
+	}
+
+	/// Mutable version of [Self::endpoints], yielding the endpoints in the same order along with their chain.
+	pub(crate) fn endpoints_mut(&mut self) -> impl Iterator<Item = (&ChainName, &mut Endpoint)> {
+		let mut chains: Vec<_> = self.rpc_endpoints.iter_mut().collect();
+		chains.sort_by(|a, b| a.0.cmp(b.0));
+		chains
+			.into_iter()
+			.flat_map(|(chain, endpoints)| endpoints.iter_mut().filter(|e| !e.is_removed()).map(move |e| (&*chain, e)))
 	}
 
 	/// Ping all endpoints and refresh the stats
-	pub fn refresh_stats(&mut self) {
-		self.rpc_endpoints.iter_mut().for_each(|(_name, endpoints)| {
-			endpoints.iter_mut().for_each(|endpoint| {
-				let (success, latency) = Self::ping(endpoint).unwrap_or((false, None));
-				let stats = &mut endpoint.stats;
-				stats.add(success, latency)
+	pub async fn refresh_stats(&mut self) {
+		self.refresh_stats_with(&NetworkOptions::default()).await
+	}
+
+	/// Same as [Self::refresh_stats] but using the given [NetworkOptions].
+	/// The endpoints are pinged concurrently.
+	pub async fn refresh_stats_with(&mut self, opts: &NetworkOptions) {
+		let mut keys: Vec<_> = self.rpc_endpoints.keys().collect();
+		keys.sort();
+		let chains: Vec<Chain> = keys.into_iter().map(|key| self.chain(key)).collect();
+		let ids = self.chain_ids(&chains);
+
+		let results = ping_endpoints(self.chain_endpoints(), opts).await;
+		record_pings(self.endpoints_mut().map(|(key, e)| (ids[key].clone(), e)), results)
+	}
+
+	/// Identity of the chain of each key: the id of the first of `chains` that is [the same](Chain::is_same),
+	/// the id of its own chain if none is.
+	pub(crate) fn chain_ids(&self, chains: &[Chain]) -> HashMap<ChainName, String> {
+		self.rpc_endpoints
+			.keys()
+			.map(|key| {
+				let chain = self.chain(key);
+				let id = chains.iter().find(|c| c.is_same(&chain)).map_or(chain.id, |c| c.id.clone());
+				(key.clone(), id)
 			})
-		})
+			.collect()
+	}
+
+	/// Probe the [Capabilities] of all endpoints and store them in their stats.
+	/// Endpoints failing the probe keep their previous capabilities.
+	pub async fn refresh_capabilities(&mut self, opts: &NetworkOptions) {
+		let results = probe_endpoints(self.endpoints(), opts).await;
+		record_probes(self.endpoints_mut().map(|(_, e)| e), results)
 	}
 
 	/// Ping all endpoints and print the results to stdout.
 	///
 	/// Calling this function does NOT refresh the stats.
-	pub fn ping_all(&mut self) {
-		self.rpc_endpoints.iter_mut().for_each(|(_name, endpoints)| {
-			endpoints.iter_mut().for_each(|endpoint| match Self::ping(endpoint) {
-				Ok((success, latency)) => {
-					if success {
-						print!("✅ {:0.3}s", latency.unwrap_or(0f32));
-					} else {
-						print!("{: <8}", "❌");
+	pub async fn ping_all(&mut self) {
+		let results = ping_endpoints(self.chain_endpoints(), &NetworkOptions::default()).await;
+		for (endpoint, res) in self.endpoints().zip(results) {
+			match res {
+				Ok(Some(ping)) => {
+					print!("✅ {:0.3}s", ping.latency);
+					print!(" - {:<20} {}", endpoint.name, endpoint.url);
+					match ping.mismatch {
+						Some(mismatch) => println!(" ⚠️  wrong chain: {mismatch}"),
+						None => println!(),
 					}
+				}
+				Ok(None) => {
+					print!("{: <8}", "❌");
 					println!(" - {:<20} {}", endpoint.name, endpoint.url);
 				}
 				Err(e) => {
 					eprint!("{: <8}", "❌");
 					eprintln!("{}: {e}", endpoint.url);
 				}
-			})
+			}
+		}
+	}
+
+	pub async fn ping(e: &Endpoint) -> Result<(bool, Option<f32>)> {
+		Self::ping_with(e, &NetworkOptions::default()).await
+	}
+
+	/// Same as [Self::ping] but using the given [NetworkOptions].
+	/// An endpoint that does not answer within the timeout is reported as failed.
+	pub async fn ping_with(e: &Endpoint, opts: &NetworkOptions) -> Result<(bool, Option<f32>)> {
+		Ok(match Self::ping_chain(e, None, opts).await? {
+			Some(ping) => (true, Some(ping.latency)),
+			None => (false, None),
 		})
 	}
 
-	pub fn ping(e: &Endpoint) -> Result<(bool, Option<f32>)> {
+	/// Ping an endpoint and check that it serves the expected `chain`.
+	///
+	/// The endpoint is asked for `system_chain` and, if the chain declares a genesis hash,
+	/// for `chain_getBlockHash(0)`. With [NetworkOptions::health_probe], the health of the node is
+	/// probed as well: a node failing the probe is still reported as up, without health.
+	/// Returns `None` if the endpoint does not answer within the timeout.
+	pub async fn ping_chain(e: &Endpoint, chain: Option<&Chain>, opts: &NetworkOptions) -> Result<Option<PingResult>> {
 		debug!("pinging endpoint {} at {}", e.name, e.url);
-		let rt = Runtime::new().unwrap();
 		let start = Instant::now();
 
-		let response: Result<String> = match &e.url {
-			EndpointUrl::Https(url) | EndpointUrl::Http(url) => {
-				trace!("Detected HTTP/S");
-				let client = HttpClientBuilder::default().build(url)?;
-				rt.block_on(client.request("system_chain", rpc_params![])).map_err(anyhow::Error::msg)
+		let url = e.resolved_url(&opts.secrets)?;
+		let headers = e.headers(&opts.secrets)?;
+		let request = async {
+			let mut client = RpcClient::connect(&url, &headers, opts.timeout).await?;
+			let system_chain = client.request::<String>("system_chain", vec![]).await?;
+			let latency = start.elapsed().as_millis() as f32 / 1000f32;
+			let genesis_hash = match chain {
+				Some(chain) if chain.needs_genesis() => {
+					Some(client.request::<String>("chain_getBlockHash", vec![0.into()]).await?)
+				}
+				_ => None,
+			};
+			Ok::<_, Error>((client, latency, system_chain, genesis_hash))
+		};
+
+		let (mut client, latency, system_chain, genesis_hash) = match tokio::time::timeout(opts.timeout, request).await
+		{
+			Ok(response) => response?,
+			Err(_) => {
+				debug!("ping of {} timed out after {:?}", e.url, opts.timeout);
+				return Ok(None);
 			}
-			EndpointUrl::Wss(url) | EndpointUrl::Ws(url) => {
-				trace!("Detected WS/S");
-				let client = rt.block_on(WsClientBuilder::default().build(url))?;
-				rt.block_on(client.request("system_chain", rpc_params![])).map_err(anyhow::Error::msg)
+		};
+
+		// The probe has its own timeout so that a slow probe does not fail the ping
+		let health = if opts.health_probe {
+			match tokio::time::timeout(opts.timeout, health::probe(&mut client)).await {
+				Ok(health) => health.inspect_err(|err| debug!("health probe of {} failed: {err}", e.url)).ok(),
+				Err(_) => {
+					debug!("health probe of {} timed out after {:?}", e.url, opts.timeout);
+					None
+				}
 			}
+		} else {
+			None
+		};
+		debug!("response = {system_chain:?}, genesis = {genesis_hash:?}");
+
+		let mismatch =
+			chain.and_then(|chain| chain.check(&system_chain, genesis_hash.as_deref())).map(|(expected, found)| {
+				warn!("Endpoint {} at {} serves another chain: expected {expected}, found {found}", e.name, e.url);
+				IdentityMismatch { time: Local::now(), expected, found }
+			});
+		Ok(Some(PingResult { latency, system_chain, genesis_hash, mismatch, health }))
+	}
+
+	/// Query the runtime version, the RPC methods and the archive support of an endpoint.
+	/// Returns `None` if the endpoint does not answer within the timeout.
+	pub async fn probe(e: &Endpoint, opts: &NetworkOptions) -> Result<Option<Capabilities>> {
+		debug!("probing endpoint {} at {}", e.name, e.url);
+		let url = e.resolved_url(&opts.secrets)?;
+		let headers = e.headers(&opts.secrets)?;
+		let request = async {
+			let mut client = RpcClient::connect(&url, &headers, opts.timeout).await?;
+			capabilities::probe(&mut client).await
 		};
-		debug!("response = {:?}", response);
-		let duration = start.elapsed().as_millis() as f32 / 1000f32;
-		let success = response.is_ok();
-		rt.shutdown_background();
-		Ok((success, Some(duration)))
+
+		match tokio::time::timeout(opts.timeout, request).await {
+			Ok(capabilities) => Ok(Some(capabilities?)),
+			Err(_) => {
+				debug!("probe of {} timed out after {:?}", e.url, opts.timeout);
+				Ok(None)
+			}
+		}
 	}
 
 	pub fn save(&self, file: PathBuf) -> Result<()> {
@@ -151,16 +547,44 @@ impl Registry {
 		Ok(())
 	}
 
+	#[deprecated(note = "Use Registry::try_load, which does not panic")]
 	pub fn load(file: PathBuf) -> Self {
-		let mut fs = File::open(file).expect("File should be valid");
+		Self::try_load(&file).expect("Registry should be valid")
+	}
+
+	/// Load a registry from a file, see [Self::parse].
+	pub fn try_load(file: &Path) -> Result<Self> {
+		let mut fs = File::open(file)?;
 		let mut s = String::new();
-		fs.read_to_string(&mut s).expect("Fail reading registry");
-		serde_json::from_str(&s).expect("Format should be valid")
+		fs.read_to_string(&mut s)?;
+		Self::parse(&s, ParseMode::Lenient)
 	}
 
-	pub fn load_from_url(url: &str) -> Result<Self> {
+	pub async fn load_from_url(url: &str) -> Result<Self> {
 		info!("Adding registry from {url}");
-		reqwest::blocking::get(url)?.json::<Registry>().map_err(anyhow::Error::msg)
+		let json = reqwest::get(url).await?.error_for_status()?.text().await?;
+		Self::parse(&json, ParseMode::Lenient)
+	}
+
+	/// Parse a registry from its JSON representation.
+	///
+	/// In [ParseMode::Strict], any issue reported by [lint::lint] is an error. In [ParseMode::Lenient],
+	/// unknown fields are ignored while malformed dates and invalid endpoints are dropped with a warning.
+	pub fn parse(json: &str, mode: ParseMode) -> Result<Self> {
+		match mode {
+			ParseMode::Strict => {
+				let issues = lint::lint(json);
+				if !issues.is_empty() {
+					return Err(Error::Schema(issues));
+				}
+				Ok(serde_json::from_str(json)?)
+			}
+			ParseMode::Lenient => {
+				let mut value: serde_json::Value = serde_json::from_str(json)?;
+				lint::sanitize(&mut value);
+				Ok(serde_json::from_value(value)?)
+			}
+		}
 	}
 
 	pub fn default_bad() -> Self {
@@ -195,6 +619,76 @@ impl Registry {
 	}
 }
 
+/// Ping the given endpoints concurrently, with at most [NetworkOptions::concurrency]
+/// requests in flight. The results are returned in the same order as the endpoints.
+pub(crate) async fn ping_endpoints<'a>(
+	endpoints: impl Iterator<Item = (Option<&'a Chain>, &'a Endpoint)>,
+	opts: &NetworkOptions,
+) -> Vec<Result<Option<PingResult>>> {
+	stream::iter(endpoints)
+		.map(|(chain, e)| Registry::ping_chain(e, chain, opts))
+		.buffered(opts.limit())
+		.collect()
+		.await
+}
+
+/// Probe the capabilities of the given endpoints concurrently, see [ping_endpoints].
+pub(crate) async fn probe_endpoints<'a>(
+	endpoints: impl Iterator<Item = &'a Endpoint>,
+	opts: &NetworkOptions,
+) -> Vec<Result<Option<Capabilities>>> {
+	stream::iter(endpoints).map(|e| Registry::probe(e, opts)).buffered(opts.limit()).collect().await
+}
+
+/// Record the results of [probe_endpoints] in the stats of the endpoints
+pub(crate) fn record_probes<'a>(
+	endpoints: impl Iterator<Item = &'a mut Endpoint>,
+	results: Vec<Result<Option<Capabilities>>>,
+) {
+	for (endpoint, res) in endpoints.zip(results) {
+		match res {
+			Ok(Some(capabilities)) => endpoint.stats.capabilities = Some(capabilities),
+			Ok(None) => warn!("Probe of {} timed out", endpoint.url),
+			Err(e) => warn!("Probe of {} failed: {e}", endpoint.url),
+		}
+	}
+}
+
+/// Record the results of pings, as returned by [ping_endpoints], in the stats of the endpoints.
+///
+/// An endpoint serving another chain still answered: the ping counts as a success
+/// but the mismatch is kept until a later ping finds the right chain.
+/// The lag of each endpoint is computed against the best block of the other endpoints of the same chain,
+/// given by its id as returned by [Registry::chain_ids], leaving out the endpoints serving another chain.
+pub(crate) fn record_pings<'a>(
+	endpoints: impl Iterator<Item = (String, &'a mut Endpoint)>,
+	results: Vec<Result<Option<PingResult>>>,
+) {
+	let endpoints: Vec<_> = endpoints.collect();
+	let best = health::best_blocks(endpoints.iter().zip(&results).map(|((id, _), res)| {
+		let health = match res {
+			Ok(Some(ping)) if ping.mismatch.is_none() => ping.health.as_ref(),
+			_ => None,
+		};
+		(id.as_str(), health)
+	}));
+
+	let now = Local::now();
+	for ((id, endpoint), res) in endpoints.into_iter().zip(results) {
+		match res {
+			Ok(Some(ping)) => {
+				endpoint.stats.add(true, Some(ping.latency));
+				endpoint.stats.mismatch = ping.mismatch;
+				if let Some(node) = ping.health {
+					let best = best.get(&id).copied().unwrap_or(node.best_block);
+					endpoint.stats.health = Some(Health::new(&node, best.saturating_sub(node.best_block), now));
+				}
+			}
+			Ok(None) | Err(_) => endpoint.stats.add(false, None),
+		}
+	}
+}
+
 impl Default for Registry {
 	fn default() -> Self {
 		let rpc_endpoints = HashMap::from([
@@ -217,12 +711,17 @@ impl Default for Registry {
 		]);
 
 		Self {
+			schema_version: REGISTRY_SCHEMA_VERSION,
 			name: "SubRPC Default".to_string(),
 			url: None,
 			rpc_endpoints,
+			chains: HashMap::new(),
 			enabled: true,
 			last_update: None,
 			labels: vec![],
+			etag: None,
+			last_modified: None,
+			last_outcome: None,
 		}
 	}
 }
@@ -253,8 +752,126 @@ impl Display for Registry {
 #[cfg(test)]
 mod test_super {
 	use std::{env, path::Path};
+	use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 	use super::*;
+	use crate::Auth;
+
+	/// Serve `body` over http, answering `304 Not Modified` when the request carries the given `etag`.
+	async fn serve_registry(body: &'static str, etag: &'static str) -> String {
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let addr = listener.local_addr().unwrap();
+		tokio::spawn(async move {
+			loop {
+				let (mut socket, _) = listener.accept().await.unwrap();
+				let mut buf = vec![0u8; 4096];
+				let n = socket.read(&mut buf).await.unwrap();
+				let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+				let response = if request.contains(&format!("if-none-match: {etag}")) {
+					"HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\r\n".to_string()
+				} else {
+					format!(
+						"HTTP/1.1 200 OK\r\nETag: {etag}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+						body.len()
+					)
+				};
+				socket.write_all(response.as_bytes()).await.unwrap();
+			}
+		});
+		format!("http://{addr}/registry.json")
+	}
+
+	#[test]
+	fn test_merge() {
+		let mut reg = Registry::new("Test", "http://localhost");
+		let mut kept = Endpoint::new("Kept", "wss://kept", vec![], vec![]);
+		kept.stats.add(true, Some(0.1));
+		kept.notes = Some("my favorite".to_string());
+		kept.label_overrides = Some(vec!["fast".to_string()]);
+		reg.rpc_endpoints = HashMap::from([
+			(
+				"Polkadot".to_string(),
+				vec![
+					kept,
+					Endpoint::new("Gone", "wss://gone", vec![], vec![]),
+					Endpoint::new("Same", "wss://same", vec![], vec![]),
+				],
+			),
+			("Kusama".to_string(), vec![Endpoint::new("Old chain", "wss://kusama", vec![], vec![])]),
+		]);
+
+		// Local state sent by upstream is ignored for the new endpoints
+		let mut new = Endpoint::new("New", "wss://new", vec![], vec![]);
+		(0..100).for_each(|_| new.stats.add(true, Some(0.001)));
+		new.removed = Some(Local::now());
+		let upstream = HashMap::from([(
+			"Polkadot".to_string(),
+			vec![
+				Endpoint::new("Renamed", "wss://kept", vec!["Parity".to_string()], vec![]),
+				Endpoint::new("Same", "wss://same", vec![], vec![]),
+				new,
+			],
+		)]);
+		let mut with_auth = upstream.clone();
+		let auth = Auth::Header { name: "X-Api-Key".to_string(), value: "{API_KEY}".to_string() };
+		with_auth.get_mut("Polkadot").unwrap()[1].auth = Some(auth.clone());
+		let report = reg.merge(upstream.clone());
+		let urls = |changes: &[EndpointChange]| changes.iter().map(|c| c.url.to_string()).collect::<Vec<_>>();
+		assert_eq!(vec!["wss://new"], urls(&report.added));
+		assert_eq!(vec!["wss://kept"], urls(&report.changed));
+		let mut removed = urls(&report.removed);
+		removed.sort();
+		assert_eq!(vec!["wss://gone", "wss://kusama"], removed);
+
+		let polkadot = &reg.rpc_endpoints["Polkadot"];
+		assert_eq!(4, polkadot.len());
+		let kept = polkadot.iter().find(|e| e.url.to_string() == "wss://kept").unwrap();
+		assert_eq!("Renamed", kept.name);
+		assert_eq!(1, kept.stats.success);
+		assert_eq!(Some("my favorite"), kept.notes.as_deref());
+		assert_eq!(vec!["fast"], kept.labels());
+		assert!(polkadot.iter().find(|e| e.url.to_string() == "wss://gone").unwrap().is_removed());
+		let new = polkadot.iter().find(|e| e.url.to_string() == "wss://new").unwrap();
+		assert_eq!((0, false), (new.stats.success, new.is_removed()));
+		assert_eq!(3, reg.endpoints().count());
+
+		// Removed endpoints are only reported once
+		assert!(reg.merge(upstream).is_empty());
+
+		// The auth of an existing endpoint follows upstream
+		let report = reg.merge(with_auth);
+		assert_eq!(vec!["wss://same"], urls(&report.changed));
+		let same = reg.rpc_endpoints["Polkadot"].iter().find(|e| e.url.to_string() == "wss://same").unwrap();
+		assert_eq!(Some(auth), same.auth);
+
+		// and they are added back if they show up again
+		let report = reg.merge(HashMap::from([(
+			"Kusama".to_string(),
+			vec![Endpoint::new("Old chain", "wss://kusama", vec![], vec![])],
+		)]));
+		assert_eq!(vec!["wss://kusama"], urls(&report.added));
+		assert_eq!(3, report.removed.len());
+		assert!(!reg.rpc_endpoints["Kusama"][0].is_removed());
+	}
+
+	#[tokio::test]
+	async fn test_conditional_update() {
+		let url = serve_registry(include_str!("../../registry/sample1.json"), "\"v1\"").await;
+		let mut reg = Registry::new("Test", &url);
+
+		assert!(reg.update().await.unwrap().changed());
+		assert_eq!(Some("\"v1\""), reg.etag.as_deref());
+		let endpoints = reg.rpc_endpoints.len();
+		assert!(endpoints > 0);
+
+		assert_eq!(UpdateOutcome::NotModified, reg.update().await.unwrap());
+		assert_eq!(Some(UpdateOutcome::NotModified), reg.last_outcome);
+		assert_eq!(endpoints, reg.rpc_endpoints.len());
+
+		reg.etag = None;
+		assert_eq!(UpdateOutcome::Unchanged, reg.update().await.unwrap());
+		assert!(!reg.last_outcome.unwrap().changed());
+	}
 
 	#[test]
 	fn test_default() {
@@ -264,31 +881,236 @@ mod test_super {
 	}
 
 	#[test]
-	fn test_refresh_stats() {
+	fn test_chain() {
+		let mut reg = Registry::new("Parity", "http://localhost/parity.json");
+		reg.chains = HashMap::from([(
+			"Statemint".to_string(),
+			Chain { name: "Asset Hub Polkadot".to_string(), para_id: Some(1000), ..Default::default() },
+		)]);
+
+		let statemint = reg.chain("Statemint");
+		assert_eq!(("statemint", "Asset Hub Polkadot"), (statemint.id.as_str(), statemint.name.as_str()));
+		assert!(statemint.is_parachain());
+		assert_eq!(Chain::named("Polkadot"), reg.chain("Polkadot"));
+	}
+
+	#[tokio::test]
+	async fn test_refresh_stats() {
 		let mut reg1 = Registry::default();
-		reg1.refresh_stats();
+		reg1.refresh_stats().await;
 		println!("{}", &reg1);
-		reg1.refresh_stats();
+		reg1.refresh_stats().await;
 		println!("{}", &reg1);
 	}
 
+	#[tokio::test]
+	async fn test_refresh_stats_with() {
+		let endpoints = (1..=3)
+			.map(|port| Endpoint::new(&format!("Local {port}"), &format!("ws://127.0.0.1:{port}"), vec![], vec![]))
+			.collect();
+		let mut reg =
+			Registry { rpc_endpoints: HashMap::from([("Local".to_string(), endpoints)]), ..Default::default() };
+		let opts = NetworkOptions::new(2, std::time::Duration::from_secs(2));
+		reg.refresh_stats_with(&opts).await;
+		reg.endpoints().for_each(|e| {
+			assert_eq!(1, e.stats.failures);
+			assert_eq!(0, e.stats.success);
+		});
+	}
+
+	#[cfg(unix)]
+	#[tokio::test]
+	async fn test_ping_unix() {
+		let path = env::temp_dir().join("subrpc_test_ping.sock");
+		let _ = std::fs::remove_file(&path);
+		let listener = tokio::net::UnixListener::bind(&path).unwrap();
+		tokio::spawn(async move { crate::rpc::test_rpc::serve_node(listener.accept().await.unwrap().0).await });
+
+		let e = Endpoint::new("Local node", &format!("unix://{}", path.display()), vec![], vec![]);
+		let (success, latency) = Registry::ping(&e).await.unwrap();
+		assert!(success);
+		assert!(latency.is_some());
+
+		let e = Endpoint::new("Gone", "unix:///tmp/subrpc_no_such.sock", vec![], vec![]);
+		assert!(Registry::ping(&e).await.is_err());
+	}
+
+	#[tokio::test]
+	async fn test_identity() {
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let url = format!("tcp://{}", listener.local_addr().unwrap());
+		tokio::spawn(async move {
+			loop {
+				let (socket, _) = listener.accept().await.unwrap();
+				tokio::spawn(crate::rpc::test_rpc::serve_node(socket));
+			}
+		});
+
+		let node = |name: &str| vec![Endpoint::new(name, &url, vec![], vec![])];
+		let genesis = |hash: &str| Chain { genesis_hash: Some(hash.to_string()), ..Default::default() };
+		let mut reg = Registry {
+			rpc_endpoints: HashMap::from([
+				("Development".to_string(), node("Good")),
+				("Polkadot".to_string(), node("Wrong name")),
+				("Kusama".to_string(), node("Wrong genesis")),
+			]),
+			chains: HashMap::from([
+				("Development".to_string(), genesis(&crate::rpc::test_rpc::GENESIS.to_uppercase())),
+				("Polkadot".to_string(), Chain { system_chain: Some("Polkadot".to_string()), ..Default::default() }),
+				("Kusama".to_string(), genesis("0xb0a8d493285c2df73290dfb7e61f870f17b41801197a149ca93654499ea3dafe")),
+			]),
+			..Default::default()
+		};
+		reg.refresh_stats_with(&NetworkOptions::default()).await;
+
+		let stats = |reg: &Registry, chain: &str| reg.rpc_endpoints[chain][0].stats.clone();
+		assert_eq!(1, stats(&reg, "Development").success);
+		assert!(!stats(&reg, "Development").is_misconfigured());
+		assert!(stats(&reg, "Development").score() > 0f32);
+
+		assert_eq!(1, stats(&reg, "Polkadot").success);
+		assert_eq!("chain 'Development'", stats(&reg, "Polkadot").mismatch.unwrap().found);
+		assert_eq!(0f32, stats(&reg, "Polkadot").score());
+		assert!(stats(&reg, "Kusama").mismatch.unwrap().expected.starts_with("genesis 0xb0a8"));
+
+		// The mismatch is cleared once the registry is fixed
+		reg.chains.remove("Polkadot");
+		reg.refresh_stats_with(&NetworkOptions::default()).await;
+		assert!(!reg.rpc_endpoints["Polkadot"][0].stats.is_misconfigured());
+	}
+
+	async fn serve_nodes(best_blocks: &[u64]) -> Vec<Endpoint> {
+		let mut endpoints = vec![];
+		for &best_block in best_blocks {
+			let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+			let url = format!("tcp://{}", listener.local_addr().unwrap());
+			tokio::spawn(async move {
+				loop {
+					let (socket, _) = listener.accept().await.unwrap();
+					tokio::spawn(crate::rpc::test_rpc::serve_node_at(socket, best_block));
+				}
+			});
+			endpoints.push(Endpoint::new(&format!("Node {best_block}"), &url, vec![], vec![]));
+		}
+		endpoints
+	}
+
+	#[tokio::test]
+	async fn test_health() {
+		let endpoints = serve_nodes(&[100, 90, 100]).await;
+		let mut reg =
+			Registry { rpc_endpoints: HashMap::from([("Local".to_string(), endpoints)]), ..Default::default() };
+
+		reg.refresh_stats_with(&NetworkOptions::default().with_health_probe(false)).await;
+		assert!(reg.endpoints().all(|e| e.stats.health.is_none()));
+
+		reg.refresh_stats_with(&NetworkOptions::default()).await;
+		let health: Vec<_> = reg.endpoints().map(|e| e.stats.health.clone().unwrap()).collect();
+		assert_eq!(vec![0, 10, 0], health.iter().map(|h| h.lag).collect::<Vec<_>>());
+		assert_eq!((4, false), (health[1].peers, health[1].is_syncing));
+
+		let scores: Vec<_> = reg.endpoints().map(|e| e.stats.score()).collect();
+		assert!(scores[1] < scores[0] && scores[1] < scores[2]);
+	}
+
+	#[tokio::test]
+	async fn test_lag_of_same_chain() {
+		let mut reg = Registry {
+			rpc_endpoints: HashMap::from([
+				("Statemint".to_string(), serve_nodes(&[100]).await),
+				("Asset Hub Polkadot".to_string(), serve_nodes(&[90]).await),
+				("Polkadot Collectives".to_string(), serve_nodes(&[80]).await),
+				("polkadot-collectives".to_string(), serve_nodes(&[70]).await),
+			]),
+			chains: HashMap::from([(
+				"Statemint".to_string(),
+				Chain { id: "assethubpolkadot".to_string(), ..Default::default() },
+			)]),
+			..Default::default()
+		};
+		reg.refresh_stats_with(&NetworkOptions::default()).await;
+
+		let lag = |chain: &str| reg.rpc_endpoints[chain][0].stats.health.as_ref().unwrap().lag;
+		assert_eq!((0, 10), (lag("Statemint"), lag("Asset Hub Polkadot")));
+		assert_eq!((0, 10), (lag("Polkadot Collectives"), lag("polkadot-collectives")));
+	}
+
 	#[test]
-	fn test_ping_all() {
+	fn test_chain_ids() {
+		let reg = Registry {
+			rpc_endpoints: HashMap::from([("Statemint".to_string(), vec![]), ("Kusama".to_string(), vec![])]),
+			chains: HashMap::from([(
+				"Statemint".to_string(),
+				Chain { genesis_hash: Some("0x68D5".to_string()), ..Default::default() },
+			)]),
+			..Default::default()
+		};
+		let known = Chain {
+			id: "asset-hub-polkadot".to_string(),
+			genesis_hash: Some("0x68d5".to_string()),
+			..Default::default()
+		};
+
+		let ids = reg.chain_ids(&[known]);
+		assert_eq!("asset-hub-polkadot", ids["Statemint"]);
+		assert_eq!("kusama", ids["Kusama"]);
+	}
+
+	#[tokio::test]
+	async fn test_health_timeout() {
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let url = format!("tcp://{}", listener.local_addr().unwrap());
+		tokio::spawn(async move {
+			crate::rpc::test_rpc::serve_node_without_health(listener.accept().await.unwrap().0).await
+		});
+
+		let e = Endpoint::new("No health", &url, vec![], vec![]);
+		let opts = NetworkOptions::new(1, std::time::Duration::from_millis(200));
+		let result = Registry::ping_chain(&e, None, &opts).await.unwrap().expect("The node should be up");
+		assert_eq!(("Development", None), (result.system_chain.as_str(), result.health));
+	}
+
+	#[tokio::test]
+	async fn test_probe() {
+		let mut endpoints = vec![];
+		for best_block in [1000, 100] {
+			let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+			let url = format!("tcp://{}", listener.local_addr().unwrap());
+			tokio::spawn(async move {
+				crate::rpc::test_rpc::serve_node_at(listener.accept().await.unwrap().0, best_block).await
+			});
+			endpoints.push(Endpoint::new(&format!("Node {best_block}"), &url, vec![], vec![]));
+		}
+		let mut reg =
+			Registry { rpc_endpoints: HashMap::from([("Local".to_string(), endpoints)]), ..Default::default() };
+		reg.refresh_capabilities(&NetworkOptions::default()).await;
+
+		let capabilities: Vec<_> = reg.endpoints().map(|e| e.stats.capabilities.clone().unwrap()).collect();
+		assert_eq!(("polkadot", 1_002_000), (capabilities[0].spec_name.as_str(), capabilities[0].spec_version));
+		assert!(capabilities[0].supports("chainHead_v1"));
+		// The old state is pruned
+		assert_eq!(Some(false), capabilities[0].archive);
+		// Too young to tell
+		assert_eq!(None, capabilities[1].archive);
+	}
+
+	#[tokio::test]
+	async fn test_ping_all() {
 		let mut reg1 = Registry::default();
-		reg1.ping_all();
+		reg1.ping_all().await;
 	}
 
-	#[test]
-	fn test_ping_each() {
+	#[tokio::test]
+	async fn test_ping_each() {
 		let reg1 = Registry::default();
-		reg1.rpc_endpoints.iter().for_each(|(_chain, endpoints)| {
-			endpoints.iter().for_each(|e| {
+		for endpoints in reg1.rpc_endpoints.values() {
+			for e in endpoints {
 				println!("Checking {}: {:?}", e.name, e.url);
-				let (success, duration) = Registry::ping(e).unwrap();
+				let (success, duration) = Registry::ping(e).await.unwrap();
 				println!("{} => {:?} {:?}", e.name, success, duration);
 				assert!(success);
-			});
-		});
+			}
+		}
 	}
 
 	#[test]
@@ -306,14 +1128,51 @@ mod test_super {
 		let tmpdir = env::temp_dir();
 		let target_file = Path::new(&tmpdir).join("subrpc.json");
 		assert!(reg1.save(target_file.clone()).is_ok());
-		let reg2 = Registry::load(target_file);
+		let reg2 = Registry::try_load(&target_file).unwrap();
 		assert_eq!(reg2, reg1);
 	}
 
 	#[test]
-	fn test_load_from_url() {
+	fn test_try_load_errors() {
+		let tmpdir = env::temp_dir();
+		let missing = Path::new(&tmpdir).join("subrpc_missing.json");
+		assert!(matches!(Registry::try_load(&missing), Err(Error::Io(_))));
+
+		let invalid = Path::new(&tmpdir).join("subrpc_invalid.json");
+		std::fs::write(&invalid, "{ not json").unwrap();
+		assert!(matches!(Registry::try_load(&invalid), Err(Error::Json(_))));
+
+		let json = r#"{ "name": "Test", "foo": 1, "rpc_endpoints": {} }"#;
+		assert!(matches!(Registry::parse(json, ParseMode::Strict), Err(Error::Schema(issues)) if issues.len() == 2));
+	}
+
+	#[test]
+	fn test_parse_modes() {
+		let json = include_str!("../../registry/sample1.json");
+		let reg = Registry::parse(json, ParseMode::Lenient).unwrap();
+		assert_eq!("SubRPC Test Registry 1", reg.name);
+		assert_eq!(REGISTRY_SCHEMA_VERSION, reg.schema_version);
+		assert!(Registry::parse(json, ParseMode::Strict).is_ok());
+
+		let json = r#"{
+			"name": "Legacy",
+			"last_updated": "2022-12-06Z16:38:00T",
+			"foo": "bar",
+			"rpc_endpoints": { "Polkadot": [
+				{ "name": "Good", "url": "wss://rpc.polkadot.io" },
+				{ "name": "Bad", "url": "ftp://rpc.polkadot.io" }
+			] }
+		}"#;
+		assert!(Registry::parse(json, ParseMode::Strict).is_err());
+		let reg = Registry::parse(json, ParseMode::Lenient).unwrap();
+		assert!(reg.last_update.is_none());
+		assert_eq!(1, reg.rpc_endpoints["Polkadot"].len());
+	}
+
+	#[tokio::test]
+	async fn test_load_from_url() {
 		let test_url = "https://raw.githubusercontent.com/chevdor/subrpc/master/registry/sample1.json";
-		let reg = Registry::load_from_url(test_url).unwrap();
+		let reg = Registry::load_from_url(test_url).await.unwrap();
 		println!("{reg:#?}");
 		assert_eq!("SubRPC Test Registry 1", reg.name);
 	}
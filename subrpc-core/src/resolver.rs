@@ -0,0 +1,267 @@
+//! Resolution of the chain names typed by the users.
+//!
+//! Chains are listed under various names: `Polkadot`, `dot`, `polkadot-relay`, `Statemint` or
+//! `Asset Hub Polkadot`... The [ChainResolver] knows the chain names of the registries and their
+//! aliases, declared by the registries or in the `[aliases]` table of the [Config](crate::Config).
+//! When no name matches exactly, it ranks the chains by similarity to suggest the closest ones.
+
+use crate::{Error, Result};
+use std::collections::BTreeMap;
+
+/// Score of an exact match, on the name of the chain or on one of its aliases
+pub const EXACT_SCORE: f32 = 1.0;
+
+/// Minimum score of a fuzzy match: all the characters typed appear, in order, in the name
+/// or in an alias. Names only close to what was typed, with a typo, score lower.
+pub const FUZZY_SCORE: f32 = 0.3;
+
+/// Maximum number of suggestions returned in errors
+pub const MAX_SUGGESTIONS: usize = 5;
+
+/// Normalized form of a name, used to compare names: lowercase, only letters and digits.
+/// `Asset Hub Polkadot` and `asset-hub-polkadot` are the same name.
+pub fn normalize(name: &str) -> String {
+	name.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// A chain matching what was typed, see [ChainResolver::search]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainMatch {
+	/// Name of the chain, as listed by the registries
+	pub chain: String,
+
+	/// Name or alias of the chain that matched best
+	pub matched: String,
+
+	/// Similarity in the range `]0, 1]`, [EXACT_SCORE] for an exact match
+	pub score: f32,
+}
+
+impl ChainMatch {
+	/// Whether the chain name or one of its aliases is what was typed
+	pub fn is_exact(&self) -> bool {
+		self.score >= EXACT_SCORE
+	}
+}
+
+/// Resolve the names typed by the users into the chain names used by the registries.
+///
+/// Names are compared once [normalized](normalize). A chain name always wins over an alias.
+/// Approximate names are not resolved, [Self::search] finds the closest chains instead.
+/// ```
+/// use subrpc_core::ChainResolver;
+///
+/// let mut resolver = ChainResolver::new();
+/// resolver.add_alias("Polkadot", "dot");
+/// resolver.add_alias("Polkadot Collectives", "collectives");
+/// assert_eq!("Polkadot", resolver.resolve("DOT").unwrap().chain);
+/// assert_eq!("Polkadot Collectives", resolver.resolve("polkadot-collectives").unwrap().chain);
+/// assert_eq!("Polkadot Collectives", resolver.resolve("collectives").unwrap().chain);
+/// assert!(resolver.resolve("collect").is_err());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct ChainResolver {
+	/// Aliases of each chain, indexed by the normalized name of the chain
+	chains: BTreeMap<String, (String, Vec<String>)>,
+}
+
+impl ChainResolver {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Add a chain. Chains whose names only differ by their case or punctuation are the same chain,
+	/// the first name added is kept.
+	pub fn add_chain(&mut self, chain: &str) {
+		self.chains.entry(normalize(chain)).or_insert_with(|| (chain.to_string(), vec![]));
+	}
+
+	/// Add an alias of `chain`, adding the chain if needed
+	pub fn add_alias(&mut self, chain: &str, alias: &str) {
+		let key = normalize(chain);
+		let (_, aliases) = self.chains.entry(key.clone()).or_insert_with(|| (chain.to_string(), vec![]));
+		if normalize(alias) != key && !aliases.iter().any(|a| normalize(a) == normalize(alias)) {
+			aliases.push(alias.to_string());
+		}
+	}
+
+	/// Names of the known chains, sorted
+	pub fn chains(&self) -> Vec<&str> {
+		let mut chains: Vec<&str> = self.chains.values().map(|(chain, _)| chain.as_str()).collect();
+		chains.sort();
+		chains
+	}
+
+	/// Aliases of `chain`, in the order they were added
+	pub fn aliases(&self, chain: &str) -> &[String] {
+		self.chains.get(&normalize(chain)).map(|(_, aliases)| aliases.as_slice()).unwrap_or_default()
+	}
+
+	/// Chains matching `input`, the best first. Chains with the same score are sorted by name.
+	///
+	/// Only the chains scoring at least half of [FUZZY_SCORE] are returned.
+	pub fn search(&self, input: &str) -> Vec<ChainMatch> {
+		let input = normalize(input);
+		let mut matches: Vec<ChainMatch> = self
+			.chains
+			.values()
+			.filter_map(|(chain, aliases)| {
+				std::iter::once(chain)
+					.chain(aliases)
+					.map(|name| (name, similarity(&input, &normalize(name))))
+					.max_by(|(_, a), (_, b)| a.total_cmp(b))
+					.filter(|(_, score)| *score >= FUZZY_SCORE / 2.0)
+					.map(|(name, score)| ChainMatch { chain: chain.clone(), matched: name.clone(), score })
+			})
+			.collect();
+		matches.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.chain.cmp(&b.chain)));
+		matches
+	}
+
+	/// Resolve `input` into a single chain.
+	///
+	/// The name of a chain is tried first, then the aliases. Only exact matches are accepted: a close
+	/// name could well be another chain, so the error suggests the closest chains instead.
+	pub fn resolve(&self, input: &str) -> Result<ChainMatch> {
+		if let Some((chain, _)) = self.chains.get(&normalize(input)) {
+			return Ok(ChainMatch { chain: chain.clone(), matched: chain.clone(), score: EXACT_SCORE });
+		}
+
+		let matches = self.search(input);
+		let exact: Vec<&ChainMatch> = matches.iter().filter(|m| m.is_exact()).collect();
+		let names = |matches: &[&ChainMatch]| -> Vec<String> {
+			matches.iter().take(MAX_SUGGESTIONS).map(|m| m.chain.clone()).collect()
+		};
+		match exact.as_slice() {
+			[m] => Ok((*m).clone()),
+			[] => Err(Error::UnknownChain {
+				name: input.to_string(),
+				suggestions: names(&matches.iter().collect::<Vec<_>>()),
+			}),
+			candidates => Err(Error::AmbiguousChain { name: input.to_string(), candidates: names(candidates) }),
+		}
+	}
+}
+
+/// Similarity of `name` to `input`, both normalized, in the range `[0, 1]`.
+///
+/// Prefixes score higher than substrings, which score higher than subsequences, all of them
+/// scoring at least [FUZZY_SCORE]. Within each kind, the longer the input compared to the name, the better.
+/// Names that are none of those score below [FUZZY_SCORE], according to their edit distance.
+fn similarity(input: &str, name: &str) -> f32 {
+	if input.is_empty() || name.is_empty() {
+		return 0f32;
+	}
+	if input == name {
+		return EXACT_SCORE;
+	}
+
+	let coverage = 0.2 * input.chars().count() as f32 / name.chars().count().max(input.chars().count()) as f32;
+	if name.starts_with(input) {
+		0.7 + coverage
+	} else if name.contains(input) {
+		0.5 + coverage
+	} else if is_subsequence(input, name) {
+		FUZZY_SCORE + coverage
+	} else {
+		let len = input.chars().count().max(name.chars().count()) as f32;
+		let closeness = 1f32 - levenshtein(input, name) as f32 / len;
+		FUZZY_SCORE * closeness.max(0f32) * 0.99
+	}
+}
+
+/// Whether all the characters of `input` appear in `name`, in the same order
+fn is_subsequence(input: &str, name: &str) -> bool {
+	let mut name = name.chars();
+	input.chars().all(|c| name.any(|n| n == c))
+}
+
+/// Number of single character edits needed to turn `a` into `b`
+fn levenshtein(a: &str, b: &str) -> usize {
+	let b: Vec<char> = b.chars().collect();
+	let mut row: Vec<usize> = (0..=b.len()).collect();
+	for (i, ca) in a.chars().enumerate() {
+		let mut previous = row[0];
+		row[0] = i + 1;
+		for (j, cb) in b.iter().enumerate() {
+			let substitution = previous + usize::from(ca != *cb);
+			previous = row[j + 1];
+			row[j + 1] = substitution.min(row[j] + 1).min(previous + 1);
+		}
+	}
+	row[b.len()]
+}
+
+#[cfg(test)]
+mod test_resolver {
+	use super::*;
+
+	fn resolver() -> ChainResolver {
+		let mut resolver = ChainResolver::new();
+		["Polkadot", "Polkadot Collectives", "Kusama", "Statemint", "Moonriver", "Moonbeam"]
+			.iter()
+			.for_each(|chain| resolver.add_chain(chain));
+		resolver.add_alias("Polkadot", "dot");
+		resolver.add_alias("Polkadot", "polkadot-relay");
+		resolver.add_alias("Statemint", "Asset Hub Polkadot");
+		resolver.add_alias("kusama", "ksm");
+		resolver
+	}
+
+	#[test]
+	fn test_levenshtein() {
+		assert_eq!(0, levenshtein("polkadot", "polkadot"));
+		assert_eq!(1, levenshtein("polkdot", "polkadot"));
+		assert_eq!(3, levenshtein("kitten", "sitting"));
+		assert_eq!(4, levenshtein("", "dots"));
+	}
+
+	#[test]
+	fn test_resolve() {
+		let resolver = resolver();
+		let chain = |input: &str| resolver.resolve(input).map(|m| m.chain);
+
+		assert_eq!("Polkadot", chain("polkadot").unwrap());
+		assert_eq!("Polkadot", chain("DOT").unwrap());
+		assert_eq!("Polkadot", chain("Polkadot-Relay").unwrap());
+		assert_eq!("Statemint", chain("asset-hub-polkadot").unwrap());
+		assert_eq!("Kusama", chain("ksm").unwrap());
+		assert_eq!(vec!["ksm"], resolver.aliases("KUSAMA"));
+
+		// Close names are only suggested, they could be another chain
+		assert!(
+			matches!(chain("river"), Err(Error::UnknownChain { suggestions, .. }) if suggestions[0] == "Moonriver")
+		);
+		assert!(matches!(
+			chain("collectives"),
+			Err(Error::UnknownChain { suggestions, .. }) if suggestions[0] == "Polkadot Collectives"
+		));
+		assert!(matches!(
+			chain("moon"),
+			Err(Error::UnknownChain { suggestions, .. }) if suggestions[..2] == ["Moonbeam", "Moonriver"]
+		));
+		assert!(matches!(
+			chain("kusamma"),
+			Err(Error::UnknownChain { suggestions, .. }) if suggestions[0] == "Kusama"
+		));
+		assert!(matches!(chain("xyz"), Err(Error::UnknownChain { suggestions, .. }) if suggestions.is_empty()));
+
+		let mut resolver = resolver;
+		resolver.add_alias("Kusama", "ksm-relay");
+		resolver.add_alias("Polkadot", "ksm-relay");
+		assert!(matches!(
+			resolver.resolve("ksm-relay"),
+			Err(Error::AmbiguousChain { candidates, .. }) if candidates == ["Kusama", "Polkadot"]
+		));
+	}
+
+	#[test]
+	fn test_search() {
+		let resolver = resolver();
+		let chains = |input: &str| resolver.search(input).into_iter().map(|m| m.chain).collect::<Vec<_>>();
+
+		assert_eq!(vec!["Polkadot", "Polkadot Collectives", "Statemint"], chains("polka"));
+		assert_eq!(vec!["Polkadot", "Statemint", "Polkadot Collectives"], chains("polkdot"));
+		assert_eq!("Asset Hub Polkadot", resolver.search("asset hub").remove(0).matched);
+	}
+}
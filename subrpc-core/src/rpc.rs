@@ -0,0 +1,226 @@
+//! Minimal JSON-RPC client over the transports supported by [EndpointUrl].
+//!
+//! Http and WebSocket endpoints go through jsonrpsee. Unix sockets and plain TCP use newline
+//! delimited JSON-RPC, as exposed by the IPC server of the nodes. Auth headers only apply to
+//! Http and WebSocket.
+
+use crate::{EndpointUrl, Error, Result};
+use jsonrpsee::{
+	core::{client::ClientT, params::ArrayParams, ClientError},
+	http_client::{HeaderMap, HttpClient, HttpClientBuilder},
+	types::ErrorObjectOwned,
+	ws_client::{WsClient, WsClientBuilder},
+};
+use log::*;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::{collections::HashMap, io, time::Duration};
+use tokio::{
+	io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+	net::TcpStream,
+};
+
+pub(crate) trait Stream: AsyncRead + AsyncWrite + Unpin + Send {}
+impl<T: AsyncRead + AsyncWrite + Unpin + Send> Stream for T {}
+
+/// A connection to an endpoint
+pub(crate) enum RpcClient {
+	Http(Box<HttpClient>),
+	Ws(WsClient),
+	Stream { stream: BufReader<Box<dyn Stream>>, next_id: u64 },
+}
+
+impl RpcClient {
+	/// Connect to `url`, sending the given `headers` to Http and WebSocket endpoints.
+	pub(crate) async fn connect(url: &EndpointUrl, headers: &[(String, String)], timeout: Duration) -> Result<Self> {
+		Ok(match url {
+			EndpointUrl::Https(url) | EndpointUrl::Http(url) => {
+				trace!("Detected HTTP/S");
+				let client = HttpClientBuilder::default()
+					.request_timeout(timeout)
+					.set_headers(header_map(headers)?)
+					.build(url.as_str())?;
+				RpcClient::Http(Box::new(client))
+			}
+			EndpointUrl::Wss(url) | EndpointUrl::Ws(url) => {
+				trace!("Detected WS/S");
+				let client = WsClientBuilder::default()
+					.set_headers(header_map(headers)?)
+					.connection_timeout(timeout)
+					.request_timeout(timeout)
+					.build(url.as_str())
+					.await?;
+				RpcClient::Ws(client)
+			}
+			EndpointUrl::Tcp(_) => {
+				trace!("Detected TCP");
+				let stream = TcpStream::connect((url.host(), url.port().unwrap_or_default())).await?;
+				Self::stream(Box::new(stream))
+			}
+			EndpointUrl::Unix(_) => {
+				trace!("Detected Unix socket");
+				let path = url.socket_path().ok_or(Error::InvalidUrl(url.to_string()))?;
+				Self::stream(connect_unix(&path).await?)
+			}
+		})
+	}
+
+	fn stream(stream: Box<dyn Stream>) -> Self {
+		RpcClient::Stream { stream: BufReader::new(stream), next_id: 0 }
+	}
+
+	/// Call `method` and deserialize its result
+	pub(crate) async fn request<R: DeserializeOwned>(&mut self, method: &str, params: Vec<Value>) -> Result<R> {
+		match self {
+			RpcClient::Http(client) => Ok(client.request(method, array_params(params)?).await?),
+			RpcClient::Ws(client) => Ok(client.request(method, array_params(params)?).await?),
+			RpcClient::Stream { stream, next_id } => {
+				*next_id += 1;
+				let id = *next_id;
+				let mut request =
+					serde_json::to_vec(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))?;
+				request.push(b'\n');
+				stream.get_mut().write_all(&request).await?;
+				stream.get_mut().flush().await?;
+
+				let mut line = String::new();
+				loop {
+					line.clear();
+					if stream.read_line(&mut line).await? == 0 {
+						return Err(
+							io::Error::new(io::ErrorKind::UnexpectedEof, "Connection closed by the node").into()
+						);
+					}
+					let response: Value = serde_json::from_str(&line)?;
+					// Skip the notifications and the responses to other requests
+					if response.get("id") != Some(&json!(id)) {
+						continue;
+					}
+					if let Some(error) = response.get("error") {
+						let error: ErrorObjectOwned = serde_json::from_value(error.clone())?;
+						return Err(ClientError::Call(error).into());
+					}
+					return Ok(serde_json::from_value(response.get("result").cloned().unwrap_or_default())?);
+				}
+			}
+		}
+	}
+}
+
+fn header_map(headers: &[(String, String)]) -> Result<HeaderMap> {
+	let headers: HashMap<_, _> = headers.iter().cloned().collect();
+	HeaderMap::try_from(&headers).map_err(|e| Error::Auth(e.to_string()))
+}
+
+fn array_params(params: Vec<Value>) -> Result<ArrayParams> {
+	let mut array = ArrayParams::new();
+	for param in params {
+		array.insert(param)?;
+	}
+	Ok(array)
+}
+
+#[cfg(unix)]
+async fn connect_unix(path: &std::path::Path) -> Result<Box<dyn Stream>> {
+	Ok(Box::new(tokio::net::UnixStream::connect(path).await?))
+}
+
+#[cfg(not(unix))]
+async fn connect_unix(_path: &std::path::Path) -> Result<Box<dyn Stream>> {
+	Err(io::Error::new(io::ErrorKind::Unsupported, "Unix sockets are not supported on this platform").into())
+}
+
+#[cfg(test)]
+pub(crate) mod test_rpc {
+	use super::*;
+
+	/// Genesis hash of the chain served by [serve_node]
+	pub(crate) const GENESIS: &str = "0x0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef";
+
+	/// Answer newline delimited JSON-RPC requests on `stream` like a node would.
+	pub(crate) async fn serve_node<S: AsyncRead + AsyncWrite + Unpin>(stream: S) {
+		serve_node_at(stream, 100).await
+	}
+
+	/// Same as [serve_node], for a node whose best block is `best_block`
+	pub(crate) async fn serve_node_at<S: AsyncRead + AsyncWrite + Unpin>(stream: S, best_block: u64) {
+		serve(stream, best_block, true).await
+	}
+
+	/// Same as [serve_node], for a node that never answers `system_health`
+	pub(crate) async fn serve_node_without_health<S: AsyncRead + AsyncWrite + Unpin>(stream: S) {
+		serve(stream, 100, false).await
+	}
+
+	async fn serve<S: AsyncRead + AsyncWrite + Unpin>(stream: S, best_block: u64, health: bool) {
+		let mut stream = BufReader::new(stream);
+		let mut line = String::new();
+		while stream.read_line(&mut line).await.unwrap_or(0) > 0 {
+			let request: Value = serde_json::from_str(&line).unwrap();
+			if !health && request["method"] == "system_health" {
+				line.clear();
+				continue;
+			}
+			let response = match request["method"].as_str() {
+				Some("system_chain") => json!({ "jsonrpc": "2.0", "id": request["id"], "result": "Development" }),
+				Some("chain_getBlockHash") => json!({ "jsonrpc": "2.0", "id": request["id"], "result": GENESIS }),
+				// Only the recent state is available, like on a pruned node
+				Some("state_getRuntimeVersion") if request["params"].as_array().is_none_or(|p| p.is_empty()) => json!({
+					"jsonrpc": "2.0",
+					"id": request["id"],
+					"result": { "specName": "polkadot", "specVersion": 1_002_000 }
+				}),
+				Some("rpc_methods") => json!({
+					"jsonrpc": "2.0",
+					"id": request["id"],
+					"result": { "methods": ["chain_getHeader", "chainHead_v1_follow", "system_chain"] }
+				}),
+				Some("system_health") => json!({
+					"jsonrpc": "2.0",
+					"id": request["id"],
+					"result": { "isSyncing": false, "peers": 4, "shouldHavePeers": true }
+				}),
+				Some("chain_getHeader") => json!({
+					"jsonrpc": "2.0",
+					"id": request["id"],
+					"result": { "number": format!("{best_block:#x}"), "parentHash": GENESIS }
+				}),
+				_ => {
+					json!({ "jsonrpc": "2.0", "id": request["id"], "error": { "code": -32601, "message": "Method not found" } })
+				}
+			};
+			// A notification first, that the client must skip
+			let notification = json!({ "jsonrpc": "2.0", "method": "noise", "params": {} });
+			let out = format!("{notification}\n{response}\n");
+			stream.get_mut().write_all(out.as_bytes()).await.unwrap();
+			line.clear();
+		}
+	}
+
+	#[tokio::test]
+	async fn test_tcp() {
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let url = EndpointUrl::try_from(format!("tcp://{}", listener.local_addr().unwrap())).unwrap();
+		tokio::spawn(async move { serve_node(listener.accept().await.unwrap().0).await });
+
+		let mut client = RpcClient::connect(&url, &[], Duration::from_secs(1)).await.unwrap();
+		assert_eq!("Development", client.request::<String>("system_chain", vec![]).await.unwrap());
+		assert!(matches!(
+			client.request::<String>("foo", vec![json!(1)]).await,
+			Err(Error::Rpc(ClientError::Call(e))) if e.code() == -32601
+		));
+	}
+
+	#[cfg(unix)]
+	#[tokio::test]
+	async fn test_unix() {
+		let path = std::env::temp_dir().join("subrpc_test_rpc.sock");
+		let _ = std::fs::remove_file(&path);
+		let listener = tokio::net::UnixListener::bind(&path).unwrap();
+		let url = EndpointUrl::try_from(format!("unix://{}", path.display())).unwrap();
+		tokio::spawn(async move { serve_node(listener.accept().await.unwrap().0).await });
+
+		let mut client = RpcClient::connect(&url, &[], Duration::from_secs(1)).await.unwrap();
+		assert_eq!("Development", client.request::<String>("system_chain", vec![]).await.unwrap());
+	}
+}
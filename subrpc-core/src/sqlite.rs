@@ -0,0 +1,274 @@
+use chrono::{DateTime, Duration, Local};
+use log::*;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde_json::{Map, Value};
+use std::{
+	collections::HashSet,
+	fs,
+	path::{Path, PathBuf},
+	sync::{Mutex, PoisonError},
+};
+
+use crate::{
+	migrate, Error, LocalData, MigrationReport, Registry, Result, Sample, Storage, Window, DATA_VERSION,
+	HISTORY_CAPACITY,
+};
+
+/// Number of days the [SqliteStorage] keeps the samples of the endpoints
+pub const HISTORY_RETENTION_DAYS: i64 = 90;
+
+const SCHEMA: &str = "
+	CREATE TABLE IF NOT EXISTS meta (
+		key TEXT PRIMARY KEY,
+		value TEXT NOT NULL
+	);
+	CREATE TABLE IF NOT EXISTS registries (
+		name TEXT PRIMARY KEY,
+		data TEXT NOT NULL
+	);
+	CREATE TABLE IF NOT EXISTS endpoints (
+		registry TEXT NOT NULL,
+		chain TEXT NOT NULL,
+		position INTEGER NOT NULL,
+		url TEXT NOT NULL,
+		data TEXT NOT NULL,
+		PRIMARY KEY (registry, chain, position)
+	);
+	CREATE TABLE IF NOT EXISTS samples (
+		registry TEXT NOT NULL,
+		chain TEXT NOT NULL,
+		url TEXT NOT NULL,
+		time INTEGER NOT NULL,
+		success INTEGER NOT NULL,
+		latency REAL,
+		PRIMARY KEY (registry, chain, url, time)
+	);
+	CREATE INDEX IF NOT EXISTS samples_url ON samples (url, time);
+";
+
+/// Store the data in an embedded SQLite database.
+///
+/// Registries and endpoints are stored as JSON in their own rows and only the rows that changed are
+/// written. The samples of the [history](crate::EndpointStats::history) of the endpoints get their own table,
+/// where they are kept for [HISTORY_RETENTION_DAYS], see [Storage::history].
+/// Each save is a single transaction.
+#[derive(Debug)]
+pub struct SqliteStorage {
+	file: PathBuf,
+	connection: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+	/// Open the database stored in `file`, creating it if needed
+	pub fn open(file: &Path) -> Result<Self> {
+		debug!("Opening SQLite database {}", file.display());
+		if let Some(dir) = file.parent() {
+			fs::create_dir_all(dir)?;
+		}
+		let connection = Connection::open(file)?;
+		connection.execute_batch(SCHEMA)?;
+		Ok(Self { file: file.to_path_buf(), connection: Mutex::new(connection) })
+	}
+
+	fn version(connection: &Connection) -> Result<Option<u32>> {
+		let version: Option<String> =
+			connection.query_row("SELECT value FROM meta WHERE key = 'version'", [], |row| row.get(0)).optional()?;
+		version.map(|v| v.parse().map_err(|_| Error::Migration(format!("Invalid version {v}")))).transpose()
+	}
+
+	/// Samples of an endpoint since `since`, at most the `limit` most recent ones, the oldest first
+	fn samples(
+		connection: &Connection,
+		(registry, chain, url): (&str, &str, &str),
+		since: DateTime<Local>,
+		limit: usize,
+	) -> Result<Vec<Sample>> {
+		let mut statement = connection.prepare_cached(
+			"SELECT time, success, latency FROM samples
+			WHERE registry = ?1 AND chain = ?2 AND url = ?3 AND time >= ?4 ORDER BY time DESC LIMIT ?5",
+		)?;
+		let mut samples = statement
+			.query_map(params![registry, chain, url, nanos(since), limit as i64], sample)?
+			.collect::<rusqlite::Result<Vec<Sample>>>()?;
+		samples.reverse();
+		Ok(samples)
+	}
+
+	/// Read the data stored in the database and upgrade it to [DATA_VERSION].
+	///
+	/// The rows are gathered into the JSON layout of the data so that the same [migrations](crate::MIGRATIONS)
+	/// apply to all the storages.
+	fn read_migrated(&self) -> Result<(LocalData, MigrationReport)> {
+		let connection = self.connection.lock().unwrap_or_else(PoisonError::into_inner);
+		let version = Self::version(&connection)?;
+		let last_update: Option<String> = connection
+			.query_row("SELECT value FROM meta WHERE key = 'last_update'", [], |row| row.get(0))
+			.optional()?;
+
+		let since = Local::now() - Window::Week.duration();
+		let mut registries = Map::new();
+		let mut statement = connection.prepare("SELECT name, data FROM registries")?;
+		let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+		for row in rows {
+			let (name, data) = row?;
+			let mut registry: Value = serde_json::from_str(&data)?;
+			let mut chains = Map::new();
+			let mut endpoints = connection.prepare_cached(
+				"SELECT chain, url, data FROM endpoints WHERE registry = ?1 ORDER BY chain, position",
+			)?;
+			let rows = endpoints.query_map([&name], |row| Ok((row.get::<_, String>(0)?, row.get(1)?, row.get(2)?)))?;
+			for row in rows {
+				let (chain, url, data): (String, String, String) = row?;
+				let mut endpoint: Value = serde_json::from_str(&data)?;
+				let history = Self::samples(&connection, (&name, &chain, &url), since, HISTORY_CAPACITY)?;
+				if let Some(Value::Object(stats)) = endpoint.get_mut("stats") {
+					stats.insert("history".to_string(), serde_json::to_value(history)?);
+				}
+				if let Value::Array(endpoints) = chains.entry(chain).or_insert_with(|| Value::Array(vec![])) {
+					endpoints.push(endpoint);
+				}
+			}
+			registry["rpc_endpoints"] = Value::Object(chains);
+			registries.insert(name, registry);
+		}
+
+		let mut data = serde_json::json!({
+			"file": self.file,
+			"registries": registries,
+			"last_update": last_update.map(|t| serde_json::from_str::<Value>(&t)).transpose()?,
+		});
+		// Data without version is considered version 0 and upgraded, see [migrate]
+		if let Some(version) = version {
+			data["version"] = Value::from(version);
+		}
+		let report = migrate(&mut data)?;
+		Ok((serde_json::from_value(data)?, report))
+	}
+
+	fn save_registry(tx: &Transaction, reg: &Registry) -> Result<()> {
+		let mut data = serde_json::to_value(reg)?;
+		if let Value::Object(data) = &mut data {
+			data.remove("rpc_endpoints");
+		}
+		tx.prepare_cached(
+			"INSERT INTO registries (name, data) VALUES (?1, ?2)
+			ON CONFLICT (name) DO UPDATE SET data = excluded.data WHERE data != excluded.data",
+		)?
+		.execute(params![reg.name, data.to_string()])?;
+
+		let mut upsert = tx.prepare_cached(
+			"INSERT INTO endpoints (registry, chain, position, url, data) VALUES (?1, ?2, ?3, ?4, ?5)
+			ON CONFLICT (registry, chain, position) DO UPDATE SET url = excluded.url, data = excluded.data
+			WHERE url != excluded.url OR data != excluded.data",
+		)?;
+		let mut add_sample = tx.prepare_cached(
+			"INSERT OR IGNORE INTO samples (registry, chain, url, time, success, latency) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+		)?;
+		for (chain, endpoints) in &reg.rpc_endpoints {
+			for (position, endpoint) in endpoints.iter().enumerate() {
+				let url = endpoint.url.to_string();
+				let mut data = serde_json::to_value(endpoint)?;
+				if let Some(Value::Object(stats)) = data.get_mut("stats") {
+					stats.remove("history");
+				}
+				upsert.execute(params![reg.name, chain, position as i64, url, data.to_string()])?;
+				for s in &endpoint.stats.history {
+					add_sample.execute(params![reg.name, chain, url, nanos(s.time), s.success, s.latency])?;
+				}
+			}
+			tx.prepare_cached("DELETE FROM endpoints WHERE registry = ?1 AND chain = ?2 AND position >= ?3")?
+				.execute(params![reg.name, chain, endpoints.len() as i64])?;
+		}
+
+		let chains: Vec<String> = tx
+			.prepare_cached("SELECT DISTINCT chain FROM endpoints WHERE registry = ?1")?
+			.query_map([&reg.name], |row| row.get(0))?
+			.collect::<rusqlite::Result<_>>()?;
+		for chain in chains.iter().filter(|chain| !reg.rpc_endpoints.contains_key(*chain)) {
+			tx.execute("DELETE FROM endpoints WHERE registry = ?1 AND chain = ?2", params![reg.name, chain])?;
+		}
+		Ok(())
+	}
+}
+
+/// Time as stored in the database, in nanoseconds since the epoch
+fn nanos(time: DateTime<Local>) -> i64 {
+	time.timestamp_nanos_opt().unwrap_or(i64::MAX)
+}
+
+fn sample(row: &rusqlite::Row) -> rusqlite::Result<Sample> {
+	Ok(Sample {
+		time: DateTime::from_timestamp_nanos(row.get(0)?).with_timezone(&Local),
+		success: row.get(1)?,
+		latency: row.get(2)?,
+	})
+}
+
+impl Storage for SqliteStorage {
+	fn location(&self) -> String {
+		self.file.display().to_string()
+	}
+
+	fn file(&self) -> Option<&Path> {
+		Some(&self.file)
+	}
+
+	fn exists(&self) -> bool {
+		let connection = self.connection.lock().unwrap_or_else(PoisonError::into_inner);
+		Self::version(&connection).is_ok_and(|version| version.is_some())
+	}
+
+	fn load(&self) -> Result<LocalData> {
+		let (data, report) = self.read_migrated()?;
+		if !report.is_empty() {
+			info!("Upgraded the data of {} from version {} to {}", self.file.display(), report.from, report.to);
+		}
+		Ok(data)
+	}
+
+	fn save(&self, data: &LocalData) -> Result<()> {
+		let mut connection = self.connection.lock().unwrap_or_else(PoisonError::into_inner);
+		let tx = connection.transaction()?;
+		tx.execute(
+			"INSERT OR REPLACE INTO meta (key, value) VALUES ('version', ?1), ('last_update', ?2)",
+			params![data.version.to_string(), serde_json::to_string(&data.last_update)?],
+		)?;
+
+		let names: HashSet<String> = tx
+			.prepare("SELECT name FROM registries")?
+			.query_map([], |row| row.get(0))?
+			.collect::<rusqlite::Result<_>>()?;
+		for name in names.iter().filter(|name| !data.registries.contains_key(*name)) {
+			debug!("Removing registry '{name}' from {}", self.file.display());
+			for table in ["registries WHERE name", "endpoints WHERE registry", "samples WHERE registry"] {
+				tx.execute(&format!("DELETE FROM {table} = ?1"), [name])?;
+			}
+		}
+		for reg in data.registries.values() {
+			Self::save_registry(&tx, reg)?;
+		}
+
+		let oldest = Local::now() - Duration::days(HISTORY_RETENTION_DAYS);
+		tx.execute("DELETE FROM samples WHERE time < ?1", [nanos(oldest)])?;
+		tx.commit()?;
+		Ok(())
+	}
+
+	fn history(&self, url: &str, since: DateTime<Local>) -> Result<Vec<Sample>> {
+		let connection = self.connection.lock().unwrap_or_else(PoisonError::into_inner);
+		let mut statement = connection
+			.prepare_cached("SELECT time, success, latency FROM samples WHERE url = ?1 AND time >= ?2 ORDER BY time")?;
+		let mut samples =
+			statement.query_map(params![url, nanos(since)], sample)?.collect::<rusqlite::Result<Vec<Sample>>>()?;
+		samples.dedup_by_key(|s| s.time);
+		Ok(samples)
+	}
+
+	fn migrate(&self, dry_run: bool) -> Result<MigrationReport> {
+		let (data, report) = self.read_migrated()?;
+		if !report.is_empty() && !dry_run {
+			self.save(&LocalData { version: DATA_VERSION, ..data })?;
+		}
+		Ok(report)
+	}
+}
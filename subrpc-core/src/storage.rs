@@ -0,0 +1,432 @@
+//! Storage of the [LocalData].
+//!
+//! The local data is loaded from and saved to a [Storage]. The [StorageBackend] set as `storage`
+//! in the [Config](crate::Config) selects which one:
+//! - [JsonStorage]: a pretty-printed JSON file, rewritten in full on each save and backed up. This is the default.
+//! - [SqliteStorage]: an embedded SQLite database, only writing what changed. It keeps the history of the
+//!   stats for [HISTORY_RETENTION_DAYS](crate::HISTORY_RETENTION_DAYS). Requires the `sqlite` feature.
+//! - [MemoryStorage]: nothing is written to disk, for tests and apps embedding subrpc.
+
+use chrono::{DateTime, Local};
+use log::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+	fmt::{Debug, Display},
+	fs::{self, File},
+	io::{self, Write},
+	path::{Path, PathBuf},
+	process,
+	sync::{Arc, Mutex, PoisonError},
+};
+
+#[cfg(feature = "sqlite")]
+use crate::SqliteStorage;
+use crate::{migrate, Error, LocalData, MigrationReport, Result, Sample, BACKUPS, DATA_VERSION};
+
+/// Where the [LocalData] is loaded from and saved to
+pub trait Storage: Debug + Send + Sync {
+	/// Where the data is stored, such as the path of a file, for display
+	fn location(&self) -> String;
+
+	/// File holding the data, if any
+	fn file(&self) -> Option<&Path> {
+		None
+	}
+
+	/// Whether some data was saved already
+	fn exists(&self) -> bool;
+
+	/// Load the data saved last
+	fn load(&self) -> Result<LocalData>;
+
+	/// Save the data, replacing the data saved previously
+	fn save(&self, data: &LocalData) -> Result<()>;
+
+	/// Upgrade the stored data to [DATA_VERSION] and save it, unless `dry_run` is set.
+	/// The report lists the changes, it is empty if the data is up to date.
+	fn migrate(&self, dry_run: bool) -> Result<MigrationReport>;
+
+	/// Samples recorded for the endpoints at `url` since `since`, the oldest first.
+	///
+	/// Storages keeping more than the [history](crate::EndpointStats::history) of the endpoints return all they have.
+	fn history(&self, url: &str, since: DateTime<Local>) -> Result<Vec<Sample>> {
+		let data = self.load()?;
+		let mut samples: Vec<Sample> = data
+			.registries
+			.values()
+			.flat_map(|reg| reg.rpc_endpoints.values().flatten())
+			.filter(|e| e.url.to_string() == url)
+			.flat_map(|e| e.stats.history.iter().filter(|s| s.time >= since).cloned())
+			.collect();
+		samples.sort_by_key(|s| s.time);
+		samples.dedup_by_key(|s| s.time);
+		Ok(samples)
+	}
+}
+
+/// Storages are the same if they store the data at the same location
+impl PartialEq for dyn Storage {
+	fn eq(&self, other: &Self) -> bool {
+		self.location() == other.location()
+	}
+}
+
+/// Kind of [Storage] used for the local data
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+	/// [JsonStorage]
+	#[default]
+	Json,
+
+	/// [SqliteStorage]
+	Sqlite,
+
+	/// [MemoryStorage]
+	Memory,
+}
+
+impl StorageBackend {
+	/// Default file of the backend, next to [LocalData::get_default_file]
+	pub fn default_file(&self) -> Result<PathBuf> {
+		let file = LocalData::get_default_file()?;
+		Ok(match self {
+			StorageBackend::Sqlite => file.with_extension("db"),
+			StorageBackend::Json | StorageBackend::Memory => file,
+		})
+	}
+
+	/// Backend storing data in `file`, according to its extension: `.json`, or `.db`, `.sqlite` and `.sqlite3`
+	pub fn for_file(file: &Path) -> Option<Self> {
+		match file.extension()?.to_str()?.to_lowercase().as_str() {
+			"json" => Some(StorageBackend::Json),
+			"db" | "sqlite" | "sqlite3" => Some(StorageBackend::Sqlite),
+			_ => None,
+		}
+	}
+
+	/// Open the storage of this kind using `file`. The [MemoryStorage] ignores the file.
+	pub fn open(&self, file: &Path) -> Result<Arc<dyn Storage>> {
+		Ok(match self {
+			StorageBackend::Json => Arc::new(JsonStorage::new(file)),
+			#[cfg(feature = "sqlite")]
+			StorageBackend::Sqlite => Arc::new(SqliteStorage::open(file)?),
+			#[cfg(not(feature = "sqlite"))]
+			StorageBackend::Sqlite => {
+				return Err(Error::Config("the sqlite storage requires the sqlite feature of subrpc-core".to_string()))
+			}
+			StorageBackend::Memory => Arc::new(MemoryStorage::new()),
+		})
+	}
+}
+
+impl Display for StorageBackend {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.write_str(match self {
+			StorageBackend::Json => "json",
+			StorageBackend::Sqlite => "sqlite",
+			StorageBackend::Memory => "memory",
+		})
+	}
+}
+
+/// Store the data as pretty-printed JSON in a file.
+///
+/// Each save writes a temporary file next to the target, which is then renamed: an interrupted save
+/// never leaves a truncated file behind. The previous file is kept as the most recent backup,
+/// see [LocalData::backup_file].
+#[derive(Debug, Clone)]
+pub struct JsonStorage {
+	file: PathBuf,
+}
+
+impl JsonStorage {
+	pub fn new(file: &Path) -> Self {
+		Self { file: file.to_path_buf() }
+	}
+
+	/// Read the data stored in `file` and upgrade it to [DATA_VERSION]
+	fn read_migrated(file: &Path) -> Result<(LocalData, MigrationReport)> {
+		let mut value: Value = serde_json::from_str(&fs::read_to_string(file)?)?;
+		let report = migrate(&mut value)?;
+		Ok((serde_json::from_value(value)?, report))
+	}
+
+	/// Read and deserialize the data stored in `file`, upgrading it if it uses an older layout
+	fn read(file: &Path) -> Result<LocalData> {
+		let (data, report) = Self::read_migrated(file)?;
+		if !report.is_empty() {
+			info!("Upgraded the data of {} from version {} to {}", file.display(), report.from, report.to);
+		}
+		Ok(data)
+	}
+
+	/// Restore the most recent valid backup of a corrupt file
+	fn recover(&self, reason: String) -> Result<LocalData> {
+		warn!("The data in {} is corrupt: {reason}", self.file.display());
+		// Replacing the file must not race a process saving it, or recovering it too
+		let _lock = LocalData::lock_unless_held(&self.file)?;
+		if let Ok(data) = Self::read(&self.file) {
+			debug!("{} was saved while waiting for the lock", self.file.display());
+			return Ok(data);
+		}
+		let Some((backup, mut data)) = (1..=BACKUPS)
+			.map(|n| LocalData::backup_file(&self.file, n))
+			.find_map(|backup| Self::read(&backup).ok().map(|data| (backup, data)))
+		else {
+			return Err(Error::CorruptData { file: self.file.clone(), reason });
+		};
+
+		warn!("Restoring {} from {}", self.file.display(), backup.display());
+		fs::rename(&self.file, LocalData::sibling(&self.file, ".corrupt"))?;
+		let tmp_file = LocalData::sibling(&self.file, &format!(".tmp.{}", process::id()));
+		fs::copy(&backup, &tmp_file)?;
+		fs::rename(&tmp_file, &self.file)?;
+		data.recovered_from = Some(backup);
+		Ok(data)
+	}
+
+	/// Shift the backups and back up the current file as the most recent one, keeping [BACKUPS] of them
+	fn rotate_backups(&self) -> Result<()> {
+		if !self.file.exists() {
+			return Ok(());
+		}
+		for n in (1..BACKUPS).rev() {
+			let backup = LocalData::backup_file(&self.file, n);
+			if backup.exists() {
+				fs::rename(&backup, LocalData::backup_file(&self.file, n + 1))?;
+			}
+		}
+		fs::copy(&self.file, LocalData::backup_file(&self.file, 1))?;
+		Ok(())
+	}
+}
+
+impl Storage for JsonStorage {
+	fn location(&self) -> String {
+		self.file.display().to_string()
+	}
+
+	fn file(&self) -> Option<&Path> {
+		Some(&self.file)
+	}
+
+	fn exists(&self) -> bool {
+		self.file.exists()
+	}
+
+	/// If the file is corrupt, the most recent backup that can be read is restored, see [LocalData::recovered_from].
+	/// The corrupt file is kept aside with the `.corrupt` extension.
+	fn load(&self) -> Result<LocalData> {
+		match Self::read(&self.file) {
+			Err(Error::Json(e)) => self.recover(e.to_string()),
+			Err(Error::Io(e)) if e.kind() == io::ErrorKind::InvalidData => self.recover(e.to_string()),
+			res => res,
+		}
+	}
+
+	fn save(&self, data: &LocalData) -> Result<()> {
+		let json = serde_json::to_string_pretty(data)?;
+		if let Some(dir) = self.file.parent() {
+			fs::create_dir_all(dir)?;
+		}
+
+		let tmp_file = LocalData::sibling(&self.file, &format!(".tmp.{}", process::id()));
+		let mut fs = File::create(&tmp_file)?;
+		fs.write_all(json.as_bytes())?;
+		fs.sync_all()?;
+		self.rotate_backups()?;
+		fs::rename(&tmp_file, &self.file)?;
+		Ok(())
+	}
+
+	fn migrate(&self, dry_run: bool) -> Result<MigrationReport> {
+		let (data, report) = Self::read_migrated(&self.file)?;
+		if !report.is_empty() && !dry_run {
+			self.save(&LocalData { version: DATA_VERSION, file: self.file.clone(), ..data })?;
+		}
+		Ok(report)
+	}
+}
+
+/// Keep the data in memory, for tests and apps embedding subrpc.
+///
+/// The data is stored serialized, as the other storages do, so a load returns a copy of the data saved last.
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+	data: Mutex<Option<Value>>,
+}
+
+impl MemoryStorage {
+	pub fn new() -> Self {
+		Self::default()
+	}
+}
+
+impl Storage for MemoryStorage {
+	fn location(&self) -> String {
+		format!("memory ({:p})", self)
+	}
+
+	fn exists(&self) -> bool {
+		self.data.lock().unwrap_or_else(PoisonError::into_inner).is_some()
+	}
+
+	fn load(&self) -> Result<LocalData> {
+		let data = self.data.lock().unwrap_or_else(PoisonError::into_inner).clone();
+		match data {
+			Some(mut data) => {
+				migrate(&mut data)?;
+				Ok(serde_json::from_value(data)?)
+			}
+			None => Ok(LocalData { file: PathBuf::new(), ..Default::default() }),
+		}
+	}
+
+	fn save(&self, data: &LocalData) -> Result<()> {
+		let data = serde_json::to_value(data)?;
+		*self.data.lock().unwrap_or_else(PoisonError::into_inner) = Some(data);
+		Ok(())
+	}
+
+	fn migrate(&self, dry_run: bool) -> Result<MigrationReport> {
+		let mut data = self.data.lock().unwrap_or_else(PoisonError::into_inner);
+		let Some(stored) = data.as_mut() else {
+			return Ok(MigrationReport { from: DATA_VERSION, to: DATA_VERSION, changes: vec![] });
+		};
+		let mut migrated = stored.clone();
+		let report = migrate(&mut migrated)?;
+		if !dry_run {
+			*stored = migrated;
+		}
+		Ok(report)
+	}
+}
+
+#[cfg(test)]
+mod test_storage {
+	use super::*;
+	use crate::{Endpoint, Registry};
+	use std::{collections::HashMap, env};
+
+	/// Data with one registry listing a Polkadot endpoint pinged `pings` times
+	fn sample_data(pings: usize) -> LocalData {
+		let mut endpoint = Endpoint::new("Parity", "wss://rpc.polkadot.io", vec![], vec!["dot".to_string()]);
+		(0..pings).for_each(|i| endpoint.stats.add(i % 2 == 0, Some(0.1)));
+		let mut reg = Registry::new("Parity", "http://localhost/parity.json");
+		reg.rpc_endpoints = HashMap::from([("Polkadot".to_string(), vec![endpoint])]);
+		LocalData { file: PathBuf::new(), ..Default::default() }.add_registry(reg)
+	}
+
+	/// Initialize, save and load data with `storage`
+	fn check_round_trip(storage: Arc<dyn Storage>) {
+		assert!(!storage.exists());
+		let data = LocalData::init_with(storage.clone(), false).unwrap();
+		assert!(data.registries.is_empty() && storage.exists());
+
+		let saved = LocalData { storage: Some(storage.clone()), ..sample_data(3) }.save().unwrap();
+		let loaded = LocalData::init_with(storage.clone(), false).unwrap();
+		assert_eq!(saved.registries, loaded.registries);
+		assert_eq!(
+			3,
+			storage.history("wss://rpc.polkadot.io", Local::now() - chrono::Duration::hours(1)).unwrap().len()
+		);
+
+		let loaded = loaded.remove_registry("Parity").unwrap().save().unwrap();
+		assert!(loaded.storage().load().unwrap().registries.is_empty());
+	}
+
+	#[test]
+	fn test_memory() {
+		check_round_trip(Arc::new(MemoryStorage::new()));
+
+		let storage = MemoryStorage::new();
+		assert!(storage.migrate(false).unwrap().is_empty());
+		let mut data = serde_json::to_value(sample_data(1)).unwrap();
+		data.as_object_mut().unwrap().remove("version");
+		*storage.data.lock().unwrap() = Some(data);
+		assert_eq!(0, storage.migrate(true).unwrap().from);
+		assert_eq!(DATA_VERSION, storage.load().unwrap().version);
+		assert_eq!(0, storage.migrate(false).unwrap().from);
+		assert!(storage.migrate(true).unwrap().is_empty());
+	}
+
+	#[test]
+	fn test_json() {
+		let file = env::temp_dir().join("subrpc_test_storage.json");
+		let _ = fs::remove_file(&file);
+		check_round_trip(StorageBackend::Json.open(&file).unwrap());
+		assert!(LocalData::backup_file(&file, 1).exists());
+	}
+
+	#[cfg(feature = "sqlite")]
+	#[test]
+	fn test_sqlite() {
+		let file = env::temp_dir().join("subrpc_test_storage.db");
+		let _ = fs::remove_file(&file);
+		check_round_trip(StorageBackend::Sqlite.open(&file).unwrap());
+
+		// The database keeps the samples dropped from the history of the endpoints
+		let capacity = crate::HISTORY_CAPACITY;
+		let storage: Arc<dyn Storage> = Arc::new(SqliteStorage::open(&file).unwrap());
+		let mut data = LocalData { storage: Some(storage.clone()), ..sample_data(capacity) }.save().unwrap();
+		let endpoint = &mut data.registries.get_mut("Parity").unwrap().rpc_endpoints.get_mut("Polkadot").unwrap()[0];
+		(0..10).for_each(|_| endpoint.stats.add(true, Some(0.2)));
+		data.save().unwrap();
+
+		let loaded = storage.load().unwrap();
+		assert_eq!(capacity, loaded.registries["Parity"].rpc_endpoints["Polkadot"][0].stats.history.len());
+		let since = Local::now() - chrono::Duration::hours(1);
+		assert_eq!(capacity + 10, storage.history("wss://rpc.polkadot.io", since).unwrap().len());
+	}
+
+	#[cfg(feature = "sqlite")]
+	#[test]
+	fn test_sqlite_migrate() {
+		let file = env::temp_dir().join("subrpc_test_storage_migrate.db");
+		let _ = fs::remove_file(&file);
+		let storage = SqliteStorage::open(&file).unwrap();
+		storage.save(&sample_data(1)).unwrap();
+		assert!(storage.migrate(true).unwrap().is_empty());
+
+		// Rows written by an older version, with a url that is not in its canonical form
+		let connection = rusqlite::Connection::open(&file).unwrap();
+		connection
+			.execute_batch(
+				"UPDATE meta SET value = '0' WHERE key = 'version';
+				UPDATE endpoints SET data = replace(data, 'wss://rpc.polkadot.io', 'WSS://rpc.polkadot.io:443');",
+			)
+			.unwrap();
+		let report = storage.migrate(true).unwrap();
+		assert_eq!((0, DATA_VERSION), (report.from, report.to));
+		assert!(report.changes.iter().any(|c| c.contains("stored the url as wss://rpc.polkadot.io")), "{report:#?}");
+		assert_eq!(0, storage.migrate(true).unwrap().from);
+
+		let loaded = storage.load().unwrap();
+		assert_eq!(DATA_VERSION, loaded.version);
+		assert_eq!("wss://rpc.polkadot.io", loaded.registries["Parity"].rpc_endpoints["Polkadot"][0].url.to_string());
+		storage.migrate(false).unwrap();
+		assert!(storage.migrate(true).unwrap().is_empty());
+
+		connection
+			.execute("UPDATE meta SET value = ?1 WHERE key = 'version'", [(DATA_VERSION + 1).to_string()])
+			.unwrap();
+		assert!(matches!(storage.load(), Err(Error::Migration(_))));
+	}
+
+	#[test]
+	fn test_backend() {
+		let config = crate::Config::parse(r#"storage = "memory""#).unwrap();
+		assert_eq!(StorageBackend::Memory, config.storage);
+		assert!(crate::Config::parse(r#"storage = "csv""#).is_err());
+
+		let storage = config.storage.open(Path::new("ignored")).unwrap();
+		assert_eq!(None, storage.file());
+		assert!(*storage != *StorageBackend::Memory.open(Path::new("ignored")).unwrap());
+
+		assert_eq!(Some(StorageBackend::Sqlite), StorageBackend::for_file(Path::new("/tmp/data.DB")));
+		assert_eq!(Some(StorageBackend::Json), StorageBackend::for_file(Path::new("data.json")));
+		assert_eq!(None, StorageBackend::for_file(Path::new("data")));
+	}
+}